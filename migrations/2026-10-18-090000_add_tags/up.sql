@@ -0,0 +1,9 @@
+CREATE TABLE `tags`(
+        `id` INTEGER NOT NULL PRIMARY KEY,
+        `name` TEXT NOT NULL UNIQUE
+);
+CREATE TABLE `todo_tags`(
+        `todo_id` INTEGER NOT NULL REFERENCES `todos`(`id`) ON DELETE CASCADE,
+        `tag_id` INTEGER NOT NULL REFERENCES `tags`(`id`) ON DELETE CASCADE,
+        PRIMARY KEY(`todo_id`, `tag_id`)
+);
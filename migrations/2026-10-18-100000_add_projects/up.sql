@@ -0,0 +1,9 @@
+CREATE TABLE `projects`(
+        `id` INTEGER NOT NULL PRIMARY KEY,
+        `name` TEXT NOT NULL UNIQUE,
+        `created` TIMESTAMPTZSQLITE NOT NULL,
+        `archived` TIMESTAMPTZSQLITE DEFAULT NULL
+);
+ALTER TABLE `todos`
+ADD COLUMN `project_id` INTEGER DEFAULT NULL REFERENCES `projects`(`id`)
+;
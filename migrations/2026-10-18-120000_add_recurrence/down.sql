@@ -0,0 +1,2 @@
+ALTER TABLE `todos` DROP COLUMN `template_id`;
+ALTER TABLE `todos` DROP COLUMN `recurrence`;
@@ -0,0 +1,7 @@
+ALTER TABLE `todos`
+ADD COLUMN `recurrence` TEXT DEFAULT NULL
+;
+
+ALTER TABLE `todos`
+ADD COLUMN `template_id` INTEGER DEFAULT NULL REFERENCES `todos`(`id`)
+;
@@ -0,0 +1,3 @@
+ALTER TABLE `todos`
+ADD COLUMN `parent_id` INTEGER DEFAULT NULL REFERENCES `todos`(`id`)
+;
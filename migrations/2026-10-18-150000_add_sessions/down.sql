@@ -0,0 +1,2 @@
+DROP INDEX `sessions_single_active`;
+DROP TABLE `sessions`;
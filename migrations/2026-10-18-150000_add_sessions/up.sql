@@ -0,0 +1,8 @@
+CREATE TABLE `sessions`(
+        `id` INTEGER NOT NULL PRIMARY KEY,
+        `todo_id` INTEGER NOT NULL REFERENCES `todos`(`id`) ON DELETE CASCADE,
+        `started` TIMESTAMPTZSQLITE NOT NULL,
+        `stopped` TIMESTAMPTZSQLITE DEFAULT NULL
+);
+-- Only a single session can be active at a time
+CREATE UNIQUE INDEX `sessions_single_active` ON `sessions`((1)) WHERE `stopped` IS NULL;
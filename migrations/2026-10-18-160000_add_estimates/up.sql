@@ -0,0 +1,3 @@
+ALTER TABLE `todos`
+ADD COLUMN `estimate` INTEGER DEFAULT NULL
+;
@@ -0,0 +1,18 @@
+-- Full-text index over titles and notes, the text itself stays in `todos`
+CREATE VIRTUAL TABLE `todos_search` USING fts5(
+        `title`,
+        `notes`,
+        content = `todos`,
+        content_rowid = `id`
+);
+INSERT INTO `todos_search`(`rowid`, `title`, `notes`) SELECT `id`, `title`, `notes` FROM `todos`;
+CREATE TRIGGER `todos_search_insert` AFTER INSERT ON `todos` BEGIN
+        INSERT INTO `todos_search`(`rowid`, `title`, `notes`) VALUES (new.`id`, new.`title`, new.`notes`);
+END;
+CREATE TRIGGER `todos_search_delete` AFTER DELETE ON `todos` BEGIN
+        INSERT INTO `todos_search`(`todos_search`, `rowid`, `title`, `notes`) VALUES ('delete', old.`id`, old.`title`, old.`notes`);
+END;
+CREATE TRIGGER `todos_search_update` AFTER UPDATE OF `title`, `notes` ON `todos` BEGIN
+        INSERT INTO `todos_search`(`todos_search`, `rowid`, `title`, `notes`) VALUES ('delete', old.`id`, old.`title`, old.`notes`);
+        INSERT INTO `todos_search`(`rowid`, `title`, `notes`) VALUES (new.`id`, new.`title`, new.`notes`);
+END;
@@ -0,0 +1,12 @@
+CREATE TABLE `views`(
+        `id` INTEGER NOT NULL PRIMARY KEY,
+        `name` TEXT NOT NULL UNIQUE,
+        -- open, completed or all
+        `status` TEXT NOT NULL,
+        -- JSON array of tag names
+        `tags` TEXT NOT NULL,
+        -- A `list --where` filter, kept as typed so that days like "friday" are resolved when used
+        `filter` TEXT DEFAULT NULL,
+        `sort` TEXT DEFAULT NULL,
+        `show_time` BOOLEAN NOT NULL
+);
@@ -0,0 +1,2 @@
+ALTER TABLE `views` DROP COLUMN `wide`;
+ALTER TABLE `views` DROP COLUMN `list_columns`;
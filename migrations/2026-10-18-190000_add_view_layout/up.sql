@@ -0,0 +1,7 @@
+ALTER TABLE `views`
+-- Comma separated `list --columns`
+ADD COLUMN `list_columns` TEXT DEFAULT NULL
+;
+ALTER TABLE `views`
+ADD COLUMN `wide` BOOLEAN NOT NULL DEFAULT 0
+;
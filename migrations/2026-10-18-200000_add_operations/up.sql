@@ -0,0 +1,9 @@
+CREATE TABLE `operations`(
+        `id` INTEGER NOT NULL PRIMARY KEY,
+        `created` TIMESTAMPTZSQLITE NOT NULL,
+        -- What was done, like "complete bl5kg"
+        `description` TEXT NOT NULL,
+        -- JSON array of the rows the operation changed, as they were before and after it
+        `changes` TEXT NOT NULL,
+        `undone` BOOLEAN NOT NULL DEFAULT 0
+);
@@ -1,10 +1,23 @@
 use crate::constants::{BIN, BIN_VERSION};
-use crate::models::NewTodo;
+use crate::filter::{Condition, Filter};
+use crate::models::{NewTodo, NewView, Operation, Priority, Session, Todos};
+use crate::output::{
+    print_many, print_one, todo_json, CompletedTodoJson, DeletedTodoJson, NextTodoJson,
+    OperationJson, OutputFormat, ProjectJson, SearchMatchJson, SessionJson, SessionsJson, TodoJson,
+    TodoJsonContext, ViewJson,
+};
+use crate::recurrence::Recurrence;
+use crate::sync::SyncChangeKind;
+use crate::urgency::{due_bucket, DueBucket};
+use crate::{Error, TodoStore};
 
-use chrono::{DateTime, Local, Utc};
-use clap::{Parser, Subcommand};
+use chrono::{DateTime, Days, Local, NaiveDate, TimeDelta, Utc};
+use clap::builder::PossibleValuesParser;
+use clap::{Args, CommandFactory, Parser, Subcommand, ValueEnum};
 use colored::{ColoredString, Colorize};
+use serde::Serialize;
 use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
 
 #[derive(Parser)]
 #[command(
@@ -19,6 +32,14 @@ use std::cmp::Ordering;
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+    /// how to print output, JSON includes a schema_version that changes when fields do
+    #[arg(
+        long,
+        global = true,
+        value_parser = format_names::<OutputFormat>(),
+        default_value = "table"
+    )]
+    format: String,
     #[arg(
         short = 'v',
         long,
@@ -36,35 +57,43 @@ enum Commands {
     #[clap(hide = true)]
     LocateDb,
     /// Add a new TODO
-    Add {
-        /// title of the new TODO
-        #[clap()]
-        title: Option<String>,
-        /// close the TODO right after creation
-        #[clap(short, long, action)]
-        complete: bool,
-        /// due date by which the TODO should be done
-        #[clap(short, long, action)]
-        due: Option<String>,
-    },
+    Add(AddArgs),
     /// List current TODOs, flag priority: all > completed > open (default).
     #[clap(visible_alias = "ls")]
     List {
-        /// show only completed TODOs
+        /// a view saved with `view save`, other flags are added to it
+        #[clap()]
+        view: Option<String>,
+        #[command(flatten)]
+        args: ListArgs,
+    },
+    /// Find TODOs by words in their title or notes, like: deploy* "release notes"
+    Search {
+        /// words have to match whole, unless they end with *, "quoted words" match as a phrase
+        #[arg(required = true)]
+        query: Vec<String>,
+        /// search only completed TODOs
         #[arg(long, action = clap::builder::ArgAction::SetTrue)]
         completed: bool,
-        /// show only open TODOs (this is the default behavior)
-        #[arg(long, action = clap::builder::ArgAction::SetTrue)]
-        open: bool,
-        /// show both completed and open TODOs, overwrites other flags
+        /// search both completed and open TODOs, overwrites other flags
         #[arg(long, action = clap::builder::ArgAction::SetTrue)]
         all: bool,
     },
+    /// Show the most urgent open TODOs and why they are urgent
+    Next {
+        /// how many TODOs to show
+        #[arg(short, default_value_t = 1)]
+        n: usize,
+    },
     #[clap(visible_alias = "rm")]
-    /// Remove a TODO
+    /// Remove TODOs
     Delete {
-        #[clap()]
-        id: String,
+        /// ids of the TODOs, - reads them from stdin
+        #[arg(required = true)]
+        ids: Vec<String>,
+        /// delete none of the TODOs if one of them can't be
+        #[arg(long, action)]
+        atomic: bool,
     },
     #[clap()]
     /// Show information about a TODO
@@ -79,24 +108,501 @@ enum Commands {
         id: String,
     },
     #[clap(visible_alias = "done")]
-    /// Complete a TODO
+    /// Complete TODOs
     Complete {
-        #[clap()]
-        id: String,
+        /// ids of the TODOs, - reads them from stdin
+        #[arg(required = true)]
+        ids: Vec<String>,
+        /// complete the TODO even if some of its subtasks are still open
+        #[clap(short, long, action)]
+        force: bool,
+        /// complete none of the TODOs if one of them can't be
+        #[arg(long, action)]
+        atomic: bool,
     },
     #[clap()]
-    /// Reopen a done TODO
+    /// Reopen done TODOs
     Reopen {
-        #[clap()]
-        id: String,
+        /// ids of the TODOs, - reads them from stdin
+        #[arg(required = true)]
+        ids: Vec<String>,
+        /// reopen none of the TODOs if one of them can't be
+        #[arg(long, action)]
+        atomic: bool,
     },
     /// Set the due time
     Due {
+        /// ids of the TODOs, - reads them from stdin, followed by a human readable description of
+        /// a time by which they should be done, like: "Monday 9am". If there are only ids the due
+        /// time will be removed
+        #[arg(required = true, value_name = "ID... [DUE_TEXT]")]
+        args: Vec<String>,
+        /// the due time, for when it could be mistaken for an id
+        #[arg(long, value_name = "DUE_TEXT")]
+        at: Option<String>,
+        /// remove the due time of every TODO given
+        #[arg(long, action, conflicts_with = "at")]
+        clear: bool,
+        /// change none of the TODOs if one of them can't be
+        #[arg(long, action)]
+        atomic: bool,
+    },
+    /// Set the priority
+    Priority {
+        #[clap()]
+        id: String,
+        /// One of: none, low, medium, high
+        level: Priority,
+    },
+    /// Set how long a TODO is expected to take
+    Estimate {
+        #[clap()]
+        id: String,
+        /// How long the TODO should take, like: "90m", "2h" or "1h30m". If not provided the
+        /// estimate will be removed
+        #[arg(value_parser = crate::report::parse_duration)]
+        estimate: Option<TimeDelta>,
+    },
+    /// Set how often a TODO repeats, completing it spawns the next occurrence
+    Recur {
+        #[clap()]
+        id: String,
+        /// How often the TODO repeats, like: "week", "every 3 days" or "FREQ=MONTHLY;INTERVAL=1".
+        /// If not provided the TODO stops repeating
+        every: Option<Recurrence>,
+    },
+    /// Start tracking time on a TODO, stops tracking the previous one
+    Start {
+        #[clap()]
+        id: String,
+    },
+    /// Stop tracking time
+    Stop,
+    /// Show what's being worked on right now
+    Current,
+    /// Sum up tracked time between two days, like for a timesheet
+    Report(ReportArgs),
+    /// Block a TODO until another one is completed
+    Block {
         #[clap()]
         id: String,
-        /// A human readable description of a time by which the TODO should be dune, like: "Monday
-        /// 9am". If not provided due time will be removed
-        due_text: Option<String>,
+        /// id of the TODO that has to be completed first
+        #[arg(long)]
+        on: String,
+    },
+    /// Remove a blocker of a TODO
+    Unblock {
+        #[clap()]
+        id: String,
+        /// id of the TODO that shouldn't block anymore
+        #[arg(long)]
+        on: String,
+    },
+    /// Add or remove tags of a TODO
+    Tag {
+        #[clap()]
+        id: String,
+        /// Tags prefixed with + (or nothing) are added, tags prefixed with - are removed, like:
+        /// "+infra -oncall"
+        #[arg(required = true, allow_hyphen_values = true)]
+        changes: Vec<String>,
+    },
+    /// Revert the last changes, like a deleted TODO
+    Undo {
+        /// how many changes to revert
+        #[arg(short, default_value_t = 1)]
+        n: usize,
+    },
+    /// Apply changes reverted by `undo` again
+    Redo {
+        /// how many changes to apply again
+        #[arg(short, default_value_t = 1)]
+        n: usize,
+    },
+    /// Write TODOs to stdout so they can be moved elsewhere
+    Export {
+        /// how to write the TODOs
+        #[arg(long, value_parser = format_names::<ExportFormat>())]
+        format: String,
+        /// export only completed TODOs
+        #[arg(long, action = clap::builder::ArgAction::SetTrue)]
+        completed: bool,
+        /// export both completed and open TODOs, overwrites other flags
+        #[arg(long, action = clap::builder::ArgAction::SetTrue)]
+        all: bool,
+    },
+    /// Add TODOs from a file, the format is taken from --format, --from or the file extension
+    Import {
+        #[clap()]
+        file: std::path::PathBuf,
+        /// how the file is written, taken from its extension when not given
+        #[arg(long, value_parser = format_names::<ImportFormat>())]
+        format: Option<String>,
+        /// the tool the file was exported from
+        #[arg(long, value_enum)]
+        from: Option<ImportSource>,
+        /// only show what would be imported
+        #[arg(long, action = clap::builder::ArgAction::SetTrue)]
+        dry_run: bool,
+    },
+    /// Sync TODOs both ways with a todo.txt file, creating it if needed
+    SyncTodotxt {
+        #[clap()]
+        path: std::path::PathBuf,
+    },
+    /// Manage projects that group TODOs
+    Project {
+        #[command(subcommand)]
+        command: ProjectCommands,
+    },
+    /// Manage views, `list` options saved under a name
+    View {
+        #[command(subcommand)]
+        command: ViewCommands,
+    },
+    /// Show or change defaults, like: config columns id,due,title
+    Config {
+        /// the setting to show or change, all of them are shown when not given
+        #[clap()]
+        key: Option<ConfigKey>,
+        /// the new value of the setting
+        #[arg(conflicts_with = "unset")]
+        value: Option<String>,
+        /// go back to the built-in default
+        #[arg(long, action = clap::builder::ArgAction::SetTrue, requires = "key")]
+        unset: bool,
+    },
+    /// `workingon <view>` is a shortcut for `workingon list <view>`
+    #[command(external_subcommand)]
+    External(Vec<String>),
+}
+
+#[derive(Args)]
+pub struct ListArgs {
+    /// show only completed TODOs
+    #[arg(long, action = clap::builder::ArgAction::SetTrue)]
+    completed: bool,
+    /// show only open TODOs (this is the default behavior)
+    #[arg(long, action = clap::builder::ArgAction::SetTrue)]
+    open: bool,
+    /// show both completed and open TODOs, overwrites other flags
+    #[arg(long, action = clap::builder::ArgAction::SetTrue)]
+    all: bool,
+    /// show only TODOs with this tag, can be given multiple times to require every tag
+    #[arg(short, long)]
+    tag: Vec<String>,
+    /// show only TODOs matching a filter, like: due<friday and tag:work and not title~"meeting".
+    /// Fields are status, tag, project, title, notes, created, due, completed, priority and
+    /// estimate, combined with and, or, not and parentheses
+    #[arg(long = "where", value_parser = crate::filter::check_filter)]
+    filter: Option<String>,
+    /// how to order the TODOs (default: due)
+    #[arg(long, value_enum)]
+    sort: Option<SortOrder>,
+    /// show how much time was tracked on each TODO
+    #[arg(long, action = clap::builder::ArgAction::SetTrue)]
+    time: bool,
+    /// which columns to show and in which order, like: id,due,priority,title,notes (default: set
+    /// with `config columns`, or id,created,due,priority,estimate,title)
+    #[arg(long, value_enum, value_delimiter = ',')]
+    columns: Vec<Column>,
+    /// show the first line of notes and don't cut long titles
+    #[arg(long, action = clap::builder::ArgAction::SetTrue)]
+    wide: bool,
+}
+
+impl ListArgs {
+    /// Which TODOs the flags ask for, `None` when none of them were given
+    fn status(&self) -> Option<&'static str> {
+        // Priority: --all > --completed > --open
+        if self.all {
+            Some("all")
+        } else if self.completed {
+            Some("completed")
+        } else if self.open {
+            Some("open")
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Args)]
+pub struct AddArgs {
+    /// title of the new TODO
+    #[clap()]
+    title: Option<String>,
+    /// close the TODO right after creation
+    #[clap(short, long, action)]
+    complete: bool,
+    /// due date by which the TODO should be done
+    #[clap(short, long, action)]
+    due: Option<String>,
+    /// tag to attach to the new TODO, can be given multiple times
+    #[clap(short, long, action)]
+    tag: Vec<String>,
+    /// name of the project the new TODO belongs to
+    #[clap(short, long, action)]
+    project: Option<String>,
+    /// how important the new TODO is: none, low, medium or high
+    #[clap(long, action)]
+    priority: Option<Priority>,
+    /// how often the TODO repeats once completed, like: "week", "every 3 days" or
+    /// "FREQ=MONTHLY;INTERVAL=1"
+    #[clap(long, action)]
+    every: Option<Recurrence>,
+    /// id of the TODO the new TODO is a subtask of
+    #[clap(long, action)]
+    parent: Option<String>,
+    /// how long the TODO is expected to take, like: "90m" or "2h"
+    #[clap(long, value_parser = crate::report::parse_duration)]
+    estimate: Option<TimeDelta>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum SortOrder {
+    /// by how soon TODOs are due, then by priority
+    Due,
+    /// by urgency score, like `next` does
+    Urgency,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Column {
+    Id,
+    Created,
+    Due,
+    /// when the TODO was completed
+    Completed,
+    Priority,
+    Estimate,
+    Title,
+    /// first line of the notes
+    Notes,
+    Tags,
+    Project,
+    /// time tracked on the TODO
+    Tracked,
+}
+
+const DEFAULT_COLUMNS: [Column; 6] = [
+    Column::Id,
+    Column::Created,
+    Column::Due,
+    Column::Priority,
+    Column::Estimate,
+    Column::Title,
+];
+
+/// Longer titles are cut with an ellipsis, unless the table is wide
+const TITLE_WIDTH: usize = 50;
+/// Longer notes are cut with an ellipsis, unless the table is wide
+const NOTES_WIDTH: usize = 40;
+
+/// Parses a comma separated list of columns, like "id,due,title"
+fn parse_columns(s: &str) -> Result<Vec<Column>, String> {
+    s.split(',')
+        .map(|name| {
+            Column::from_str(name.trim(), true).map_err(|_| {
+                let names: Vec<String> = Column::value_variants()
+                    .iter()
+                    .map(|column| column.to_possible_value().unwrap().get_name().to_string())
+                    .collect();
+                format!(
+                    "unknown column \"{}\", pick from {}",
+                    name.trim(),
+                    names.join(", ")
+                )
+            })
+        })
+        .collect()
+}
+
+fn format_columns(columns: &[Column]) -> String {
+    columns
+        .iter()
+        .map(|column| column.to_possible_value().unwrap().get_name().to_string())
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+/// How `list` and `search` lay out their table
+pub struct TableLayout {
+    columns: Vec<Column>,
+    /// Keep long titles and notes whole instead of cutting them
+    wide: bool,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ConfigKey {
+    /// columns `list` shows when --columns isn't given, like: id,due,priority,title
+    Columns,
+}
+
+#[derive(Args)]
+#[command(args_conflicts_with_subcommands = true)]
+pub struct ReportArgs {
+    #[command(subcommand)]
+    command: Option<ReportCommands>,
+    /// first day to report on, like: "monday", "yesterday" or "2026-10-01"
+    #[arg(long, default_value = "monday")]
+    since: String,
+    /// last day to report on, included in the report
+    #[arg(long, default_value = "today")]
+    until: String,
+    /// how to group tracked time
+    #[arg(long, value_enum, default_value_t = ReportBy::Day)]
+    by: ReportBy,
+    /// how to print the report
+    #[arg(
+        long,
+        global = true,
+        value_parser = format_names::<ReportFormat>(),
+        default_value = "table"
+    )]
+    format: String,
+}
+
+#[derive(Subcommand)]
+enum ReportCommands {
+    /// Summarize how far off estimates were for completed TODOs with tracked time
+    Accuracy,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    /// comma separated values
+    Csv,
+    /// todo.txt lines
+    Todotxt,
+    /// an iCalendar with a VTODO per TODO
+    Ics,
+    /// a checklist
+    Markdown,
+    /// org-mode headlines
+    Org,
+    /// a single JSON object
+    Json,
+    /// one JSON object per line
+    Jsonl,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ImportFormat {
+    /// comma separated values, with a header like the one `export` writes
+    Csv,
+    /// todo.txt lines
+    Todotxt,
+    /// an iCalendar with VTODOs
+    Ics,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ReportFormat {
+    /// a human readable table
+    Table,
+    /// comma separated values, for spreadsheets
+    Csv,
+    /// a single JSON object
+    Json,
+    /// one JSON object per line
+    Jsonl,
+}
+
+/// clap gives the value of a global arg to a subcommand arg with the same id, whatever its type,
+/// so `--format` is parsed as the name of a format. That way `export`, `import` and `report`
+/// take formats of their own, and every other command only the ones of [`OutputFormat`].
+fn format_names<T: ValueEnum>() -> PossibleValuesParser {
+    PossibleValuesParser::new(T::value_variants().iter().filter_map(T::to_possible_value))
+}
+
+/// The format a `--format` name stands for, `None` when it's one of another command
+fn parse_format<T: ValueEnum>(name: &str) -> Option<T> {
+    T::from_str(name, true).ok()
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ImportSource {
+    /// the JSON printed by `task export`, deleted tasks and recurring templates are skipped
+    Taskwarrior,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum ReportBy {
+    Day,
+    /// by week, starting on Monday
+    Week,
+    Todo,
+    Project,
+    /// a TODO with multiple tags counts towards each of them
+    Tag,
+}
+
+#[derive(Serialize)]
+struct ReportRow {
+    key: String,
+    title: Option<String>,
+    seconds: i64,
+    hours: f64,
+}
+
+impl ReportRow {
+    fn new(key: String, title: Option<String>, duration: TimeDelta) -> Self {
+        ReportRow {
+            key,
+            title,
+            seconds: duration.num_seconds(),
+            hours: to_hours(duration),
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum ProjectCommands {
+    /// Add a new project
+    Add {
+        #[clap()]
+        name: String,
+    },
+    /// List projects with their number of open TODOs
+    #[clap(visible_alias = "ls")]
+    List {
+        /// show archived projects too
+        #[arg(long, action = clap::builder::ArgAction::SetTrue)]
+        all: bool,
+    },
+    /// Rename a project
+    Rename {
+        #[clap()]
+        name: String,
+        #[clap()]
+        new_name: String,
+    },
+    /// Archive a project, no new TODOs can be added to it afterwards
+    Archive {
+        #[clap()]
+        name: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum ViewCommands {
+    /// Save `list` options under a name, replacing the view with that name if there's one, like:
+    /// view save today --where "due<tomorrow" --sort urgency
+    Save {
+        #[clap()]
+        name: String,
+        #[command(flatten)]
+        args: ListArgs,
+    },
+    /// List saved views
+    #[clap(visible_alias = "ls")]
+    List,
+    /// Delete a view
+    #[clap(visible_alias = "rm")]
+    Delete {
+        #[clap()]
+        name: String,
     },
 }
 
@@ -106,58 +612,478 @@ fn get_version_str() -> String {
 
 // TODO: make this private?
 pub fn run_cli() {
-    let cli = Cli::parse();
+    if let Err(e) = run(Cli::parse()) {
+        eprintln!("{}", e);
+        std::process::exit(exit_code(&e));
+    }
+}
 
+/// Every kind of error exits with its own code, 2 is for bad arguments like clap uses. A batch
+/// exits with the code of its first failure.
+fn exit_code(error: &Error) -> i32 {
+    match error {
+        Error::Refused(_) => 1,
+        Error::Usage(_) => 2,
+        Error::NotFound { .. } => 3,
+        Error::InvalidId(_) => 4,
+        Error::BadDate(_) => 5,
+        Error::Conflict(_) => 6,
+        Error::Editor(_) => 7,
+        Error::Io { .. } => 8,
+        Error::Db { .. } => 9,
+        Error::Batch { failed, .. } => failed.first().map_or(1, exit_code),
+    }
+}
+
+/// An argument error found after parsing, printed the way clap prints its own
+fn usage_error(e: clap::Error) -> Error {
+    Error::Usage(e.to_string().trim_end().to_string())
+}
+
+fn run(cli: Cli) -> Result<(), Error> {
+    // export, import and report parse --format themselves
+    let format = parse_format(&cli.format).unwrap_or(OutputFormat::Table);
     match cli.command {
         Commands::Version => {
-            println!("{} {}", BIN, get_version_str());
+            if format.is_json() {
+                print_one(&serde_json::json!({ "version": BIN_VERSION }));
+            } else {
+                println!("{} {}", BIN, get_version_str());
+            }
         }
         Commands::LocateDb => {
-            println!("{}", crate::get_db_file().display());
+            if format.is_json() {
+                print_one(&serde_json::json!({ "path": crate::get_db_file()? }));
+            } else {
+                println!("{}", crate::get_db_file()?.display());
+            }
         }
-        Commands::Add {
-            title,
-            complete,
-            due,
-        } => {
-            add_todo(title, complete, due);
+        Commands::Add(args) => {
+            add_todo(args, format)?;
         }
-        Commands::List {
-            all,
+        Commands::List { view, args } => {
+            list_view(view.as_deref(), args, format)?;
+        }
+        Commands::Search {
+            query,
             completed,
-            open: _,
+            all,
         } => {
-            // Priority: --all > --completed > default (--open)
-            if all {
-                // Show all TODOs
-                list_todos(Some(false));
+            let show_completed = if all {
+                Some(false)
             } else if completed {
-                // Show only completed TODOs
-                list_todos(Some(true));
+                Some(true)
             } else {
-                // Default: show open (uncompleted) TODOs
-                list_todos(None);
-            }
+                None
+            };
+            search_todos(&query.join(" "), show_completed, format)?;
+        }
+        Commands::Next { n } => {
+            next_todos(n, format)?;
         }
-        Commands::Delete { id } => {
-            delete_todo(&id.to_string());
+        Commands::Delete { ids, atomic } => {
+            delete_todos(&read_ids(ids)?, atomic, format)?;
         }
         Commands::Show { id } => {
-            show_todo(&id.to_string());
+            show_todo(&id.to_string(), format)?;
         }
         Commands::Edit { id } => {
-            edit_todo(id.to_string());
+            edit_todo(id.to_string(), format)?;
+        }
+        Commands::Complete { ids, force, atomic } => {
+            complete_todos(&read_ids(ids)?, force, atomic, format)?;
+        }
+        Commands::Reopen { ids, atomic } => {
+            reopen_todos(&read_ids(ids)?, atomic, format)?;
+        }
+        Commands::Due {
+            mut args,
+            at,
+            clear,
+            atomic,
+        } => {
+            // Without --at the last argument is the due time, unless it's the only one or an id
+            let due_text = match at {
+                Some(at) => Some(at),
+                None if clear || args.len() == 1 => None,
+                None if is_todo_id(args.last().unwrap())? => None,
+                None => args.pop(),
+            };
+            set_due_todos(&read_ids(args)?, due_text.as_deref(), atomic, format)?;
+        }
+        Commands::Priority { id, level } => {
+            set_priority_todo(&id, level, format)?;
+        }
+        Commands::Estimate { id, estimate } => {
+            set_estimate_todo(&id, estimate, format)?;
+        }
+        Commands::Recur { id, every } => {
+            set_recurrence_todo(&id, every, format)?;
+        }
+        Commands::Start { id } => {
+            start_todo(&id, format)?;
         }
-        Commands::Complete { id } => {
-            complete_todo(&id);
+        Commands::Stop => {
+            stop_todo(format)?;
         }
-        Commands::Reopen { id } => {
-            reopen_todo(&id);
+        Commands::Current => {
+            current_todo(format)?;
+        }
+        Commands::Report(args) => {
+            let report_format = parse_format(&args.format).unwrap_or(ReportFormat::Table);
+            report(args, report_format)?;
+        }
+        Commands::Block { id, on } => {
+            crate::add_dependency(&id, &on)?;
+            if format.is_json() {
+                print_one(&todo_json(&id)?);
+            } else {
+                println!("{} is blocked by {}", id.yellow(), on.yellow());
+            }
+        }
+        Commands::Unblock { id, on } => {
+            crate::remove_dependency(&id, &on)?;
+            if format.is_json() {
+                print_one(&todo_json(&id)?);
+            } else {
+                println!("{} is no longer blocked by {}", id.yellow(), on.yellow());
+            }
+        }
+        Commands::Tag { id, changes } => {
+            tag_todo(&id, &changes, format)?;
+        }
+        Commands::Undo { n } => {
+            print_operations(&crate::undo(n)?, "Undid", "undo", format);
+        }
+        Commands::Redo { n } => {
+            print_operations(&crate::redo(n)?, "Redid", "redo", format);
+        }
+        Commands::Export {
+            format: export_format,
+            completed,
+            all,
+        } => {
+            // Same priority as list: --all > --completed > open
+            let show_completed = if all {
+                Some(false)
+            } else if completed {
+                Some(true)
+            } else {
+                None
+            };
+            // clap only accepts the names of export formats
+            let export_format = parse_format(&export_format).unwrap_or(ExportFormat::Json);
+            export_todos(show_completed, export_format)?;
+        }
+        Commands::Import {
+            file,
+            format: import_format,
+            from,
+            dry_run,
+        } => {
+            // A global --format like json ends up here too, the extension is used then
+            let import_format = import_format.as_deref().and_then(parse_format);
+            import_todos(&file, import_format, from, dry_run)?;
+        }
+        Commands::SyncTodotxt { path } => {
+            sync_todotxt(&path, format)?;
+        }
+        Commands::Project { command } => match command {
+            ProjectCommands::Add { name } => {
+                let project = crate::add_project(&name)?;
+                if format.is_json() {
+                    print_one(&ProjectJson::new(project, 0));
+                } else {
+                    println!("{} created", name.yellow());
+                }
+            }
+            ProjectCommands::List { all } => {
+                list_projects(all, format)?;
+            }
+            ProjectCommands::Rename { name, new_name } => {
+                crate::rename_project(&name, &new_name)?;
+                if format.is_json() {
+                    print_project_json(&new_name)?;
+                } else {
+                    println!("{} renamed to {}", name.yellow(), new_name.yellow());
+                }
+            }
+            ProjectCommands::Archive { name } => {
+                crate::archive_project(&name)?;
+                if format.is_json() {
+                    print_project_json(&name)?;
+                } else {
+                    println!("{} archived", name.yellow());
+                }
+            }
+        },
+        Commands::View { command } => match command {
+            ViewCommands::Save { name, args } => {
+                save_view(&name, args, format)?;
+            }
+            ViewCommands::List => {
+                list_views(format)?;
+            }
+            ViewCommands::Delete { name } => {
+                if !crate::delete_view(&name)? {
+                    return Err(Error::NotFound { kind: "view", name });
+                }
+                if format.is_json() {
+                    print_one(&serde_json::json!({ "name": name, "deleted": true }));
+                } else {
+                    println!("{} deleted", name.yellow());
+                }
+            }
+        },
+        Commands::Config { key, value, unset } => {
+            configure(key, value, unset, format)?;
+        }
+        Commands::External(args) => {
+            let name = &args[0];
+            if crate::get_view(name)?.is_none() {
+                return Err(usage_error(Cli::command().error(
+                    clap::error::ErrorKind::InvalidSubcommand,
+                    format!("unrecognized subcommand '{}'", name),
+                )));
+            }
+            // Parse again as `list <view>`, keeping flags given before and after the view name
+            let mut argv: Vec<String> = std::env::args().collect();
+            let position = argv
+                .iter()
+                .skip(1)
+                .position(|arg| arg == name)
+                .ok_or_else(|| Error::Usage(format!("{} isn't in the arguments", name)))?
+                + 1;
+            argv.insert(position, "list".to_string());
+            return match Cli::try_parse_from(argv) {
+                Ok(cli) => run(cli),
+                // --help and --version aren't errors
+                Err(e) if !e.use_stderr() => e.print().map_err(Error::io("stdout")),
+                Err(e) => Err(usage_error(e)),
+            };
+        }
+    }
+    Ok(())
+}
+
+fn list_view(name: Option<&str>, args: ListArgs, format: OutputFormat) -> Result<(), Error> {
+    let view = match name {
+        Some(name) => Some(crate::get_view(name)?.ok_or_else(|| Error::NotFound {
+            kind: "view",
+            name: name.to_string(),
+        })?),
+        None => None,
+    };
+    let status = args
+        .status()
+        .or(view.as_ref().map(|view| view.status.as_str()))
+        .unwrap_or("open");
+    let show_completed = match status {
+        "all" => Some(false),
+        "completed" => Some(true),
+        _ => None,
+    };
+    let mut tags = view
+        .as_ref()
+        .map(|view| view.tag_names())
+        .transpose()?
+        .unwrap_or_default();
+    tags.extend(args.tag);
+    let filter = view
+        .as_ref()
+        .and_then(|view| view.filter.clone())
+        .into_iter()
+        .chain(args.filter)
+        .map(|filter| {
+            crate::filter::parse_filter(&filter)
+                .map_err(|e| Error::Usage(format!("The filter \"{}\" doesn't work: {}", filter, e)))
+        })
+        .collect::<Result<Vec<_>, _>>()?
+        .into_iter()
+        .reduce(Filter::and);
+    let sort = args
+        .sort
+        .or(view
+            .as_ref()
+            .and_then(|view| view.sort.as_deref())
+            .and_then(|sort| SortOrder::from_str(sort, true).ok()))
+        .unwrap_or(SortOrder::Due);
+    // Columns come from the flag, then the view, then the config
+    let mut columns = if !args.columns.is_empty() {
+        args.columns
+    } else if let Some(columns) = view.as_ref().and_then(|view| view.list_columns.as_deref()) {
+        stored_columns(columns, &format!("view {}", view.as_ref().unwrap().name))?
+    } else if let Some(columns) = crate::config::load_config()?.columns {
+        stored_columns(&columns, "config")?
+    } else if show_completed.is_some() {
+        let mut columns = DEFAULT_COLUMNS.to_vec();
+        columns.insert(3, Column::Completed);
+        columns
+    } else {
+        DEFAULT_COLUMNS.to_vec()
+    };
+    let show_time = args.time || view.as_ref().is_some_and(|view| view.show_time);
+    if show_time && !columns.contains(&Column::Tracked) {
+        columns.push(Column::Tracked);
+    }
+    let wide = args.wide || view.as_ref().is_some_and(|view| view.wide);
+    if wide && !columns.contains(&Column::Notes) {
+        columns.push(Column::Notes);
+    }
+    let layout = TableLayout { columns, wide };
+    list_todos(show_completed, &tags, filter, sort, &layout, format)?;
+    Ok(())
+}
+
+/// Parses columns saved in a view or the config, `source` says which one for the error
+fn stored_columns(columns: &str, source: &str) -> Result<Vec<Column>, Error> {
+    parse_columns(columns).map_err(|e| {
+        Error::Usage(format!(
+            "The columns \"{}\" of {} don't work: {}",
+            columns, source, e
+        ))
+    })
+}
+
+fn save_view(name: &str, args: ListArgs, format: OutputFormat) -> Result<(), Error> {
+    // Views can be used as `workingon <view>`, so they can't shadow a command
+    if name == "help" || Cli::command().find_subcommand(name).is_some() {
+        return Err(Error::Refused(format!(
+            "{} is a command, views need a different name",
+            name.yellow()
+        )));
+    }
+    let existed = crate::get_view(name)?.is_some();
+    let sort = args
+        .sort
+        .map(|sort| sort.to_possible_value().unwrap().get_name().to_string());
+    let columns = Some(format_columns(&args.columns)).filter(|columns| !columns.is_empty());
+    let view = crate::save_view(&NewView {
+        name,
+        status: args.status().unwrap_or("open"),
+        tags: serde_json::to_string(&args.tag).map_err(|e| Error::Db {
+            context: "View tags couldn't be saved".to_string(),
+            source: Box::new(e),
+        })?,
+        filter: args.filter.as_deref(),
+        sort: sort.as_deref(),
+        show_time: args.time,
+        list_columns: columns.as_deref(),
+        wide: args.wide,
+    })?;
+    if format.is_json() {
+        print_one(&ViewJson::new(view)?);
+    } else if existed {
+        println!("{} updated, see it with `{} {}`", name.yellow(), BIN, name);
+    } else {
+        println!("{} saved, see it with `{} {}`", name.yellow(), BIN, name);
+    }
+    Ok(())
+}
+
+fn list_views(format: OutputFormat) -> Result<(), Error> {
+    let views = crate::get_views()?;
+    if format.is_json() {
+        let views = views
+            .into_iter()
+            .map(ViewJson::new)
+            .collect::<Result<Vec<_>, _>>()?;
+        print_many(format, "views", &views);
+        return Ok(());
+    }
+    if views.is_empty() {
+        println!(
+            "There are no views currently, save one with `{} view save`",
+            BIN
+        );
+        return Ok(());
+    }
+    let mut table = comfy_table::Table::new();
+    table.load_preset(comfy_table::presets::NOTHING);
+    table.set_header(vec![
+        "name", "shows", "tags", "where", "sort", "columns", "time", "wide",
+    ]);
+    for view in views {
+        table.add_row(vec![
+            comfy_table::Cell::new(view.name.yellow().to_string()),
+            comfy_table::Cell::new(&view.status),
+            comfy_table::Cell::new(view.tag_names()?.join(", ")),
+            comfy_table::Cell::new(view.filter.clone().unwrap_or_default()),
+            comfy_table::Cell::new(view.sort.clone().unwrap_or_default()),
+            comfy_table::Cell::new(view.list_columns.clone().unwrap_or_default()),
+            comfy_table::Cell::new(if view.show_time { "yes" } else { "" }),
+            comfy_table::Cell::new(if view.wide { "yes" } else { "" }),
+        ]);
+    }
+    println!("{table}");
+    Ok(())
+}
+
+fn configure(
+    key: Option<ConfigKey>,
+    value: Option<String>,
+    unset: bool,
+    format: OutputFormat,
+) -> Result<(), Error> {
+    let mut config = crate::config::load_config()?;
+    let Some(key) = key else {
+        if format.is_json() {
+            print_one(&config);
+        } else if let Some(columns) = &config.columns {
+            println!("columns = {}", columns);
+        } else {
+            println!(
+                "Nothing is configured currently, see `{} config --help`",
+                BIN
+            );
         }
-        Commands::Due { id, due_text } => {
-            set_due_todo(&id, due_text); // TODO: borrow due_text instead
+        return Ok(());
+    };
+    let name = key.to_possible_value().unwrap().get_name().to_string();
+    let setting = match key {
+        ConfigKey::Columns => &mut config.columns,
+    };
+    let changed = if unset {
+        *setting = None;
+        true
+    } else if let Some(value) = value {
+        let value = match key {
+            ConfigKey::Columns => parse_columns(&value).map(|columns| format_columns(&columns)),
         }
+        .map_err(|e| Error::Usage(format!("{} can't be set to \"{}\": {}", name, value, e)))?;
+        *setting = Some(value);
+        true
+    } else {
+        false
+    };
+    let current = setting.clone();
+    if changed {
+        crate::config::save_config(&config)?;
+    }
+    if format.is_json() {
+        print_one(&serde_json::json!({ "key": name, "value": current }));
+        return Ok(());
     }
+    match (changed, current) {
+        (true, Some(value)) => println!("{} set to {}", name.yellow(), value),
+        (true, None) => println!("{} unset", name.yellow()),
+        (false, Some(value)) => println!("{}", value),
+        (false, None) => println!(
+            "{} isn't set, the default is {}",
+            name.yellow(),
+            format_columns(&DEFAULT_COLUMNS)
+        ),
+    }
+    Ok(())
+}
+
+fn print_project_json(name: &str) -> Result<(), Error> {
+    let project = crate::get_project(name)?;
+    let open_todos = *crate::count_open_todos_by_project()?
+        .get(&Some(project.id))
+        .unwrap_or(&0);
+    print_one(&ProjectJson::new(project, open_todos));
+    Ok(())
 }
 
 fn format_datetime(ts: DateTime<Utc>, precise: bool) -> String {
@@ -169,23 +1095,36 @@ fn format_datetime(ts: DateTime<Utc>, precise: bool) -> String {
     format!("{}", local_tz.format("%d/%m/%Y %H:%M"))
 }
 
+fn format_duration(duration: TimeDelta) -> String {
+    let minutes = duration.num_minutes();
+    if minutes < 1 {
+        format!("{}s", duration.num_seconds())
+    } else if minutes < 60 {
+        format!("{}m", minutes)
+    } else {
+        format!("{}h {:02}m", minutes / 60, minutes % 60)
+    }
+}
+
 fn format_duetime(ts: DateTime<Utc>, precise: bool) -> ColoredString {
     let duetime = format_datetime(ts, precise);
-    let offset = ts - Utc::now();
-    if offset.num_seconds() < 0 {
-        duetime.red()
-    } else if offset.num_seconds() < 60 * 60 * 24 {
-        // A day
+    match due_bucket(Some(ts), Utc::now()) {
+        DueBucket::Overdue => duetime.red(),
         // TODO: could this be orange and then next be yellow
-        duetime.yellow()
-    } else if offset.num_seconds() < 60 * 60 * 24 * 3 {
-        // 3 days
-        duetime.magenta()
-    } else if offset.num_seconds() < 60 * 60 * 24 * 7 {
-        // 3 days
-        duetime.green()
-    } else {
-        duetime.into()
+        DueBucket::WithinADay => duetime.yellow(),
+        DueBucket::WithinThreeDays => duetime.magenta(),
+        DueBucket::WithinAWeek => duetime.green(),
+        DueBucket::Later | DueBucket::NoDueDate => duetime.into(),
+    }
+}
+
+fn format_priority(level: i32) -> ColoredString {
+    let priority = Priority::from_level(level);
+    match priority {
+        Priority::Unset => "".into(),
+        Priority::Low => priority.to_string().blue(),
+        Priority::Medium => priority.to_string().yellow(),
+        Priority::High => priority.to_string().red(),
     }
 }
 
@@ -207,103 +1146,1002 @@ fn format_datetime_or_else(ts: Option<DateTime<Utc>>, else_item: String, precise
     }
 }
 
-fn show_todo(id: &String) {
-    let found_todo = crate::get_todo(id);
+fn show_todo(id: &str, format: OutputFormat) -> Result<(), Error> {
+    if format.is_json() {
+        print_one(&todo_json(id)?);
+        return Ok(());
+    }
+    let found_todo = crate::get_todo(id)?;
     let created_str: String = format_datetime(found_todo.created, false);
     let completed_str: String =
         format_datetime_or_else(found_todo.completed, "not yet".to_string(), false);
     let due_str = format_duetime_or_else(found_todo.due, "no due date".to_string(), false);
+    let tags_str = format_tags(&crate::get_todo_tags(id)?);
+    let priority_str = Priority::from_level(found_todo.priority);
+    let recurrence_str = format_recurrence(&found_todo.recurrence);
+    let estimate_str = format_estimate(found_todo.estimate_duration());
     println!(
-        "{}\n{}\nIt was created: {}\nIt was completed: {}\nIt's due on: {}",
-        found_todo.title, found_todo.notes, created_str, completed_str, due_str,
+        "{}\n{}\nIt was created: {}\nIt was completed: {}\nIt's due on: {}\nIt's tagged with: {}\nIts priority is: {}\nIt's estimated at: {}\nIt repeats: {}",
+        found_todo.title,
+        found_todo.notes,
+        created_str,
+        completed_str,
+        due_str,
+        tags_str,
+        priority_str,
+        estimate_str,
+        recurrence_str,
     );
+    if let Some(parent_id) = found_todo.parent_id {
+        println!(
+            "It's a subtask of: {}",
+            crate::encode_id(parent_id.try_into().unwrap()).yellow()
+        );
+    }
+    let open_blockers: Vec<String> = crate::get_blockers(id)?
+        .into_iter()
+        .filter(|blocker| blocker.completed.is_none())
+        .map(|blocker| {
+            format!(
+                "{} {}",
+                crate::encode_id(blocker.id.try_into().unwrap()).yellow(),
+                blocker.title
+            )
+        })
+        .collect();
+    if !open_blockers.is_empty() {
+        println!("It's blocked by:");
+        for blocker in open_blockers {
+            println!("  {}", blocker);
+        }
+    }
+    let now = Utc::now();
+    let sessions = crate::get_sessions(id)?;
+    if !sessions.is_empty() {
+        let actual = sessions.iter().fold(TimeDelta::zero(), |total, session| {
+            total + session.duration(now)
+        });
+        match found_todo.estimate_duration() {
+            Some(estimate) => println!(
+                "It was worked on for: {} of {} estimated ({})",
+                format_duration(actual),
+                format_duration(estimate),
+                format_estimate_error(actual, estimate)
+            ),
+            None => println!("It was worked on for: {}", format_duration(actual)),
+        }
+    }
+    let subtasks = crate::get_subtasks(id)?;
+    if !subtasks.is_empty() {
+        println!(
+            "Its subtasks are done: {}/{}",
+            subtasks
+                .iter()
+                .filter(|subtask| subtask.completed.is_some())
+                .count(),
+            subtasks.len()
+        );
+    }
+    if let Some(template_id) = found_todo.template_id {
+        println!(
+            "It's an occurrence of: {}",
+            crate::encode_id(template_id.try_into().unwrap()).yellow()
+        );
+    }
+    Ok(())
 }
 
-pub fn edit_todo(id: String) {
-    let found_todo = crate::get_todo(&id);
-    let p_buff = crate::get_todoeditmsg_file();
-    let fp = p_buff.as_path();
-    let (t, n) = crate::create_temp_todo_file_open_and_then_read_remove_process(
-        fp,
-        found_todo.title.clone(),
-        found_todo.notes.clone(),
-    );
-    crate::set_todo_title(&id, &t);
-    crate::set_todo_notes(&id, &n);
-    println!("{} updated", id.yellow());
+/// Like "25% over" or "10% under", red when the estimate was exceeded
+fn format_estimate_error(actual: TimeDelta, estimate: TimeDelta) -> ColoredString {
+    let error = crate::report::estimate_error(actual, estimate);
+    let percent = (error.abs() * 100.0).round();
+    if error > 0.0 {
+        format!("{}% over", percent).red()
+    } else {
+        format!("{}% under", percent).green()
+    }
 }
 
-fn complete_todo(id: &String) {
-    crate::complete_todo(id, None);
-    println!(
-        "{} completed, if this was a mistake reopen with `{} reopen {}`",
+fn format_tags(tags: &[String]) -> String {
+    if tags.is_empty() {
+        return "no tags".to_string();
+    }
+    tags.join(", ")
+}
+
+fn tag_todo(id: &str, changes: &[String], format: OutputFormat) -> Result<(), Error> {
+    let mut to_add: Vec<String> = Vec::new();
+    let mut to_remove: Vec<String> = Vec::new();
+    for change in changes {
+        if let Some(tag) = change.strip_prefix('-') {
+            to_remove.push(tag.to_string());
+        } else {
+            to_add.push(change.strip_prefix('+').unwrap_or(change).to_string());
+        }
+    }
+    TodoStore::open()?.operation(format!("tag {}", id), |store| {
+        store.add_tags(id, &to_add)?;
+        store.remove_tags(id, &to_remove)
+    })?;
+    if format.is_json() {
+        print_one(&todo_json(id)?);
+        return Ok(());
+    }
+    println!(
+        "{} is tagged with: {}",
         id.yellow(),
-        BIN,
-        id
+        format_tags(&crate::get_todo_tags(id)?)
+    );
+    Ok(())
+}
+
+pub fn edit_todo(id: String, format: OutputFormat) -> Result<(), Error> {
+    let found_todo = crate::get_todo(&id)?;
+    let p_buff = crate::get_todoeditmsg_file()?;
+    let fp = p_buff.as_path();
+    let (t, n) = crate::create_temp_todo_file_open_and_then_read_remove_process(
+        fp,
+        found_todo.title.clone(),
+        found_todo.notes.clone(),
+    )?;
+    TodoStore::open()?.operation(format!("edit {}", id), |store| {
+        store.set_todo_title(&id, &t)?;
+        store.set_todo_notes(&id, &n)
+    })?;
+    if format.is_json() {
+        print_one(&todo_json(&id)?);
+    } else {
+        println!("{} updated", id.yellow());
+    }
+    Ok(())
+}
+
+/// Whether `arg` is the id of an existing TODO, written the way workingon shows it. A `-` counts,
+/// since it stands for ids read from stdin.
+fn is_todo_id(arg: &str) -> Result<bool, Error> {
+    if arg == "-" {
+        return Ok(true);
+    }
+    let Ok(decoded_id) = crate::decode_id(arg) else {
+        return Ok(false);
+    };
+    if crate::encode_id(decoded_id.try_into().unwrap()) != arg {
+        return Ok(false);
+    }
+    match crate::get_todo(arg) {
+        Ok(_) => Ok(true),
+        Err(Error::NotFound { .. }) => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Ids given to a batch command, a `-` reads more from stdin, the first word of each line
+fn read_ids(ids: Vec<String>) -> Result<Vec<String>, Error> {
+    let mut read = Vec::new();
+    for id in ids {
+        if id != "-" {
+            read.push(id);
+            continue;
+        }
+        for line in std::io::stdin().lines() {
+            if let Some(stdin_id) = line.map_err(Error::io("stdin"))?.split_whitespace().next() {
+                read.push(stdin_id.to_string());
+            }
+        }
+    }
+    Ok(read)
+}
+
+/// What a batch command changed, see [`change_each`]
+struct Batch<T> {
+    store: TodoStore,
+    changed: Vec<(String, T)>,
+    failed: Vec<Error>,
+}
+
+/// Applies `change` to every id in a single transaction, undone at once as `command`. An id
+/// that fails is reported and the others are still changed, unless `atomic` is set, then the
+/// first failure is returned without changing anything.
+fn change_each<T>(
+    command: &str,
+    ids: &[String],
+    atomic: bool,
+    mut change: impl FnMut(&mut TodoStore, &str) -> Result<T, Error>,
+) -> Result<Batch<T>, Error> {
+    let mut store = TodoStore::open()?;
+    let mut failed = Vec::new();
+    let changed = store.operation(format!("{} {}", command, ids.join(" ")), |store| {
+        let mut changed = Vec::new();
+        for id in ids {
+            // Each id gets its own savepoint, so that one failing halfway leaves nothing behind
+            match store.transaction(|store| change(store, id)) {
+                Ok(value) => changed.push((id.clone(), value)),
+                Err(failure) if atomic => return Err(failure),
+                Err(failure) => failed.push(failure),
+            }
+        }
+        Ok(changed)
+    });
+    match changed {
+        Ok(changed) => Ok(Batch {
+            store,
+            changed,
+            failed,
+        }),
+        Err(failure) if ids.len() > 1 => Err(Error::Batch {
+            failed: vec![failure],
+            atomic: true,
+        }),
+        Err(failure) => Err(failure),
+    }
+}
+
+impl<T> Batch<T> {
+    /// Prints the JSON of every changed TODO, a single object when only one id was given
+    fn print_json<J: Serialize>(&self, format: OutputFormat, items: &[J]) {
+        match items {
+            [item] if self.failed.is_empty() => print_one(item),
+            _ => print_many(format, "todos", items),
+        }
+    }
+
+    /// Returns the ids that failed as a [`Error::Batch`]. When there were several ids a summary
+    /// like "2 of 3 TODOs completed" is printed first.
+    fn finish(self, done: &str, format: OutputFormat) -> Result<(), Error> {
+        let total = self.changed.len() + self.failed.len();
+        if total > 1 && !format.is_json() {
+            println!("{} of {} TODOs {}", self.changed.len(), total, done);
+        }
+        if self.failed.is_empty() {
+            return Ok(());
+        }
+        Err(Error::Batch {
+            failed: self.failed,
+            atomic: false,
+        })
+    }
+}
+
+fn complete_todos(
+    ids: &[String],
+    force: bool,
+    atomic: bool,
+    format: OutputFormat,
+) -> Result<(), Error> {
+    let mut batch = change_each("complete", ids, atomic, |store, id| {
+        let open_subtasks = store
+            .get_subtasks(id)?
+            .into_iter()
+            .filter(|subtask| subtask.completed.is_none())
+            .count();
+        if open_subtasks > 0 && !force {
+            return Err(Error::Refused(format!(
+                "{} still has {} open subtasks, complete them first or use `{} complete --force {}`",
+                id.yellow(),
+                open_subtasks,
+                BIN,
+                id
+            )));
+        }
+        let was_open = store.get_todo(id)?.completed.is_none();
+        Ok((was_open, store.complete_todo(id, None)?))
+    })?;
+    let store = &mut batch.store;
+    let blocked_ids = store.get_blocked_todo_ids()?;
+    // Only TODOs completed now can unblock anything, a dependent is listed once
+    let mut reported = HashSet::new();
+    let mut unblocked_by = Vec::new();
+    for (id, (was_open, _)) in &batch.changed {
+        let mut unblocked = Vec::new();
+        if *was_open {
+            for dependent in store.get_dependents(id)? {
+                if dependent.completed.is_none()
+                    && !blocked_ids.contains(&dependent.id)
+                    && reported.insert(dependent.id)
+                {
+                    unblocked.push(dependent);
+                }
+            }
+        }
+        unblocked_by.push(unblocked);
+    }
+    if format.is_json() {
+        let context = TodoJsonContext::load_from(store)?;
+        let mut items = Vec::new();
+        for ((id, (_, next_todo)), unblocked) in batch.changed.iter().zip(&unblocked_by) {
+            items.push(CompletedTodoJson {
+                todo: context.todo_json(&store.get_todo(id)?),
+                next_occurrence: next_todo
+                    .as_ref()
+                    .map(|next_todo| context.todo_json(next_todo)),
+                unblocked: unblocked
+                    .iter()
+                    .map(|dependent| context.todo_json(dependent))
+                    .collect(),
+            });
+        }
+        batch.print_json(format, &items);
+    } else {
+        for ((id, (_, next_todo)), unblocked) in batch.changed.iter().zip(&unblocked_by) {
+            println!(
+                "{} completed, if this was a mistake reopen with `{} reopen {}`",
+                id.yellow(),
+                BIN,
+                id
+            );
+            for dependent in unblocked {
+                println!(
+                    "{} is now unblocked: {}",
+                    crate::encode_id(dependent.id.try_into().unwrap()).yellow(),
+                    dependent.title
+                );
+            }
+            if let Some(next_todo) = next_todo {
+                print_next_occurrence(next_todo);
+            }
+        }
+    }
+    batch.finish("completed", format)
+}
+
+fn print_next_occurrence(next_todo: &Todos) {
+    println!(
+        "{} is the next occurrence, due: {}",
+        crate::encode_id(next_todo.id.try_into().unwrap()).yellow(),
+        format_duetime_or_else(next_todo.due, "no set time".to_string(), false)
     )
 }
 
-fn set_due_todo(id: &String, due_text: Option<String>) {
-    let mut due_ts: Option<DateTime<Utc>> = None;
-    if let Some(due_str) = due_text {
-        due_ts = Some(crate::parse_due_str(&due_str));
+fn format_recurrence(rule: &Option<String>) -> String {
+    match rule {
+        Some(rule) => match rule.parse::<Recurrence>() {
+            Ok(recurrence) => recurrence.describe(),
+            Err(_) => rule.clone(),
+        },
+        None => "never".to_string(),
+    }
+}
+
+fn set_recurrence_todo(
+    id: &str,
+    every: Option<Recurrence>,
+    format: OutputFormat,
+) -> Result<(), Error> {
+    crate::set_recurrence(id, every.as_ref())?;
+    if format.is_json() {
+        print_one(&todo_json(id)?);
+        return Ok(());
     }
-    crate::set_due(id, due_ts);
     println!(
-        // TODO: add undo message
-        "{} is due at: {}",
+        "{} repeats: {}",
         id.yellow(),
-        format_duetime_or_else(due_ts, "no set time".to_string(), false)
-    )
+        format_recurrence(&every.map(|recurrence| recurrence.to_string()))
+    );
+    Ok(())
+}
+
+fn set_due_todos(
+    ids: &[String],
+    due_text: Option<&str>,
+    atomic: bool,
+    format: OutputFormat,
+) -> Result<(), Error> {
+    let due_ts = due_text.map(crate::parse_due_str).transpose()?;
+    let mut batch = change_each("due", ids, atomic, |store, id| store.set_due(id, due_ts))?;
+    if format.is_json() {
+        let items = todos_json(&mut batch)?;
+        batch.print_json(format, &items);
+    } else {
+        for (id, _) in &batch.changed {
+            println!(
+                // TODO: add undo message
+                "{} is due at: {}",
+                id.yellow(),
+                format_duetime_or_else(due_ts, "no set time".to_string(), false)
+            );
+        }
+    }
+    batch.finish("changed", format)
+}
+
+/// The JSON of every TODO a batch command changed
+fn todos_json<T>(batch: &mut Batch<T>) -> Result<Vec<TodoJson>, Error> {
+    let context = TodoJsonContext::load_from(&mut batch.store)?;
+    batch
+        .changed
+        .iter()
+        .map(|(id, _)| Ok(context.todo_json(&batch.store.get_todo(id)?)))
+        .collect()
 }
 
-fn reopen_todo(id: &String) {
-    crate::reopen_todo(id);
+fn set_estimate_todo(
+    id: &str,
+    estimate: Option<TimeDelta>,
+    format: OutputFormat,
+) -> Result<(), Error> {
+    crate::set_estimate(id, estimate)?;
+    if format.is_json() {
+        print_one(&todo_json(id)?);
+        return Ok(());
+    }
     println!(
-        "{} reopened, if this was a mistake complete with `{} complete {}`",
+        "{} is estimated at: {}",
         id.yellow(),
-        BIN,
-        id
-    )
+        format_estimate(crate::get_todo(id)?.estimate_duration())
+    );
+    Ok(())
+}
+
+fn format_estimate(estimate: Option<TimeDelta>) -> String {
+    match estimate {
+        Some(estimate) => format_duration(estimate),
+        None => "no estimate".to_string(),
+    }
 }
 
-pub fn delete_todo(id: &String) {
-    crate::delete_todo(id);
-    println!("{} deleted", id.yellow());
+fn set_priority_todo(id: &str, level: Priority, format: OutputFormat) -> Result<(), Error> {
+    crate::set_priority(id, level)?;
+    if format.is_json() {
+        print_one(&todo_json(id)?);
+        return Ok(());
+    }
+    println!(
+        "{} has priority: {}",
+        id.yellow(),
+        format_priority(level.level())
+    );
+    Ok(())
+}
+
+fn start_todo(id: &str, format: OutputFormat) -> Result<(), Error> {
+    if let Some(session) = crate::get_active_session()? {
+        if session.todo_id == crate::decode_id(id)? {
+            if format.is_json() {
+                print_one(&SessionsJson {
+                    session: Some(SessionJson::new(&session)),
+                    stopped: None,
+                });
+                return Ok(());
+            }
+            println!(
+                "Already working on {} for {}",
+                id.yellow(),
+                format_duration(session.duration(Utc::now()))
+            );
+            return Ok(());
+        }
+    }
+    let (session, stopped_session) = crate::start_session(id)?;
+    if format.is_json() {
+        print_one(&SessionsJson {
+            session: Some(SessionJson::new(&session)),
+            stopped: stopped_session.as_ref().map(SessionJson::new),
+        });
+        return Ok(());
+    }
+    if let Some(stopped_session) = stopped_session {
+        print_stopped_session(&stopped_session);
+    }
+    println!(
+        "Started working on {} {}",
+        id.yellow(),
+        crate::get_todo(id)?.title
+    );
+    Ok(())
+}
+
+fn stop_todo(format: OutputFormat) -> Result<(), Error> {
+    let stopped_session = crate::stop_session()?;
+    if format.is_json() {
+        print_one(&SessionsJson {
+            session: None,
+            stopped: stopped_session.as_ref().map(SessionJson::new),
+        });
+        return Ok(());
+    }
+    match stopped_session {
+        Some(stopped_session) => print_stopped_session(&stopped_session),
+        None => println!("Nothing is being worked on currently"),
+    }
+    Ok(())
+}
+
+fn print_operations(operations: &[Operation], done: &str, verb: &str, format: OutputFormat) {
+    if format.is_json() {
+        let items: Vec<OperationJson> = operations.iter().map(OperationJson::new).collect();
+        print_many(format, "operations", &items);
+        return;
+    }
+    if operations.is_empty() {
+        println!("Nothing to {}", verb);
+    }
+    for operation in operations {
+        println!("{}: {}", done, operation.description);
+    }
+}
+
+fn print_stopped_session(session: &Session) {
+    let show_id = crate::encode_id(session.todo_id.try_into().unwrap());
+    println!(
+        "Stopped working on {} after {}",
+        show_id.yellow(),
+        format_duration(session.duration(Utc::now()))
+    );
+}
+
+fn current_todo(format: OutputFormat) -> Result<(), Error> {
+    let active_session = crate::get_active_session()?;
+    if format.is_json() {
+        print_one(&SessionsJson {
+            session: active_session.as_ref().map(SessionJson::new),
+            stopped: None,
+        });
+        return Ok(());
+    }
+    match active_session {
+        Some(session) => {
+            let show_id = crate::encode_id(session.todo_id.try_into().unwrap());
+            println!(
+                "Working on {} {} for {}",
+                show_id.yellow(),
+                crate::get_todo(&show_id)?.title,
+                format_duration(session.duration(Utc::now()))
+            );
+        }
+        None => println!(
+            "Nothing is being worked on currently, start with `{} start <id>`",
+            BIN
+        ),
+    }
+    Ok(())
+}
+
+/// Hours rounded to two decimals, which is what timesheets usually want
+fn to_hours(duration: TimeDelta) -> f64 {
+    (duration.num_seconds() as f64 / 36.0).round() / 100.0
+}
+
+fn report(args: ReportArgs, format: ReportFormat) -> Result<(), Error> {
+    if let Some(ReportCommands::Accuracy) = args.command {
+        report_accuracy(format)?;
+        return Ok(());
+    }
+    let today = Local::now().date_naive();
+    let since_day = crate::report::parse_day(&args.since, today)?;
+    let until_day = crate::report::parse_day(&args.until, today)?;
+    if until_day < since_day {
+        return Err(Error::Usage(format!(
+            "The report would end on {} before it starts on {}",
+            until_day, since_day
+        )));
+    }
+    let since = crate::report::day_start(since_day);
+    let until = crate::report::day_start(until_day + Days::new(1));
+    let entries = crate::report::time_entries(
+        &crate::get_sessions_between(since, until)?,
+        since,
+        until,
+        Utc::now(),
+    );
+    let rows: Vec<ReportRow> = match args.by {
+        ReportBy::Day => crate::report::group_entries(&entries, |entry| vec![entry.day])
+            .into_iter()
+            .map(|(day, duration)| ReportRow::new(day.to_string(), None, duration))
+            .collect(),
+        ReportBy::Week => crate::report::group_entries(&entries, |entry| {
+            vec![crate::report::week_start(entry.day)]
+        })
+        .into_iter()
+        .map(|(week, duration)| ReportRow::new(week.to_string(), None, duration))
+        .collect(),
+        ReportBy::Todo => {
+            let titles: HashMap<i32, String> = crate::get_todos()?
+                .into_iter()
+                .map(|todo| (todo.id, todo.title))
+                .collect();
+            crate::report::group_entries(&entries, |entry| vec![entry.todo_id])
+                .into_iter()
+                .map(|(todo_id, duration)| {
+                    ReportRow::new(
+                        crate::encode_id(todo_id.try_into().unwrap()),
+                        titles.get(&todo_id).cloned(),
+                        duration,
+                    )
+                })
+                .collect()
+        }
+        ReportBy::Project => {
+            let project_names: HashMap<i32, String> = crate::get_projects()?
+                .into_iter()
+                .map(|project| (project.id, project.name))
+                .collect();
+            let todo_projects: HashMap<i32, Option<i32>> = crate::get_todos()?
+                .into_iter()
+                .map(|todo| (todo.id, todo.project_id))
+                .collect();
+            // Keyed by whether there's no project first, so that TODOs without a project are
+            // listed last like in `list`
+            crate::report::group_entries(&entries, |entry| {
+                match todo_projects
+                    .get(&entry.todo_id)
+                    .copied()
+                    .flatten()
+                    .and_then(|project_id| project_names.get(&project_id))
+                {
+                    Some(name) => vec![(false, name.clone())],
+                    None => vec![(true, "no project".to_string())],
+                }
+            })
+            .into_iter()
+            .map(|((_, name), duration)| ReportRow::new(name, None, duration))
+            .collect()
+        }
+        ReportBy::Tag => {
+            let tags_by_todo = crate::get_tags_by_todo()?;
+            crate::report::group_entries(&entries, |entry| {
+                tags_by_todo
+                    .get(&entry.todo_id)
+                    .cloned()
+                    .unwrap_or(vec!["untagged".to_string()])
+            })
+            .into_iter()
+            .map(|(tag, duration)| ReportRow::new(tag, None, duration))
+            .collect()
+        }
+    };
+    let total = crate::report::total(&entries);
+    let by = args.by.to_possible_value().unwrap().get_name().to_string();
+    match format {
+        ReportFormat::Table => print_report_table(&by, &rows, total, since_day, until_day),
+        ReportFormat::Csv => print_report_csv(&by, &rows)?,
+        ReportFormat::Json => {
+            print_one(&serde_json::json!({
+                "since": since_day.to_string(),
+                "until": until_day.to_string(),
+                "by": by,
+                "rows": rows,
+                "total": ReportRow::new("total".to_string(), None, total),
+            }));
+        }
+        ReportFormat::Jsonl => print_many(OutputFormat::Jsonl, "rows", &rows),
+    }
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct AccuracyRow {
+    id: String,
+    title: String,
+    estimate_hours: f64,
+    actual_hours: f64,
+    /// How much longer (positive) or shorter (negative) the TODO took, in percent
+    error_percent: i64,
+}
+
+fn report_accuracy(format: ReportFormat) -> Result<(), Error> {
+    let tracked_times = crate::tracked_time_by_todo(Utc::now())?;
+    let mut completed_todos: Vec<Todos> = crate::get_todos()?
+        .into_iter()
+        .filter(|todo| todo.completed.is_some() && todo.estimate.is_some())
+        .filter(|todo| tracked_times.contains_key(&todo.id))
+        .collect();
+    completed_todos.sort_by_key(|todo| todo.completed);
+    let accuracies: Vec<crate::report::EstimateAccuracy> = completed_todos
+        .iter()
+        .map(|todo| crate::report::EstimateAccuracy {
+            todo_id: todo.id,
+            estimate: todo.estimate_duration().unwrap(),
+            actual: tracked_times[&todo.id],
+        })
+        .collect();
+    let rows: Vec<AccuracyRow> = completed_todos
+        .iter()
+        .zip(&accuracies)
+        .map(|(todo, accuracy)| AccuracyRow {
+            id: crate::encode_id(todo.id.try_into().unwrap()),
+            title: todo.title.clone(),
+            estimate_hours: to_hours(accuracy.estimate),
+            actual_hours: to_hours(accuracy.actual),
+            error_percent: (accuracy.error() * 100.0).round() as i64,
+        })
+        .collect();
+    let estimated = accuracies
+        .iter()
+        .fold(TimeDelta::zero(), |total, accuracy| {
+            total + accuracy.estimate
+        });
+    let actual = accuracies
+        .iter()
+        .fold(TimeDelta::zero(), |total, accuracy| total + accuracy.actual);
+    let mean_absolute_error = crate::report::mean_absolute_error(&accuracies);
+    match format {
+        ReportFormat::Table => {
+            let Some(mean_absolute_error) = mean_absolute_error else {
+                println!(
+                    "No completed TODOs have both an estimate and tracked time, estimate with `{} estimate <id> <estimate>`",
+                    BIN
+                );
+                return Ok(());
+            };
+            let mut table = comfy_table::Table::new();
+            table.load_preset(comfy_table::presets::NOTHING);
+            table.set_header(vec!["id", "title", "estimate", "actual", "off by"]);
+            for (row, accuracy) in rows.iter().zip(&accuracies) {
+                table.add_row(vec![
+                    comfy_table::Cell::new(row.id.yellow().to_string()),
+                    comfy_table::Cell::new(&row.title),
+                    comfy_table::Cell::new(format_duration(accuracy.estimate)),
+                    comfy_table::Cell::new(format_duration(accuracy.actual)),
+                    comfy_table::Cell::new(
+                        format_estimate_error(accuracy.actual, accuracy.estimate).to_string(),
+                    ),
+                ]);
+            }
+            println!("{table}");
+            println!(
+                "Estimates of {} TODOs were off by {}% on average, they took {} instead of {} ({})",
+                accuracies.len(),
+                (mean_absolute_error * 100.0).round(),
+                format_duration(actual),
+                format_duration(estimated),
+                format_estimate_error(actual, estimated)
+            );
+        }
+        ReportFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(std::io::stdout());
+            for row in &rows {
+                writer.serialize(row).map_err(csv_error)?;
+            }
+            if rows.is_empty() {
+                writer
+                    .write_record([
+                        "id",
+                        "title",
+                        "estimate_hours",
+                        "actual_hours",
+                        "error_percent",
+                    ])
+                    .map_err(csv_error)?;
+            }
+            writer.flush().map_err(Error::io("stdout"))?;
+        }
+        ReportFormat::Json => {
+            print_one(&serde_json::json!({
+                "todos": rows,
+                "estimate_hours": to_hours(estimated),
+                "actual_hours": to_hours(actual),
+                "mean_absolute_error_percent":
+                    mean_absolute_error.map(|error| (error * 100.0).round() as i64),
+            }));
+        }
+        ReportFormat::Jsonl => print_many(OutputFormat::Jsonl, "todos", &rows),
+    }
+    Ok(())
+}
+
+fn print_report_table(
+    by: &str,
+    rows: &[ReportRow],
+    total: TimeDelta,
+    since_day: NaiveDate,
+    until_day: NaiveDate,
+) {
+    if rows.is_empty() {
+        println!(
+            "No time was tracked from {} to {}, start with `{} start <id>`",
+            since_day, until_day, BIN
+        );
+        return;
+    }
+    let with_title = rows.iter().any(|row| row.title.is_some());
+    let mut table = comfy_table::Table::new();
+    table.load_preset(comfy_table::presets::NOTHING);
+    let mut header = vec![by, "time", "hours"];
+    if with_title {
+        header.insert(1, "title");
+    }
+    table.set_header(header);
+    for row in rows {
+        let key = if with_title {
+            row.key.yellow().to_string()
+        } else {
+            row.key.clone()
+        };
+        let mut cells = vec![
+            comfy_table::Cell::new(key),
+            comfy_table::Cell::new(format_duration(TimeDelta::seconds(row.seconds))),
+            comfy_table::Cell::new(format!("{:.2}", row.hours)),
+        ];
+        if with_title {
+            cells.insert(
+                1,
+                comfy_table::Cell::new(row.title.clone().unwrap_or_default()),
+            );
+        }
+        table.add_row(cells);
+    }
+    let mut total_cells = vec![
+        comfy_table::Cell::new("total".bold().to_string()),
+        comfy_table::Cell::new(format_duration(total).bold().to_string()),
+        comfy_table::Cell::new(format!("{:.2}", to_hours(total)).bold().to_string()),
+    ];
+    if with_title {
+        total_cells.insert(1, comfy_table::Cell::new(""));
+    }
+    table.add_row(total_cells);
+    println!("{table}");
 }
 
-pub fn add_todo(title: Option<String>, complete_after_creation: bool, due: Option<String>) {
+fn print_report_csv(by: &str, rows: &[ReportRow]) -> Result<(), Error> {
+    let mut writer = csv::Writer::from_writer(std::io::stdout());
+    writer
+        .write_record([by, "title", "hours", "seconds"])
+        .map_err(csv_error)?;
+    for row in rows {
+        writer
+            .write_record([
+                row.key.clone(),
+                row.title.clone().unwrap_or_default(),
+                format!("{:.2}", row.hours),
+                row.seconds.to_string(),
+            ])
+            .map_err(csv_error)?;
+    }
+    writer.flush().map_err(Error::io("stdout"))
+}
+
+/// CSV is only written to stdout, which can fail when it's closed early
+fn csv_error(e: csv::Error) -> Error {
+    Error::io("stdout")(e.into())
+}
+
+fn reopen_todos(ids: &[String], atomic: bool, format: OutputFormat) -> Result<(), Error> {
+    let mut batch = change_each("reopen", ids, atomic, |store, id| store.reopen_todo(id))?;
+    if format.is_json() {
+        let items = todos_json(&mut batch)?;
+        batch.print_json(format, &items);
+    } else {
+        for (id, _) in &batch.changed {
+            println!(
+                "{} reopened, if this was a mistake complete with `{} complete {}`",
+                id.yellow(),
+                BIN,
+                id
+            );
+        }
+    }
+    batch.finish("reopened", format)
+}
+
+pub fn delete_todos(ids: &[String], atomic: bool, format: OutputFormat) -> Result<(), Error> {
+    let batch = change_each("delete", ids, atomic, |store, id| {
+        store.delete_todo(id)?;
+        crate::decode_id(id)
+    })?;
+    if format.is_json() {
+        let items: Vec<DeletedTodoJson> = batch
+            .changed
+            .iter()
+            .map(|(id, numeric_id)| DeletedTodoJson {
+                id: id.to_string(),
+                numeric_id: *numeric_id,
+                deleted: true,
+            })
+            .collect();
+        batch.print_json(format, &items);
+    } else {
+        for (id, _) in &batch.changed {
+            println!(
+                "{} deleted, if this was a mistake restore it with `{} undo`",
+                id.yellow(),
+                BIN
+            );
+        }
+    }
+    batch.finish("deleted", format)
+}
+
+pub fn add_todo(args: AddArgs, format: OutputFormat) -> Result<(), Error> {
+    let AddArgs {
+        title,
+        complete: complete_after_creation,
+        due,
+        tag: tags,
+        project,
+        priority,
+        every,
+        parent,
+        estimate,
+    } = args;
     // TODO: There should be a way to supply body easily just like in `git commit -m ""`, but
     //  don't forget multiline messages with multiple -m's
     let title_str = match title {
         Some(t) => t,
         None => "<title>".to_string(),
     };
-    let p_buff = crate::get_todoeditmsg_file();
+    // Make sure the due time, project and parent work before asking for the title and notes
+    let due_ts = due
+        .map(|due_text| crate::parse_due_str(&due_text))
+        .transpose()?;
+    if let Some(project_name) = &project {
+        crate::get_active_project(project_name)?;
+    }
+    if let Some(parent_id) = &parent {
+        crate::get_todo(parent_id)?;
+    }
+    let p_buff = crate::get_todoeditmsg_file()?;
     let fp = p_buff.as_path();
     let (title, notes) = crate::create_temp_todo_file_open_and_then_read_remove_process(
         fp,
         title_str,
         String::new(),
-    );
+    )?;
     let new_todo = NewTodo {
         title: title.as_str(),
         notes: notes.as_str(),
         created: Utc::now(),
     };
-    let created_todo = crate::add_todo(&new_todo);
-    if let Some(due_text) = due {
-        let due_ts = crate::parse_due_str(&due_text);
-        crate::set_due(
-            &crate::encode_id(created_todo.id.try_into().unwrap()),
-            Some(due_ts),
-        );
-    }
-    if complete_after_creation {
-        crate::complete_todo(
-            &crate::encode_id(created_todo.id.try_into().unwrap()),
-            Some(created_todo.created),
-        );
+    // Everything the new TODO is created with is undone at once
+    let mut store = TodoStore::open()?;
+    let (created_todo, next_todo) = store.operation(format!("add \"{}\"", title), |store| {
+        let created_todo = store.add_todo(&new_todo)?;
+        if due_ts.is_some() {
+            store.set_due(
+                &crate::encode_id(created_todo.id.try_into().unwrap()),
+                due_ts,
+            )?;
+        }
+        if let Some(project_name) = project {
+            store.set_todo_project(
+                &crate::encode_id(created_todo.id.try_into().unwrap()),
+                Some(&project_name),
+            )?;
+        }
+        if let Some(level) = priority {
+            store.set_priority(
+                &crate::encode_id(created_todo.id.try_into().unwrap()),
+                level,
+            )?;
+        }
+        if let Some(parent_id) = &parent {
+            store.set_parent(
+                &crate::encode_id(created_todo.id.try_into().unwrap()),
+                Some(parent_id),
+            )?;
+        }
+        if let Some(estimate) = estimate {
+            store.set_estimate(
+                &crate::encode_id(created_todo.id.try_into().unwrap()),
+                Some(estimate),
+            )?;
+        }
+        if let Some(recurrence) = every {
+            store.set_recurrence(
+                &crate::encode_id(created_todo.id.try_into().unwrap()),
+                Some(&recurrence),
+            )?;
+        }
+        if !tags.is_empty() {
+            store.add_tags(
+                &crate::encode_id(created_todo.id.try_into().unwrap()),
+                &tags,
+            )?;
+        }
+        let next_todo = if complete_after_creation {
+            store.complete_todo(
+                &crate::encode_id(created_todo.id.try_into().unwrap()),
+                Some(created_todo.created),
+            )?
+        } else {
+            None
+        };
+        Ok::<_, Error>((created_todo, next_todo))
+    })?;
+    if format.is_json() {
+        print_one(&todo_json(&crate::encode_id(
+            created_todo.id.try_into().unwrap(),
+        ))?);
+        return Ok(());
     }
     println!(
         "{} created{}",
@@ -314,50 +2152,259 @@ pub fn add_todo(title: Option<String>, complete_after_creation: bool, due: Optio
             ""
         }
     );
+    if let Some(next_todo) = next_todo {
+        print_next_occurrence(&next_todo);
+    }
+    Ok(())
 }
 
-pub fn list_todos(show_completed: Option<bool>) {
-    let mut results = crate::get_todos();
+fn next_todos(n: usize, format: OutputFormat) -> Result<(), Error> {
+    let mut results = crate::get_todos()?;
+    // Blocked TODOs can't be worked on yet, so they are never next
+    let blocked_ids = crate::get_blocked_todo_ids()?;
+    results.retain(|todo| todo.completed.is_none() && !blocked_ids.contains(&todo.id));
+    if format.is_json() {
+        let context = TodoJsonContext::load()?;
+        let ranked: Vec<NextTodoJson> = crate::urgency::sort_by_urgency(results, Utc::now())?
+            .into_iter()
+            .take(n)
+            .map(|(todo, urgency)| NextTodoJson::new(context.todo_json(&todo), urgency))
+            .collect();
+        print_many(format, "todos", &ranked);
+        return Ok(());
+    }
+    if results.is_empty() {
+        println!(
+            "There's nothing to do currently :) Add a new one with `{} add`",
+            BIN
+        );
+        return Ok(());
+    }
+    let ranked = crate::urgency::sort_by_urgency(results, Utc::now())?;
+    for (index, (todo, urgency)) in ranked.into_iter().take(n).enumerate() {
+        if index > 0 {
+            println!();
+        }
+        println!(
+            "{} {} (urgency {:.1})",
+            crate::encode_id(todo.id.try_into().unwrap()).yellow(),
+            todo.title,
+            urgency.total()
+        );
+        for factor in urgency.factors {
+            println!("  {:+.1} {}", factor.score, factor.reason);
+        }
+    }
+    Ok(())
+}
+
+pub fn list_todos(
+    show_completed: Option<bool>,
+    tags: &[String],
+    filter: Option<Filter>,
+    sort: SortOrder,
+    layout: &TableLayout,
+    format: OutputFormat,
+) -> Result<(), Error> {
     // show_completed parameter:
     // - None: show open (uncompleted) TODOs (default behavior)
     // - Some(true): show only completed TODOs
     // - Some(false): show all TODOs (both completed and open)
-    match show_completed {
-        Some(true) => {
-            // Show only completed TODOs
-            results.retain(|todo| todo.completed.is_some());
-        }
-        Some(false) => {
-            // Show all TODOs (no filter)
-        }
-        None => {
-            // Default: show only open (uncompleted) TODOs
-            results.retain(|todo| todo.completed.is_none());
+    let status = match show_completed {
+        Some(true) => Some(Filter::Condition(Condition::Completed(true))),
+        Some(false) => None,
+        None => Some(Filter::Condition(Condition::Completed(false))),
+    };
+    let has_where = filter.is_some();
+    // Every flag has to match, the filtering itself happens in the database
+    let filter = status
+        .into_iter()
+        .chain(
+            tags.iter()
+                .map(|tag| Filter::Condition(Condition::Tag(tag.clone()))),
+        )
+        .chain(filter)
+        .reduce(Filter::and);
+    let mut results = crate::get_todos_where(filter.as_ref())?;
+
+    // Sort by how close the due time is, so that within the same due bucket (like due in the next
+    // 3 days, or no due date at all) higher priorities come first. Then by due time ascending.
+    // When they are equal, keep relative order to maintain secondary sort from database query by
+    // id.
+    let now = Utc::now();
+    match sort {
+        SortOrder::Due => results.sort_by(|a, b| {
+            due_bucket(a.due, now)
+                .cmp(&due_bucket(b.due, now))
+                .then(b.priority.cmp(&a.priority))
+                .then(match (&a.due, &b.due) {
+                    (Some(d1), Some(d2)) => d1.cmp(d2), // both have dates → compare them
+                    _ => Ordering::Equal, // same bucket, so both None → keep relative order
+                })
+        }),
+        SortOrder::Urgency => {
+            results = crate::urgency::sort_by_urgency(results, now)?
+                .into_iter()
+                .map(|(todo, _)| todo)
+                .collect();
         }
     }
 
-    // Sort by due time descending, while pushing no due dates to the back
-    // When they are both None, keep relative order to maintain secondary sort from
-    // database query by id.
-    results.sort_by(|a, b| match (&a.due, &b.due) {
-        (Some(d1), Some(d2)) => d1.cmp(d2), // both have dates → compare them
-        (None, Some(_)) => Ordering::Greater, // a is None, b has a date → a goes after b
-        (Some(_), None) => Ordering::Less,  // a has a date, b is None → a goes before b
-        (None, None) => Ordering::Equal,    // both None → keep relative order (stable sort)
-    });
-
-    if results.is_empty() {
+    if format.is_json() {
+        let context = TodoJsonContext::load()?;
+        let todos: Vec<_> = results.iter().map(|todo| context.todo_json(todo)).collect();
+        print_many(format, "todos", &todos);
+    } else if results.is_empty() && has_where {
+        println!("No TODOs match the filter");
+    } else if results.is_empty() {
         println!(
             "There's nothing to do currently :) Add a new one with `{} add`",
             BIN
         );
+    } else if results.iter().all(|todo| todo.project_id.is_none()) {
+        println!("{}", todos_table(results, layout, &HashMap::new())?);
     } else {
-        let mut table = comfy_table::Table::new();
-        table.load_preset(comfy_table::presets::NOTHING);
-        table.set_header(vec!["id", "created", "due", "title"]);
-        for post in results {
-            table.add_row(vec![
-                comfy_table::Cell::new(
+        // Render one section per project, TODOs without a project go last
+        let open_counts = crate::count_open_todos_by_project()?;
+        let mut sections: Vec<(String, Option<i32>)> = crate::get_projects()?
+            .into_iter()
+            .map(|project| (project.name, Some(project.id)))
+            .collect();
+        sections.push(("no project".to_string(), None));
+        let mut first_section = true;
+        for (name, project_id) in sections {
+            let (section, rest): (Vec<Todos>, Vec<Todos>) = results
+                .into_iter()
+                .partition(|todo| todo.project_id == project_id);
+            results = rest;
+            if section.is_empty() {
+                continue;
+            }
+            if !first_section {
+                println!();
+            }
+            first_section = false;
+            println!(
+                "{} ({} open)",
+                name.bold(),
+                open_counts.get(&project_id).unwrap_or(&0)
+            );
+            println!("{}", todos_table(section, layout, &HashMap::new())?);
+        }
+    }
+    Ok(())
+}
+
+fn search_todos(
+    query: &str,
+    show_completed: Option<bool>,
+    format: OutputFormat,
+) -> Result<(), Error> {
+    let mut matches = crate::search_todos(query)?;
+    match show_completed {
+        Some(true) => matches.retain(|found| found.todo.completed.is_some()),
+        Some(false) => {}
+        None => matches.retain(|found| found.todo.completed.is_none()),
+    }
+    if format.is_json() {
+        let context = TodoJsonContext::load()?;
+        let results: Vec<_> = matches
+            .iter()
+            .map(|found| SearchMatchJson {
+                todo: context.todo_json(&found.todo),
+                snippet: Some(crate::search::strip_highlights(&found.snippet))
+                    .filter(|snippet| !snippet.is_empty()),
+            })
+            .collect();
+        print_many(format, "todos", &results);
+        return Ok(());
+    }
+    if matches.is_empty() {
+        println!("No TODOs match \"{}\"", query);
+        return Ok(());
+    }
+    let mut snippets = HashMap::new();
+    let mut results = Vec::new();
+    for mut found in matches {
+        found.todo.title = highlight(&found.title);
+        if !found.snippet.is_empty() {
+            // Keep the notes column to a single line per TODO
+            let snippet = found
+                .snippet
+                .split_whitespace()
+                .collect::<Vec<&str>>()
+                .join(" ");
+            snippets.insert(found.todo.id, highlight(&snippet));
+        }
+        results.push(found.todo);
+    }
+    let mut columns = DEFAULT_COLUMNS.to_vec();
+    if !snippets.is_empty() {
+        columns.push(Column::Notes);
+    }
+    // Cutting titles could cut through the highlighting
+    let layout = TableLayout {
+        columns,
+        wide: true,
+    };
+    println!("{}", todos_table(results, &layout, &snippets)?);
+    Ok(())
+}
+
+/// Styles the words a search matched
+fn highlight(text: &str) -> String {
+    text.split(crate::search::HIGHLIGHT_START)
+        .enumerate()
+        .map(
+            |(index, part)| match part.split_once(crate::search::HIGHLIGHT_END) {
+                Some((matched, rest)) if index > 0 => format!("{}{}", matched.bold().green(), rest),
+                _ => part.to_string(),
+            },
+        )
+        .collect()
+}
+
+/// `snippets` are shown in a notes column after the title, when there are any
+fn todos_table(
+    results: Vec<Todos>,
+    layout: &TableLayout,
+    snippets: &HashMap<i32, String>,
+) -> Result<comfy_table::Table, Error> {
+    let mut table = comfy_table::Table::new();
+    table.load_preset(comfy_table::presets::NOTHING);
+    table.set_header(
+        layout
+            .columns
+            .iter()
+            .map(|column| column.to_possible_value().unwrap().get_name().to_string())
+            .collect::<Vec<String>>(),
+    );
+    let tracked_times = if layout.columns.contains(&Column::Tracked) {
+        crate::tracked_time_by_todo(Utc::now())?
+    } else {
+        HashMap::new()
+    };
+    let tags_by_todo = if layout.columns.contains(&Column::Tags) {
+        crate::get_tags_by_todo()?
+    } else {
+        HashMap::new()
+    };
+    let project_names: HashMap<i32, String> = if layout.columns.contains(&Column::Project) {
+        crate::get_projects()?
+            .into_iter()
+            .map(|project| (project.id, project.name))
+            .collect()
+    } else {
+        HashMap::new()
+    };
+    let subtask_counts = crate::count_subtasks_by_parent()?;
+    let blocked_ids = crate::get_blocked_todo_ids()?;
+    for (post, depth) in tree_order(results) {
+        let row: Vec<comfy_table::Cell> = layout
+            .columns
+            .iter()
+            .map(|column| match column {
+                Column::Id => comfy_table::Cell::new(
                     // With custom_styling comfy_table flag we can keep using colorize colors, but
                     // slow down comfy table by 30-50%. I think this is acceptable for now, but
                     // could later switch to using comfy_table's built-in coloring.
@@ -365,17 +2412,333 @@ pub fn list_todos(show_completed: Option<bool>) {
                         .yellow()
                         .to_string(),
                 ),
-                comfy_table::Cell::new(format_datetime(post.created, false)),
-                comfy_table::Cell::new(format_duetime_or_else(post.due, "".to_string(), false)),
-                comfy_table::Cell::new(post.title),
+                Column::Created => comfy_table::Cell::new(format_datetime(post.created, false)),
+                Column::Due => {
+                    comfy_table::Cell::new(format_duetime_or_else(post.due, "".to_string(), false))
+                }
+                Column::Completed => comfy_table::Cell::new(
+                    post.completed
+                        .map(|completed| format_datetime(completed, false))
+                        .unwrap_or_default(),
+                ),
+                Column::Priority => comfy_table::Cell::new(format_priority(post.priority)),
+                Column::Estimate => comfy_table::Cell::new(
+                    post.estimate_duration()
+                        .map(format_duration)
+                        .unwrap_or_default(),
+                ),
+                Column::Title => {
+                    let mut title = if layout.wide {
+                        post.title.clone()
+                    } else {
+                        truncate(&post.title, TITLE_WIDTH)
+                    };
+                    // Blocked TODOs can't be worked on yet, so make them less prominent
+                    if blocked_ids.contains(&post.id) {
+                        title = title.dimmed().to_string();
+                    }
+                    if depth > 0 {
+                        title = format!("{}└ {}", "  ".repeat(depth - 1), title);
+                    }
+                    if let Some((done, total)) = subtask_counts.get(&post.id) {
+                        title = format!("{} [{}/{}]", title, done, total);
+                    }
+                    comfy_table::Cell::new(title)
+                }
+                Column::Notes => comfy_table::Cell::new(match snippets.get(&post.id) {
+                    Some(snippet) => snippet.clone(),
+                    None => {
+                        let first_line = post.notes.lines().next().unwrap_or_default().trim();
+                        if layout.wide {
+                            first_line.to_string()
+                        } else {
+                            truncate(first_line, NOTES_WIDTH)
+                        }
+                    }
+                }),
+                Column::Tags => comfy_table::Cell::new(
+                    tags_by_todo
+                        .get(&post.id)
+                        .map(|tags| tags.join(", "))
+                        .unwrap_or_default(),
+                ),
+                Column::Project => comfy_table::Cell::new(
+                    post.project_id
+                        .and_then(|project_id| project_names.get(&project_id).cloned())
+                        .unwrap_or_default(),
+                ),
+                Column::Tracked => comfy_table::Cell::new(
+                    tracked_times
+                        .get(&post.id)
+                        .map(|tracked| format_duration(*tracked))
+                        .unwrap_or_default(),
+                ),
+            })
+            .collect();
+        table.add_row(row);
+    }
+    if let Some(title_index) = layout
+        .columns
+        .iter()
+        .position(|column| *column == Column::Title)
+    {
+        table.column_mut(title_index).unwrap().set_constraint(
+            comfy_table::ColumnConstraint::UpperBoundary(comfy_table::Width::Percentage(60)),
+        );
+    }
+    Ok(table)
+}
+
+/// Cuts text longer than `width` characters, ending it with an ellipsis
+fn truncate(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        return text.to_string();
+    }
+    let mut truncated: String = text.chars().take(width - 1).collect();
+    truncated.push('…');
+    truncated
+}
+
+/// Orders TODOs so that subtasks come right after their parent, along with how deep they are
+/// nested. TODOs whose parent isn't in `results` are shown at the top level. Otherwise the order
+/// of `results` is kept.
+fn tree_order(results: Vec<Todos>) -> Vec<(Todos, usize)> {
+    let ids: HashSet<i32> = results.iter().map(|todo| todo.id).collect();
+    let mut children: HashMap<i32, Vec<usize>> = HashMap::new();
+    let mut roots: Vec<usize> = Vec::new();
+    for (index, todo) in results.iter().enumerate() {
+        match todo.parent_id {
+            Some(parent_id) if ids.contains(&parent_id) => {
+                children.entry(parent_id).or_default().push(index)
+            }
+            _ => roots.push(index),
+        }
+    }
+    let mut remaining: Vec<Option<Todos>> = results.into_iter().map(Some).collect();
+    let mut ordered = Vec::new();
+    let mut stack: Vec<(usize, usize)> = roots.into_iter().rev().map(|index| (index, 0)).collect();
+    while let Some((index, depth)) = stack.pop() {
+        let Some(todo) = remaining[index].take() else {
+            continue;
+        };
+        if let Some(child_indexes) = children.get(&todo.id) {
+            stack.extend(child_indexes.iter().rev().map(|child| (*child, depth + 1)));
+        }
+        ordered.push((todo, depth));
+    }
+    // Only reachable when parents form a cycle, still show those TODOs instead of dropping them
+    ordered.extend(remaining.into_iter().flatten().map(|todo| (todo, 0)));
+    ordered
+}
+
+fn export_todos(show_completed: Option<bool>, format: ExportFormat) -> Result<(), Error> {
+    let mut results = crate::get_todos()?;
+    match show_completed {
+        Some(true) => results.retain(|todo| todo.completed.is_some()),
+        Some(false) => {}
+        None => results.retain(|todo| todo.completed.is_none()),
+    }
+    let context = TodoJsonContext::load()?;
+    let todos: Vec<_> = results.iter().map(|todo| context.todo_json(todo)).collect();
+    match format {
+        ExportFormat::Csv => {
+            crate::formats::csv::write_todos(std::io::stdout(), &todos).map_err(csv_error)?
+        }
+        ExportFormat::Todotxt => print!("{}", crate::formats::todotxt::write_todos(&todos)),
+        ExportFormat::Ics => print!("{}", crate::formats::ics::write_todos(&todos)),
+        ExportFormat::Markdown => print!("{}", crate::formats::markdown::write_todos(&todos)),
+        ExportFormat::Org => print!("{}", crate::formats::org::write_todos(&todos)),
+        ExportFormat::Json => print_many(OutputFormat::Json, "todos", &todos),
+        ExportFormat::Jsonl => print_many(OutputFormat::Jsonl, "todos", &todos),
+    }
+    Ok(())
+}
+
+fn import_todos(
+    file: &std::path::Path,
+    format: Option<ImportFormat>,
+    from: Option<ImportSource>,
+    dry_run: bool,
+) -> Result<(), Error> {
+    let extension = file
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase());
+    let input_format = format.or(match extension.as_deref() {
+        Some("csv") => Some(ImportFormat::Csv),
+        Some("txt") => Some(ImportFormat::Todotxt),
+        Some("ics") => Some(ImportFormat::Ics),
+        _ => None,
+    });
+    let read_file = || std::fs::read_to_string(file).map_err(Error::io(file));
+    let read_result = match (from, input_format) {
+        (Some(ImportSource::Taskwarrior), _) => {
+            crate::formats::taskwarrior::read_todos(&read_file()?)
+                .map(|import| (import.rows, import.skipped))
+        }
+        (None, Some(ImportFormat::Csv)) => {
+            crate::formats::csv::read_todos(std::fs::File::open(file).map_err(Error::io(file))?)
+                .map(|rows| (rows, 0))
+        }
+        (None, Some(ImportFormat::Todotxt)) => {
+            Ok((crate::formats::todotxt::read_todos(&read_file()?), 0))
+        }
+        (None, Some(ImportFormat::Ics)) => Ok((crate::formats::ics::read_todos(&read_file()?), 0)),
+        (None, None) => {
+            return Err(usage_error(Cli::command().error(
+                clap::error::ErrorKind::InvalidValue,
+                "import needs a --format, like csv, todotxt or ics, or --from taskwarrior",
+            )))
+        }
+    };
+    let (mut rows, skipped) = read_result.map_err(|e| Error::Io {
+        path: file.to_path_buf(),
+        source: std::io::Error::new(std::io::ErrorKind::InvalidData, e),
+    })?;
+    let store = &mut TodoStore::open()?;
+    crate::formats::check_references(store, &mut rows)?;
+    let mut imported = Vec::new();
+    let mut failed = Vec::new();
+    for row in rows {
+        match row.todo {
+            Ok(todo) => imported.push((row.line, todo)),
+            Err(e) => failed.push((row.line, e)),
+        }
+    }
+    if dry_run {
+        let mut table = comfy_table::Table::new();
+        table.load_preset(comfy_table::presets::NOTHING);
+        table.set_header(vec!["line", "title", "due", "priority", "project", "tags"]);
+        for (line, todo) in &imported {
+            table.add_row(vec![
+                comfy_table::Cell::new(line),
+                comfy_table::Cell::new(&todo.title),
+                comfy_table::Cell::new(format_datetime_or_else(todo.due, "".to_string(), true)),
+                comfy_table::Cell::new(format_priority(
+                    todo.priority.unwrap_or(Priority::Unset).level(),
+                )),
+                comfy_table::Cell::new(todo.project.clone().unwrap_or_default()),
+                comfy_table::Cell::new(todo.tags.join(", ")),
             ]);
         }
-        table
-            .column_mut(2)
-            .unwrap()
-            .set_constraint(comfy_table::ColumnConstraint::UpperBoundary(
-                comfy_table::Width::Percentage(60),
-            ));
-        println!("{table}")
+        if !imported.is_empty() {
+            println!("{table}");
+        }
+        println!(
+            "{} TODOs would be imported{}",
+            imported.len(),
+            format_skipped(skipped)
+        );
+    } else {
+        let todos: Vec<_> = imported.into_iter().map(|(_, todo)| todo).collect();
+        let added = crate::formats::add_imported(store, &todos)?;
+        println!("{} TODOs imported{}", added.len(), format_skipped(skipped));
+    }
+    if failed.is_empty() {
+        return Ok(());
+    }
+    Err(Error::Batch {
+        failed: failed
+            .into_iter()
+            .map(|(line, e)| Error::Refused(format!("Line {} couldn't be imported: {}", line, e)))
+            .collect(),
+        atomic: false,
+    })
+}
+
+fn format_skipped(skipped: usize) -> String {
+    if skipped == 0 {
+        "".to_string()
+    } else {
+        format!(", {} skipped", skipped)
+    }
+}
+
+fn sync_todotxt(path: &std::path::Path, format: OutputFormat) -> Result<(), Error> {
+    let changes = crate::sync::sync_todotxt(path)?;
+    if format.is_json() {
+        let changes: Vec<serde_json::Value> = changes
+            .iter()
+            .map(|change| {
+                serde_json::json!({
+                    "change": format!("{:?}", change.kind),
+                    "id": change.id,
+                    "detail": change.detail,
+                })
+            })
+            .collect();
+        print_many(format, "changes", &changes);
+        return Ok(());
+    }
+    if changes.is_empty() {
+        println!("{} is already in sync", path.display());
+    }
+    for change in changes {
+        let id = change.id.unwrap_or_default().yellow();
+        match change.kind {
+            SyncChangeKind::AddedToWorkingon => println!("{} added: {}", id, change.detail),
+            SyncChangeKind::AddedToFile => {
+                println!("{} written to the file: {}", id, change.detail)
+            }
+            SyncChangeKind::UpdatedWorkingon => println!("{} updated: {}", id, change.detail),
+            SyncChangeKind::UpdatedFile => {
+                println!("{} updated in the file: {}", id, change.detail)
+            }
+            SyncChangeKind::DeletedFromWorkingon => {
+                println!("{} deleted: {}", id, change.detail)
+            }
+            SyncChangeKind::DeletedFromFile => {
+                println!("{} removed from the file: {}", id, change.detail)
+            }
+            SyncChangeKind::Conflict => println!(
+                "{} changed in both, kept workingon's version: {}",
+                id, change.detail
+            ),
+            SyncChangeKind::Skipped => eprintln!("{} skipped a line: {}", id, change.detail),
+        }
+    }
+    Ok(())
+}
+
+fn list_projects(show_archived: bool, format: OutputFormat) -> Result<(), Error> {
+    let mut projects = crate::get_projects()?;
+    if !show_archived {
+        projects.retain(|project| project.archived.is_none());
+    }
+    if format.is_json() {
+        let open_counts = crate::count_open_todos_by_project()?;
+        let projects: Vec<ProjectJson> = projects
+            .into_iter()
+            .map(|project| {
+                let open_todos = *open_counts.get(&Some(project.id)).unwrap_or(&0);
+                ProjectJson::new(project, open_todos)
+            })
+            .collect();
+        print_many(format, "projects", &projects);
+        return Ok(());
+    }
+    if projects.is_empty() {
+        println!(
+            "There are no projects currently, add a new one with `{} project add`",
+            BIN
+        );
+        return Ok(());
+    }
+    let open_counts = crate::count_open_todos_by_project()?;
+    let mut table = comfy_table::Table::new();
+    table.load_preset(comfy_table::presets::NOTHING);
+    table.set_header(vec!["name", "open", "created", "archived"]);
+    for project in projects {
+        table.add_row(vec![
+            comfy_table::Cell::new(project.name.yellow().to_string()),
+            comfy_table::Cell::new(open_counts.get(&Some(project.id)).unwrap_or(&0)),
+            comfy_table::Cell::new(format_datetime(project.created, false)),
+            comfy_table::Cell::new(format_datetime_or_else(
+                project.archived,
+                "".to_string(),
+                false,
+            )),
+        ]);
     }
+    println!("{table}");
+    Ok(())
 }
@@ -0,0 +1,41 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::Error;
+
+/// Defaults set with `workingon config`, kept as JSON in the data folder
+#[derive(Default, Serialize, Deserialize)]
+pub struct Config {
+    /// Columns `list` shows when none are given, comma separated like "id,due,title"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub columns: Option<String>,
+}
+
+pub fn get_config_file() -> Result<PathBuf, Error> {
+    let mut path = crate::get_project_data_folder()?;
+    path.push("config.json");
+    Ok(path)
+}
+
+/// Reads the config, a missing file means every default is unset
+pub fn load_config() -> Result<Config, Error> {
+    let path = get_config_file()?;
+    match std::fs::read_to_string(&path) {
+        Ok(content) => serde_json::from_str(&content).map_err(|e| Error::Io {
+            path,
+            source: e.into(),
+        }),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Config::default()),
+        Err(e) => Err(Error::Io { path, source: e }),
+    }
+}
+
+pub fn save_config(config: &Config) -> Result<(), Error> {
+    let path = get_config_file()?;
+    let content = serde_json::to_string_pretty(config).map_err(|e| Error::Io {
+        path: path.clone(),
+        source: e.into(),
+    })?;
+    std::fs::write(&path, content).map_err(Error::io(&path))
+}
@@ -0,0 +1,100 @@
+use std::fmt;
+use std::path::PathBuf;
+
+/// Why a library call failed, the CLI turns these into messages and exit codes
+#[derive(Debug)]
+pub enum Error {
+    /// There's nothing with that id or name, `kind` is what was looked for like "TODO"
+    NotFound { kind: &'static str, name: String },
+    /// The text isn't an id workingon hands out
+    InvalidId(String),
+    /// The text isn't a time chrono_english understands
+    BadDate(String),
+    /// The change would leave things in a state workingon doesn't allow, like a dependency cycle
+    Conflict(String),
+    /// The database couldn't be opened, migrated or queried
+    Db {
+        context: String,
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+    /// The editor couldn't be run, or what was written in it can't be used
+    Editor(String),
+    /// A file couldn't be read or written
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    /// The command won't do this, like completing a TODO with open subtasks
+    Refused(String),
+    /// An argument, or a setting saved from one, can't be used, like a `--where` that doesn't parse
+    Usage(String),
+    /// Some of the TODOs a command was given failed, the others were still changed unless
+    /// `atomic` is set
+    Batch { failed: Vec<Error>, atomic: bool },
+}
+
+impl Error {
+    /// Wraps a database error with what was being done, like "TODOs couldn't be loaded"
+    pub fn db(context: impl Into<String>) -> impl FnOnce(diesel::result::Error) -> Error {
+        let context = context.into();
+        move |source| Error::Db {
+            context,
+            source: Box::new(source),
+        }
+    }
+
+    pub fn io(path: impl Into<PathBuf>) -> impl FnOnce(std::io::Error) -> Error {
+        let path = path.into();
+        move |source| Error::Io { path, source }
+    }
+
+    pub fn todo_not_found(show_id: &str) -> Error {
+        Error::NotFound {
+            kind: "TODO",
+            name: show_id.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::NotFound { kind: "TODO", name } => write!(f, "There's no TODO with id {}", name),
+            Error::NotFound {
+                kind: "dependency",
+                name,
+            } => write!(f, "There's no dependency of {}", name),
+            Error::NotFound { kind, name } => write!(f, "There's no {} named {}", kind, name),
+            Error::InvalidId(id) => write!(f, "{} isn't a valid id", id),
+            Error::BadDate(text) => write!(
+                f,
+                "\"{}\" isn't a time workingon understands, try something like \"friday 5pm\"",
+                text
+            ),
+            Error::Conflict(reason) => write!(f, "{}", reason),
+            Error::Db { context, source } => write!(f, "{}: {}", context, source),
+            Error::Editor(reason) => write!(f, "{}", reason),
+            Error::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            Error::Refused(reason) => write!(f, "{}", reason),
+            Error::Usage(reason) => write!(f, "{}", reason),
+            Error::Batch { failed, atomic } => {
+                let messages: Vec<String> = failed.iter().map(Error::to_string).collect();
+                write!(f, "{}", messages.join("\n"))?;
+                if *atomic {
+                    write!(f, "\nNothing was changed, because of --atomic")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Db { source, .. } => Some(source.as_ref()),
+            Error::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,480 @@
+use chrono::{DateTime, Days, Local, TimeDelta, Utc};
+use diesel::expression::BoxableExpression;
+use diesel::prelude::*;
+use diesel::sql_types::Bool;
+use diesel::sqlite::Sqlite;
+use std::iter::Peekable;
+use std::str::Chars;
+
+use crate::models::Priority;
+use crate::schema::{projects, tags, todo_tags, todos};
+
+/// A filter for `list --where`, like `due<friday and tag:work and not title~"meeting"`. `not` binds
+/// tighter than `and`, which binds tighter than `or`, conditions next to each other have to match
+/// both and parentheses group.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Filter {
+    And(Box<Filter>, Box<Filter>),
+    Or(Box<Filter>, Box<Filter>),
+    Not(Box<Filter>),
+    Condition(Condition),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Condition {
+    /// `status:open` or `status:completed`
+    Completed(bool),
+    /// `tag:work`
+    Tag(String),
+    /// `project:name`, `project:none` has `None`
+    Project(Option<String>),
+    /// `title~text`, case insensitive
+    TitleContains(String),
+    /// `notes~text`, case insensitive
+    NotesContains(String),
+    /// `due<friday`, `created>=2026-10-01`, `completed=none`
+    Time(TimeField, Comparison, Option<Moment>),
+    /// `priority>=medium`
+    Priority(Comparison, Priority),
+    /// `estimate>1h`, `estimate=none`
+    Estimate(Comparison, Option<TimeDelta>),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimeField {
+    Created,
+    Due,
+    Completed,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Comparison {
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+}
+
+/// A span of time values are compared against, from `start` until just before `end`. A day like
+/// "friday" spans all of it, so `due<=friday` includes Friday evening.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Moment {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+}
+
+impl Filter {
+    pub fn and(self, other: Filter) -> Filter {
+        Filter::And(Box::new(self), Box::new(other))
+    }
+
+    /// The SQL condition TODOs have to match. TODOs without a due date (or any other optional
+    /// value) never match a comparison on it, and do match its negation.
+    pub fn to_sql(&self) -> BoxedCondition {
+        match self {
+            Filter::And(left, right) => Box::new(left.to_sql().and(right.to_sql())),
+            Filter::Or(left, right) => Box::new(left.to_sql().or(right.to_sql())),
+            Filter::Not(filter) => Box::new(diesel::dsl::not(filter.to_sql())),
+            Filter::Condition(condition) => condition.to_sql(),
+        }
+    }
+}
+
+pub type BoxedCondition = Box<dyn BoxableExpression<todos::table, Sqlite, SqlType = Bool>>;
+
+/// Compares a column with a range of values, `$start..$end` being the values that count as equal
+macro_rules! compare {
+    ($column:expr, $comparison:expr, $start:expr, $end:expr) => {
+        match $comparison {
+            Comparison::Lt => Box::new($column.lt($start)) as BoxedCondition,
+            Comparison::Le => Box::new($column.lt($end)),
+            Comparison::Gt => Box::new($column.ge($end)),
+            Comparison::Ge => Box::new($column.ge($start)),
+            Comparison::Eq => Box::new($column.ge($start).and($column.lt($end))),
+            Comparison::Ne => Box::new($column.lt($start).or($column.ge($end))),
+        }
+    };
+}
+
+/// Like [`compare!`] for nullable columns, `$range` being `None` compares with missing values
+macro_rules! compare_nullable {
+    ($column:expr, $comparison:expr, $range:expr) => {
+        match ($comparison, $range) {
+            (Comparison::Eq, None) => Box::new($column.is_null()) as BoxedCondition,
+            (Comparison::Ne, None) => Box::new($column.is_not_null()),
+            (_, None) => unreachable!("only = and != can compare with none"),
+            (comparison, Some((start, end))) => Box::new($column.is_not_null().and(compare!(
+                $column.assume_not_null(),
+                comparison,
+                start,
+                end
+            ))),
+        }
+    };
+}
+
+impl Condition {
+    fn to_sql(&self) -> BoxedCondition {
+        match self {
+            Condition::Completed(true) => Box::new(todos::completed.is_not_null()),
+            Condition::Completed(false) => Box::new(todos::completed.is_null()),
+            Condition::Tag(name) => Box::new(
+                todos::id.eq_any(
+                    todo_tags::table
+                        .inner_join(tags::table)
+                        .filter(tags::name.eq(name.clone()))
+                        .select(todo_tags::todo_id),
+                ),
+            ),
+            Condition::Project(None) => Box::new(todos::project_id.is_null()),
+            Condition::Project(Some(name)) => Box::new(
+                todos::project_id.is_not_null().and(
+                    todos::project_id.assume_not_null().eq_any(
+                        projects::table
+                            .filter(projects::name.eq(name.clone()))
+                            .select(projects::id),
+                    ),
+                ),
+            ),
+            Condition::TitleContains(text) => {
+                Box::new(todos::title.like(like_pattern(text)).escape('\\'))
+            }
+            Condition::NotesContains(text) => {
+                Box::new(todos::notes.like(like_pattern(text)).escape('\\'))
+            }
+            Condition::Time(TimeField::Created, comparison, moment) => {
+                let moment = moment.expect("created is never none");
+                compare!(todos::created, *comparison, moment.start, moment.end)
+            }
+            Condition::Time(TimeField::Due, comparison, moment) => compare_nullable!(
+                todos::due,
+                *comparison,
+                moment.map(|moment| (moment.start, moment.end))
+            ),
+            Condition::Time(TimeField::Completed, comparison, moment) => compare_nullable!(
+                todos::completed,
+                *comparison,
+                moment.map(|moment| (moment.start, moment.end))
+            ),
+            Condition::Priority(comparison, priority) => compare!(
+                todos::priority,
+                *comparison,
+                priority.level(),
+                priority.level() + 1
+            ),
+            Condition::Estimate(comparison, estimate) => compare_nullable!(
+                todos::estimate,
+                *comparison,
+                estimate.map(|estimate| {
+                    let minutes = estimate.num_minutes() as i32;
+                    (minutes, minutes + 1)
+                })
+            ),
+        }
+    }
+}
+
+fn like_pattern(text: &str) -> String {
+    let escaped = text
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    format!("%{}%", escaped)
+}
+
+/// Parses a `--where` expression, relative days like "friday" are resolved from now
+pub fn parse_filter(input: &str) -> Result<Filter, String> {
+    let tokens = tokenize(input)?;
+    let mut parser = Parser {
+        tokens: tokens.into_iter().peekable(),
+        now: Local::now(),
+    };
+    let filter = parser.parse_or()?;
+    match parser.tokens.next() {
+        None => Ok(filter),
+        Some(Token::RightParen) => Err("there's a ) without a matching (".to_string()),
+        Some(token) => Err(format!("unexpected {}", token)),
+    }
+}
+
+/// Checks a `--where` expression but keeps it as typed, so that it can be saved in a view and
+/// days like "friday" are resolved when the view is used
+pub fn check_filter(input: &str) -> Result<String, String> {
+    parse_filter(input).map(|_| input.to_string())
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    LeftParen,
+    RightParen,
+    And,
+    Or,
+    Not,
+    Condition(String, String, String),
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Token::LeftParen => write!(f, "("),
+            Token::RightParen => write!(f, ")"),
+            Token::And => write!(f, "and"),
+            Token::Or => write!(f, "or"),
+            Token::Not => write!(f, "not"),
+            Token::Condition(field, operator, value) => {
+                write!(f, "{}{}{}", field, operator, value)
+            }
+        }
+    }
+}
+
+const OPERATOR_CHARS: &str = "<>=!~:";
+
+fn tokenize(input: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+        if c == '(' || c == ')' {
+            chars.next();
+            tokens.push(if c == '(' {
+                Token::LeftParen
+            } else {
+                Token::RightParen
+            });
+            continue;
+        }
+        let mut field = String::new();
+        while let Some(c) = chars.next_if(|c| c.is_alphanumeric() || *c == '_') {
+            field.push(c);
+        }
+        if field.is_empty() {
+            return Err(format!("unexpected \"{}\"", c));
+        }
+        let mut operator = String::new();
+        while let Some(c) = chars.next_if(|c| OPERATOR_CHARS.contains(*c)) {
+            operator.push(c);
+        }
+        if operator.is_empty() {
+            tokens.push(match field.to_lowercase().as_str() {
+                "and" => Token::And,
+                "or" => Token::Or,
+                "not" => Token::Not,
+                _ => {
+                    return Err(format!(
+                        "\"{}\" needs an operator and a value, like {}:value or {}<value",
+                        field, field, field
+                    ))
+                }
+            });
+            continue;
+        }
+        let value = read_value(&mut chars)?;
+        if value.is_empty() {
+            return Err(format!("{}{} needs a value", field, operator));
+        }
+        tokens.push(Token::Condition(field.to_lowercase(), operator, value));
+    }
+    Ok(tokens)
+}
+
+/// Reads a "quoted value", where \" is a quote, or everything up to the next space or )
+fn read_value(chars: &mut Peekable<Chars>) -> Result<String, String> {
+    let mut value = String::new();
+    if chars.next_if_eq(&'"').is_some() {
+        loop {
+            match chars.next() {
+                Some('"') => return Ok(value),
+                Some('\\') => value.push(chars.next().unwrap_or('\\')),
+                Some(c) => value.push(c),
+                None => return Err(format!("missing the closing quote of \"{}", value)),
+            }
+        }
+    }
+    while let Some(c) = chars.next_if(|c| !c.is_whitespace() && *c != ')') {
+        value.push(c);
+    }
+    Ok(value)
+}
+
+struct Parser {
+    tokens: Peekable<std::vec::IntoIter<Token>>,
+    now: DateTime<Local>,
+}
+
+impl Parser {
+    fn parse_or(&mut self) -> Result<Filter, String> {
+        let mut filter = self.parse_and()?;
+        while self.tokens.next_if_eq(&Token::Or).is_some() {
+            filter = Filter::Or(Box::new(filter), Box::new(self.parse_and()?));
+        }
+        Ok(filter)
+    }
+
+    fn parse_and(&mut self) -> Result<Filter, String> {
+        let mut filter = self.parse_not()?;
+        loop {
+            match self.tokens.peek() {
+                Some(Token::And) => {
+                    self.tokens.next();
+                }
+                // Conditions next to each other have to match both
+                Some(Token::Not | Token::LeftParen | Token::Condition(..)) => {}
+                _ => return Ok(filter),
+            }
+            filter = filter.and(self.parse_not()?);
+        }
+    }
+
+    fn parse_not(&mut self) -> Result<Filter, String> {
+        if self.tokens.next_if_eq(&Token::Not).is_some() {
+            return Ok(Filter::Not(Box::new(self.parse_not()?)));
+        }
+        match self.tokens.next() {
+            Some(Token::LeftParen) => {
+                let filter = self.parse_or()?;
+                match self.tokens.next() {
+                    Some(Token::RightParen) => Ok(filter),
+                    _ => Err("missing a )".to_string()),
+                }
+            }
+            Some(Token::Condition(field, operator, value)) => Ok(Filter::Condition(
+                parse_condition(&field, &operator, &value, self.now)?,
+            )),
+            Some(token) => Err(format!("expected a condition, got {}", token)),
+            None => Err("expected a condition at the end".to_string()),
+        }
+    }
+}
+
+fn parse_condition(
+    field: &str,
+    operator: &str,
+    value: &str,
+    now: DateTime<Local>,
+) -> Result<Condition, String> {
+    let comparison = match operator {
+        "<" => Comparison::Lt,
+        "<=" => Comparison::Le,
+        ">" => Comparison::Gt,
+        ">=" => Comparison::Ge,
+        "=" | ":" => Comparison::Eq,
+        "!=" => Comparison::Ne,
+        "~" => {
+            return match field {
+                "title" => Ok(Condition::TitleContains(value.to_string())),
+                "notes" => Ok(Condition::NotesContains(value.to_string())),
+                _ => Err(format!(
+                    "{}~ isn't supported, only title~ and notes~ are",
+                    field
+                )),
+            }
+        }
+        _ => return Err(format!("unknown operator {} after {}", operator, field)),
+    };
+    let is_none = value.eq_ignore_ascii_case("none");
+    let only_equality = || {
+        if matches!(comparison, Comparison::Eq) {
+            Ok(())
+        } else {
+            Err(format!("{} only supports {}:value", field, field))
+        }
+    };
+    let none_or = |parse: &dyn Fn() -> Result<Condition, String>| {
+        if is_none && !matches!(comparison, Comparison::Eq | Comparison::Ne) {
+            Err(format!(
+                "{}{}none isn't supported, use {}=none or {}!=none",
+                field, operator, field, field
+            ))
+        } else {
+            parse()
+        }
+    };
+    match field {
+        "status" => {
+            only_equality()?;
+            match value.to_lowercase().as_str() {
+                "open" => Ok(Condition::Completed(false)),
+                "completed" | "done" => Ok(Condition::Completed(true)),
+                _ => Err(format!(
+                    "unknown status {}, expected open or completed",
+                    value
+                )),
+            }
+        }
+        "tag" => {
+            only_equality()?;
+            Ok(Condition::Tag(value.to_string()))
+        }
+        "project" => {
+            only_equality()?;
+            Ok(Condition::Project(
+                Some(value.to_string()).filter(|_| !is_none),
+            ))
+        }
+        "title" | "notes" => Err(format!(
+            "{} only supports {}~text, like {}~\"some words\"",
+            field, field, field
+        )),
+        "created" | "due" | "completed" => {
+            let time_field = match field {
+                "created" => TimeField::Created,
+                "due" => TimeField::Due,
+                _ => TimeField::Completed,
+            };
+            if is_none && time_field == TimeField::Created {
+                return Err("created is never none".to_string());
+            }
+            none_or(&|| {
+                let moment = if is_none {
+                    None
+                } else {
+                    Some(parse_moment(value, now)?)
+                };
+                Ok(Condition::Time(time_field, comparison, moment))
+            })
+        }
+        "priority" => Ok(Condition::Priority(comparison, value.parse()?)),
+        "estimate" => none_or(&|| {
+            let estimate = if is_none {
+                None
+            } else {
+                Some(crate::report::parse_duration(value)?)
+            };
+            Ok(Condition::Estimate(comparison, estimate))
+        }),
+        _ => Err(format!(
+            "unknown field {}, expected one of: status, tag, project, title, notes, created, due, \
+             completed, priority, estimate",
+            field
+        )),
+    }
+}
+
+/// "now" is the current minute, anything else is a whole day, like "friday", "tomorrow" or
+/// "2026-10-20"
+fn parse_moment(value: &str, now: DateTime<Local>) -> Result<Moment, String> {
+    if value.eq_ignore_ascii_case("now") {
+        return Ok(Moment {
+            start: now.to_utc(),
+            end: now.to_utc() + TimeDelta::minutes(1),
+        });
+    }
+    let day = chrono_english::parse_date_string(value, now, chrono_english::Dialect::Us)
+        .map_err(|_| {
+            format!(
+                "unknown day \"{}\", try something like: friday, 2026-10-20",
+                value
+            )
+        })?
+        .date_naive();
+    Ok(Moment {
+        start: crate::report::day_start(day),
+        end: crate::report::day_start(day + Days::new(1)),
+    })
+}
@@ -0,0 +1,126 @@
+use std::io::{Read, Write};
+
+use crate::formats::{parse_timestamp, ImportRow, ImportedTodo};
+use crate::output::TodoJson;
+
+/// Columns written by [`write_todos`], [`read_todos`] maps them back by name so they can be
+/// reordered or left out, only `title` is required
+pub const HEADER: [&str; 13] = [
+    "id",
+    "title",
+    "notes",
+    "created",
+    "completed",
+    "due",
+    "priority",
+    "project",
+    "tags",
+    "recurrence",
+    "estimate_minutes",
+    "parent",
+    "occurrence_of",
+];
+
+/// Writes TODOs with a header, tags are separated by spaces and timestamps are RFC 3339
+pub fn write_todos<W: Write>(writer: W, todos: &[TodoJson]) -> Result<(), ::csv::Error> {
+    let mut writer = ::csv::Writer::from_writer(writer);
+    writer.write_record(HEADER)?;
+    for todo in todos {
+        writer.write_record([
+            todo.id.clone(),
+            todo.title.clone(),
+            todo.notes.clone(),
+            todo.created.to_rfc3339(),
+            todo.completed.map(|ts| ts.to_rfc3339()).unwrap_or_default(),
+            todo.due.map(|ts| ts.to_rfc3339()).unwrap_or_default(),
+            todo.priority.clone(),
+            todo.project.clone().unwrap_or_default(),
+            todo.tags.join(" "),
+            todo.recurrence.clone().unwrap_or_default(),
+            todo.estimate_minutes
+                .map(|minutes| minutes.to_string())
+                .unwrap_or_default(),
+            todo.parent.clone().unwrap_or_default(),
+            todo.occurrence_of.clone().unwrap_or_default(),
+        ])?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Reads TODOs written by [`write_todos`]. A row that can't be parsed doesn't stop the others
+/// from being read. `occurrence_of` is ignored, imported TODOs start their own series.
+pub fn read_todos<R: Read>(reader: R) -> Result<Vec<ImportRow>, String> {
+    let mut reader = ::csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_reader(reader);
+    let header: Vec<String> = reader
+        .headers()
+        .map_err(|e| format!("couldn't read the header: {}", e))?
+        .iter()
+        .map(|column| column.trim().to_lowercase())
+        .collect();
+    if !header.iter().any(|column| column == "title") {
+        return Err("the header has no title column".to_string());
+    }
+    let mut rows = Vec::new();
+    for record in reader.records() {
+        let row = match record {
+            Ok(record) => {
+                let line = record
+                    .position()
+                    .map_or(0, |position| position.line() as usize);
+                let fields = header
+                    .iter()
+                    .map(String::as_str)
+                    .zip(record.iter().map(str::trim));
+                ImportRow {
+                    line,
+                    todo: parse_fields(fields),
+                }
+            }
+            Err(e) => ImportRow {
+                line: e.position().map_or(0, |position| position.line() as usize),
+                todo: Err(e.to_string()),
+            },
+        };
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+fn parse_fields<'a, I: Iterator<Item = (&'a str, &'a str)>>(
+    fields: I,
+) -> Result<ImportedTodo, String> {
+    let mut todo = ImportedTodo::default();
+    for (column, value) in fields {
+        if value.is_empty() {
+            continue;
+        }
+        match column {
+            "id" => todo.source_id = Some(value.to_string()),
+            "title" => todo.title = value.to_string(),
+            "notes" => todo.notes = value.to_string(),
+            "created" => todo.created = Some(parse_timestamp(value)?),
+            "completed" => todo.completed = Some(parse_timestamp(value)?),
+            "due" => todo.due = Some(parse_timestamp(value)?),
+            "priority" => todo.priority = Some(value.parse()?),
+            "project" => todo.project = Some(value.to_string()),
+            "tags" => {
+                todo.tags = value
+                    .split(|c: char| c.is_whitespace() || c == ',')
+                    .filter(|tag| !tag.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            }
+            "recurrence" => todo.recurrence = Some(value.parse()?),
+            "estimate_minutes" => todo.estimate = Some(crate::report::parse_duration(value)?),
+            "parent" => todo.parent = Some(value.to_string()),
+            _ => {}
+        }
+    }
+    if todo.title.is_empty() {
+        return Err("title is empty".to_string());
+    }
+    Ok(todo)
+}
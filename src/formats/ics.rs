@@ -0,0 +1,336 @@
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, Utc};
+
+use crate::formats::{ImportRow, ImportedTodo};
+use crate::models::Priority;
+use crate::output::TodoJson;
+use crate::recurrence::Recurrence;
+
+/// Suffix of the UIDs of exported TODOs, the part before it is the sqids id
+const UID_DOMAIN: &str = "@workingon";
+
+/// Lines longer than this many octets are folded, see RFC 5545 section 3.1
+const MAX_LINE_OCTETS: usize = 75;
+
+/// Writes a VCALENDAR with one VTODO per TODO, see https://datatracker.ietf.org/doc/html/rfc5545
+pub fn write_todos(todos: &[TodoJson]) -> String {
+    let now = Utc::now();
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        format!(
+            "PRODID:-//workingon//workingon {}//EN",
+            env!("CARGO_PKG_VERSION")
+        ),
+    ];
+    for todo in todos {
+        lines.push("BEGIN:VTODO".to_string());
+        lines.push(format!("UID:{}{}", todo.id, UID_DOMAIN));
+        lines.push(format!("DTSTAMP:{}", format_timestamp(now)));
+        lines.push(format!("CREATED:{}", format_timestamp(todo.created)));
+        lines.push(format!("SUMMARY:{}", escape(&todo.title)));
+        if !todo.notes.is_empty() {
+            lines.push(format!("DESCRIPTION:{}", escape(&todo.notes)));
+        }
+        if let Some(due) = todo.due {
+            lines.push(format!("DUE:{}", format_timestamp(due)));
+        }
+        if let Some(completed) = todo.completed {
+            lines.push(format!("COMPLETED:{}", format_timestamp(completed)));
+            lines.push("STATUS:COMPLETED".to_string());
+        } else {
+            lines.push("STATUS:NEEDS-ACTION".to_string());
+        }
+        if let Some(priority) = match todo.priority.parse::<Priority>() {
+            Ok(Priority::High) => Some(1),
+            Ok(Priority::Medium) => Some(5),
+            Ok(Priority::Low) => Some(9),
+            _ => None,
+        } {
+            lines.push(format!("PRIORITY:{}", priority));
+        }
+        if let Some(project) = &todo.project {
+            lines.push(format!("X-WORKINGON-PROJECT:{}", escape(project)));
+        }
+        if !todo.tags.is_empty() {
+            let tags: Vec<String> = todo.tags.iter().map(|tag| escape(tag)).collect();
+            lines.push(format!("CATEGORIES:{}", tags.join(",")));
+        }
+        if let Some(recurrence) = &todo.recurrence {
+            lines.push(format!("RRULE:{}", recurrence));
+        }
+        if let Some(minutes) = todo.estimate_minutes {
+            lines.push(format!("ESTIMATED-DURATION:PT{}M", minutes));
+        }
+        if let Some(parent) = &todo.parent {
+            lines.push(format!(
+                "RELATED-TO;RELTYPE=PARENT:{}{}",
+                parent, UID_DOMAIN
+            ));
+        }
+        lines.push("END:VTODO".to_string());
+    }
+    lines.push("END:VCALENDAR".to_string());
+    lines
+        .iter()
+        .map(|line| format!("{}\r\n", fold(line)))
+        .collect()
+}
+
+/// Reads every VTODO of a calendar, other components like VEVENTs are skipped. The line of a
+/// VTODO is the one of its `BEGIN:VTODO`.
+pub fn read_todos(content: &str) -> Vec<ImportRow> {
+    let mut rows = Vec::new();
+    let mut current: Option<(usize, Result<ImportedTodo, String>)> = None;
+    for (line_number, line) in unfold(content) {
+        let Some((name, params, value)) = split_property(&line) else {
+            continue;
+        };
+        match (name.as_str(), value) {
+            ("BEGIN", "VTODO") => {
+                current = Some((line_number, Ok(ImportedTodo::default())));
+            }
+            ("END", "VTODO") => {
+                if let Some((line, todo)) = current.take() {
+                    let todo = todo.and_then(|todo| {
+                        if todo.title.is_empty() {
+                            Err("SUMMARY is empty".to_string())
+                        } else {
+                            Ok(todo)
+                        }
+                    });
+                    rows.push(ImportRow { line, todo });
+                }
+            }
+            _ => {
+                if let Some((_, Ok(todo))) = &mut current {
+                    if let Err(e) = apply_property(todo, &name, &params, value) {
+                        current.as_mut().unwrap().1 = Err(e);
+                    }
+                }
+            }
+        }
+    }
+    rows
+}
+
+fn apply_property(
+    todo: &mut ImportedTodo,
+    name: &str,
+    params: &[(String, String)],
+    value: &str,
+) -> Result<(), String> {
+    match name {
+        "UID" => todo.source_id = Some(source_id(value)),
+        "SUMMARY" => todo.title = unescape(value),
+        "DESCRIPTION" => todo.notes = unescape(value),
+        "CREATED" => todo.created = Some(parse_timestamp(value, params)?),
+        "DUE" => todo.due = Some(parse_timestamp(value, params)?),
+        "COMPLETED" => todo.completed = Some(parse_timestamp(value, params)?),
+        // Some apps only set the status, the completion time is then unknown
+        "STATUS" if value == "COMPLETED" && todo.completed.is_none() => {
+            todo.completed = Some(Utc::now())
+        }
+        "PRIORITY" => {
+            todo.priority = match value.parse::<u8>() {
+                Ok(0) => None,
+                Ok(1..=4) => Some(Priority::High),
+                Ok(5) => Some(Priority::Medium),
+                Ok(6..=9) => Some(Priority::Low),
+                _ => return Err(format!("unknown PRIORITY {}", value)),
+            }
+        }
+        "X-WORKINGON-PROJECT" => todo.project = Some(unescape(value)),
+        "CATEGORIES" => todo.tags.extend(
+            split_list(value)
+                .iter()
+                .map(|tag| unescape(tag))
+                .filter(|tag| !tag.is_empty()),
+        ),
+        "RRULE" => todo.recurrence = Some(value.parse::<Recurrence>()?),
+        "ESTIMATED-DURATION" => todo.estimate = Some(parse_duration(value)?),
+        "RELATED-TO"
+            if params
+                .iter()
+                .all(|(param, reltype)| param != "RELTYPE" || reltype == "PARENT") =>
+        {
+            todo.parent = Some(source_id(value))
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Exported UIDs go back to the sqids id so that RELATED-TO can find them, others are kept as is
+fn source_id(uid: &str) -> String {
+    uid.strip_suffix(UID_DOMAIN).unwrap_or(uid).to_string()
+}
+
+/// Joins folded lines back, keeping the number of the line each one started on
+fn unfold(content: &str) -> Vec<(usize, String)> {
+    let mut lines: Vec<(usize, String)> = Vec::new();
+    for (index, line) in content.lines().enumerate() {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(continuation), Some((_, last))) => last.push_str(continuation),
+            _ => lines.push((index + 1, line.to_string())),
+        }
+    }
+    lines
+}
+
+/// Parameters of a property like `TZID=Europe/Paris`, names are uppercased
+type Params = Vec<(String, String)>;
+
+/// Splits "DUE;TZID=Europe/Paris:20261018T090000" into its name, parameters and value
+fn split_property(line: &str) -> Option<(String, Params, &str)> {
+    // Parameter values can be quoted and hold colons, the value starts at the first colon outside
+    // of quotes
+    let mut in_quotes = false;
+    let colon = line.char_indices().find_map(|(index, c)| match c {
+        '"' => {
+            in_quotes = !in_quotes;
+            None
+        }
+        ':' if !in_quotes => Some(index),
+        _ => None,
+    })?;
+    let (head, value) = (&line[..colon], &line[colon + 1..]);
+    let mut parts = head.split(';');
+    let name = parts.next()?.trim().to_uppercase();
+    let params = parts
+        .filter_map(|param| param.split_once('='))
+        .map(|(key, value)| (key.to_uppercase(), value.trim_matches('"').to_string()))
+        .collect();
+    Some((name, params, value))
+}
+
+fn fold(line: &str) -> String {
+    let mut folded = String::new();
+    let mut octets = 0;
+    for c in line.chars() {
+        if octets + c.len_utf8() > MAX_LINE_OCTETS {
+            folded.push_str("\r\n ");
+            // The leading space counts towards the line length
+            octets = 1;
+        }
+        folded.push(c);
+        octets += c.len_utf8();
+    }
+    folded
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') | Some('N') => unescaped.push('\n'),
+            Some(escaped) => unescaped.push(escaped),
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+/// Splits a comma separated value, leaving escaped commas alone
+fn split_list(value: &str) -> Vec<&str> {
+    let mut items = Vec::new();
+    let mut start = 0;
+    let mut escaped = false;
+    for (index, c) in value.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            ',' => {
+                items.push(&value[start..index]);
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    items.push(&value[start..]);
+    items
+}
+
+fn format_timestamp(ts: DateTime<Utc>) -> String {
+    ts.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// Parses UTC times like "20261018T090000Z", and floating times or dates like "20261018" in local
+/// time. Times with a TZID are read as local time too, which is right for calendars exported on
+/// the same machine.
+fn parse_timestamp(value: &str, params: &[(String, String)]) -> Result<DateTime<Utc>, String> {
+    if let Ok(ts) = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%SZ") {
+        return Ok(ts.and_utc());
+    }
+    let is_date = params
+        .iter()
+        .any(|(param, value_type)| param == "VALUE" && value_type == "DATE");
+    let naive = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S")
+        .ok()
+        .or_else(|| {
+            // Like todo.txt, TODOs due on a day are due at the end of it
+            let time = if is_date {
+                chrono::NaiveTime::from_hms_opt(23, 59, 0).unwrap()
+            } else {
+                chrono::NaiveTime::MIN
+            };
+            NaiveDate::parse_from_str(value, "%Y%m%d")
+                .ok()
+                .map(|day| day.and_time(time))
+        })
+        .ok_or(format!("unknown date-time \"{}\"", value))?;
+    naive
+        .and_local_timezone(Local)
+        .earliest()
+        .map(|ts| ts.to_utc())
+        .ok_or(format!(
+            "date-time \"{}\" doesn't exist in local time",
+            value
+        ))
+}
+
+/// Parses durations like "PT1H30M" or "P1D"
+fn parse_duration(value: &str) -> Result<chrono::TimeDelta, String> {
+    let unknown = || format!("unknown duration \"{}\"", value);
+    let rest = value
+        .strip_prefix('+')
+        .unwrap_or(value)
+        .strip_prefix('P')
+        .ok_or_else(unknown)?;
+    let mut seconds: i64 = 0;
+    let mut number = String::new();
+    for c in rest.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let unit_seconds = match c {
+            'T' if number.is_empty() => continue,
+            'W' => 7 * 24 * 3600,
+            'D' => 24 * 3600,
+            'H' => 3600,
+            'M' => 60,
+            'S' => 1,
+            _ => return Err(unknown()),
+        };
+        let count: i64 = number.parse().map_err(|_| unknown())?;
+        seconds += count * unit_seconds;
+        number.clear();
+    }
+    if !number.is_empty() || seconds <= 0 {
+        return Err(unknown());
+    }
+    Ok(chrono::TimeDelta::seconds(seconds))
+}
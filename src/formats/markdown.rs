@@ -0,0 +1,25 @@
+use chrono::Local;
+
+use crate::output::TodoJson;
+
+/// Writes a checklist, open TODOs first, with due dates after the title and notes indented
+/// beneath so they stay part of their item
+pub fn write_todos(todos: &[TodoJson]) -> String {
+    let (open, completed): (Vec<&TodoJson>, Vec<&TodoJson>) =
+        todos.iter().partition(|todo| todo.completed.is_none());
+    let mut markdown = String::new();
+    for todo in open.into_iter().chain(completed) {
+        let check = if todo.completed.is_some() { 'x' } else { ' ' };
+        markdown.push_str(&format!("- [{}] {}", check, todo.title));
+        if let Some(due) = todo.due {
+            let due = due.with_timezone(&Local).format("%Y-%m-%d %H:%M");
+            markdown.push_str(&format!(" (due {})", due));
+        }
+        markdown.push('\n');
+        for line in todo.notes.lines() {
+            markdown.push_str(format!("  {}", line).trim_end());
+            markdown.push('\n');
+        }
+    }
+    markdown
+}
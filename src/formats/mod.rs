@@ -0,0 +1,160 @@
+pub mod csv;
+pub mod ics;
+pub mod markdown;
+pub mod org;
+pub mod taskwarrior;
+pub mod todotxt;
+
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeDelta, Utc};
+use std::collections::{HashMap, HashSet};
+
+use crate::models::{NewTodo, Priority, Todos};
+use crate::recurrence::Recurrence;
+use crate::{Error, TodoStore};
+
+/// A TODO read from a file, before it's added to the database
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ImportedTodo {
+    /// Id the TODO had where it was exported from, so that other TODOs in the same file can refer
+    /// to it as their parent
+    pub source_id: Option<String>,
+    pub title: String,
+    pub notes: String,
+    pub created: Option<DateTime<Utc>>,
+    pub completed: Option<DateTime<Utc>>,
+    pub due: Option<DateTime<Utc>>,
+    pub priority: Option<Priority>,
+    pub project: Option<String>,
+    pub tags: Vec<String>,
+    pub recurrence: Option<Recurrence>,
+    pub estimate: Option<TimeDelta>,
+    /// `source_id` of the parent TODO
+    pub parent: Option<String>,
+}
+
+/// A parsed row, or why it couldn't be parsed, along with the line it came from
+pub struct ImportRow {
+    pub line: usize,
+    pub todo: Result<ImportedTodo, String>,
+}
+
+/// Parses timestamps as exported, like "2026-10-18T09:00:00Z", or as spreadsheets tend to
+/// rewrite them, like "2026-10-18 09:00" or "2026-10-18" in local time
+pub fn parse_timestamp(s: &str) -> Result<DateTime<Utc>, String> {
+    if let Ok(ts) = DateTime::parse_from_rfc3339(s) {
+        return Ok(ts.to_utc());
+    }
+    let naive = ["%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M"]
+        .iter()
+        .find_map(|fmt| NaiveDateTime::parse_from_str(s, fmt).ok())
+        .or_else(|| {
+            NaiveDate::parse_from_str(s, "%Y-%m-%d")
+                .ok()
+                .map(|day| day.and_time(chrono::NaiveTime::MIN))
+        })
+        .ok_or(format!("unknown timestamp \"{}\"", s))?;
+    naive
+        .and_local_timezone(Local)
+        .earliest()
+        .map(|ts| ts.to_utc())
+        .ok_or(format!("timestamp \"{}\" doesn't exist in local time", s))
+}
+
+/// Marks rows whose parent isn't in the same file, or whose project is archived, as failed
+pub fn check_references(store: &mut TodoStore, rows: &mut [ImportRow]) -> Result<(), Error> {
+    let source_ids: Vec<String> = rows
+        .iter()
+        .filter_map(|row| row.todo.as_ref().ok())
+        .filter_map(|todo| todo.source_id.clone())
+        .collect();
+    let archived_projects: Vec<String> = store
+        .get_projects()?
+        .into_iter()
+        .filter(|project| project.archived.is_some())
+        .map(|project| project.name)
+        .collect();
+    for row in rows.iter_mut() {
+        let Ok(todo) = &row.todo else {
+            continue;
+        };
+        let missing_parent = todo
+            .parent
+            .as_ref()
+            .filter(|parent| !source_ids.contains(parent))
+            .map(|parent| format!("parent {} isn't in the file", parent));
+        let archived_project = todo
+            .project
+            .as_ref()
+            .filter(|project| archived_projects.contains(project))
+            .map(|project| format!("project {} is archived", project));
+        if let Some(error) = missing_parent.or(archived_project) {
+            row.todo = Err(error);
+        }
+    }
+    Ok(())
+}
+
+/// Adds TODOs the same way `add` and the other commands would. Missing projects are created and
+/// parents are linked once every TODO exists, so they can come in any order. Recurrence is set
+/// last, so that importing a completed recurring TODO doesn't spawn its next occurrence. Nothing
+/// is added if one of them fails, and `undo` removes all of them at once.
+pub fn add_imported(store: &mut TodoStore, imported: &[ImportedTodo]) -> Result<Vec<Todos>, Error> {
+    store.operation(format!("import {} TODOs", imported.len()), |store| {
+        let mut project_names: HashSet<String> = store
+            .get_projects()?
+            .into_iter()
+            .map(|project| project.name)
+            .collect();
+        let mut new_ids: HashMap<&str, String> = HashMap::new();
+        let mut show_ids = Vec::new();
+        for todo in imported {
+            let created_todo = store.add_todo(&NewTodo {
+                title: &todo.title,
+                notes: &todo.notes,
+                created: todo.created.unwrap_or_else(Utc::now),
+            })?;
+            let show_id = crate::encode_id(created_todo.id.try_into().unwrap());
+            if todo.due.is_some() {
+                store.set_due(&show_id, todo.due)?;
+            }
+            if let Some(priority) = todo.priority {
+                store.set_priority(&show_id, priority)?;
+            }
+            if let Some(project) = &todo.project {
+                if project_names.insert(project.clone()) {
+                    store.add_project(project)?;
+                }
+                store.set_todo_project(&show_id, Some(project))?;
+            }
+            if !todo.tags.is_empty() {
+                store.add_tags(&show_id, &todo.tags)?;
+            }
+            if todo.estimate.is_some() {
+                store.set_estimate(&show_id, todo.estimate)?;
+            }
+            if let Some(completed) = todo.completed {
+                store.complete_todo(&show_id, Some(completed))?;
+            }
+            if todo.recurrence.is_some() {
+                store.set_recurrence(&show_id, todo.recurrence.as_ref())?;
+            }
+            if let Some(source_id) = &todo.source_id {
+                new_ids.insert(source_id, show_id.clone());
+            }
+            show_ids.push(show_id);
+        }
+        for (todo, show_id) in imported.iter().zip(&show_ids) {
+            if let Some(parent_id) = todo
+                .parent
+                .as_ref()
+                .and_then(|parent| new_ids.get(parent.as_str()))
+            {
+                store.set_parent(show_id, Some(parent_id))?;
+            }
+        }
+        show_ids
+            .iter()
+            .map(|show_id| store.get_todo(show_id))
+            .collect()
+    })
+}
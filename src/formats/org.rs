@@ -0,0 +1,83 @@
+use chrono::{DateTime, Local, Utc};
+
+use crate::output::TodoJson;
+
+/// Writes TODO headlines under an "Open" headline and DONE ones under a "Done" one, with
+/// DEADLINE and CLOSED timestamps, see https://orgmode.org/manual/Headlines.html
+pub fn write_todos(todos: &[TodoJson]) -> String {
+    let (open, completed): (Vec<&TodoJson>, Vec<&TodoJson>) =
+        todos.iter().partition(|todo| todo.completed.is_none());
+    let mut org = String::new();
+    for (heading, group) in [("Open", open), ("Done", completed)] {
+        if group.is_empty() {
+            continue;
+        }
+        org.push_str(&format!("* {}\n", heading));
+        for todo in group {
+            org.push_str(&headline(todo));
+        }
+    }
+    org
+}
+
+fn headline(todo: &TodoJson) -> String {
+    let keyword = if todo.completed.is_some() {
+        "DONE"
+    } else {
+        "TODO"
+    };
+    let mut words = vec![format!("** {}", keyword)];
+    let priority = match todo.priority.as_str() {
+        "high" => Some('A'),
+        "medium" => Some('B'),
+        "low" => Some('C'),
+        _ => None,
+    };
+    if let Some(priority) = priority {
+        words.push(format!("[#{}]", priority));
+    }
+    words.push(todo.title.clone());
+    if !todo.tags.is_empty() {
+        // Tags can't hold spaces or most punctuation
+        let tags: Vec<String> = todo
+            .tags
+            .iter()
+            .map(|tag| {
+                tag.chars()
+                    .map(|c| {
+                        if c.is_alphanumeric() || "_@#%".contains(c) {
+                            c
+                        } else {
+                            '_'
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+        words.push(format!(":{}:", tags.join(":")));
+    }
+    let mut headline = format!("{}\n", words.join(" "));
+
+    let mut planning = Vec::new();
+    if let Some(completed) = todo.completed {
+        planning.push(format!("CLOSED: [{}]", timestamp(completed)));
+    }
+    if let Some(due) = todo.due {
+        planning.push(format!("DEADLINE: <{}>", timestamp(due)));
+    }
+    if !planning.is_empty() {
+        headline.push_str(&format!("   {}\n", planning.join(" ")));
+    }
+    // Indented so that a line starting with * isn't read as a headline
+    for line in todo.notes.lines() {
+        headline.push_str(format!("   {}", line).trim_end());
+        headline.push('\n');
+    }
+    headline
+}
+
+fn timestamp(ts: DateTime<Utc>) -> String {
+    ts.with_timezone(&Local)
+        .format("%Y-%m-%d %a %H:%M")
+        .to_string()
+}
@@ -0,0 +1,140 @@
+use chrono::{DateTime, Local, NaiveDateTime, Utc};
+use serde::Deserialize;
+
+use crate::formats::{ImportRow, ImportedTodo};
+use crate::models::Priority;
+
+/// A task as printed by `task export`, fields workingon has no use for are ignored
+#[derive(Deserialize)]
+struct Task {
+    uuid: Option<String>,
+    #[serde(default)]
+    description: String,
+    #[serde(default)]
+    status: String,
+    entry: Option<String>,
+    end: Option<String>,
+    due: Option<String>,
+    priority: Option<String>,
+    project: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    annotations: Vec<Annotation>,
+}
+
+#[derive(Deserialize)]
+struct Annotation {
+    entry: Option<String>,
+    description: String,
+}
+
+/// What was read from a Taskwarrior export, deleted tasks and recurring templates are skipped
+pub struct TaskwarriorImport {
+    pub rows: Vec<ImportRow>,
+    pub skipped: usize,
+}
+
+/// Reads the output of `task export`. It prints a task per line, within a JSON array or without
+/// one for older versions, so rows get the line of their task. Other JSON arrays of tasks, like
+/// ones reformatted by jq, are read as a whole and rows get the position of their task instead.
+pub fn read_todos(content: &str) -> Result<TaskwarriorImport, String> {
+    let lines: Vec<(usize, &str)> = content
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim().trim_end_matches(',')))
+        .filter(|(_, line)| !matches!(*line, "" | "[" | "]"))
+        .collect();
+    let tasks: Vec<(usize, serde_json::Value)> = match lines
+        .iter()
+        .map(|(line, json)| serde_json::from_str(json).map(|value| (*line, value)))
+        .collect::<Result<Vec<_>, _>>()
+    {
+        Ok(tasks) => tasks,
+        Err(_) => serde_json::from_str::<Vec<serde_json::Value>>(content)
+            .map_err(|e| format!("isn't a Taskwarrior export: {}", e))?
+            .into_iter()
+            .enumerate()
+            .map(|(index, value)| (index + 1, value))
+            .collect(),
+    };
+
+    let mut import = TaskwarriorImport {
+        rows: Vec::new(),
+        skipped: 0,
+    };
+    for (line, value) in tasks {
+        let task = match serde_json::from_value::<Task>(value) {
+            Ok(task) => task,
+            Err(e) => {
+                import.rows.push(ImportRow {
+                    line,
+                    todo: Err(e.to_string()),
+                });
+                continue;
+            }
+        };
+        // Recurring templates only spawn the pending tasks, which are exported on their own
+        if matches!(task.status.as_str(), "deleted" | "recurring") {
+            import.skipped += 1;
+            continue;
+        }
+        import.rows.push(ImportRow {
+            line,
+            todo: to_imported(task),
+        });
+    }
+    Ok(import)
+}
+
+fn to_imported(task: Task) -> Result<ImportedTodo, String> {
+    if task.description.trim().is_empty() {
+        return Err("description is empty".to_string());
+    }
+    let completed = match task.status.as_str() {
+        "completed" => Some(match &task.end {
+            Some(end) => parse_timestamp(end)?,
+            None => Utc::now(),
+        }),
+        "pending" | "waiting" | "" => None,
+        status => return Err(format!("unknown status {}", status)),
+    };
+    let notes = task
+        .annotations
+        .iter()
+        .map(|annotation| {
+            let day = annotation
+                .entry
+                .as_deref()
+                .and_then(|entry| parse_timestamp(entry).ok())
+                .map(|ts| ts.with_timezone(&Local).format("%Y-%m-%d ").to_string());
+            format!("{}{}", day.unwrap_or_default(), annotation.description)
+        })
+        .collect::<Vec<String>>()
+        .join("\n");
+    Ok(ImportedTodo {
+        source_id: task.uuid,
+        title: task.description.trim().to_string(),
+        notes,
+        created: task.entry.as_deref().map(parse_timestamp).transpose()?,
+        completed,
+        due: task.due.as_deref().map(parse_timestamp).transpose()?,
+        priority: match task.priority.as_deref() {
+            Some("H") => Some(Priority::High),
+            Some("M") => Some(Priority::Medium),
+            Some("L") => Some(Priority::Low),
+            Some(priority) => return Err(format!("unknown priority {}", priority)),
+            None => None,
+        },
+        project: task.project,
+        tags: task.tags,
+        ..Default::default()
+    })
+}
+
+/// Parses Taskwarrior's timestamps, like "20261018T090000Z"
+fn parse_timestamp(s: &str) -> Result<DateTime<Utc>, String> {
+    NaiveDateTime::parse_from_str(s, "%Y%m%dT%H%M%SZ")
+        .map(|ts| ts.and_utc())
+        .map_err(|_| format!("unknown timestamp \"{}\"", s))
+}
@@ -0,0 +1,260 @@
+use chrono::{DateTime, Local, NaiveDate, NaiveTime, TimeDelta, Utc};
+use std::fmt;
+
+use crate::formats::{ImportRow, ImportedTodo};
+use crate::models::Priority;
+use crate::output::TodoJson;
+use crate::recurrence::{Frequency, Recurrence};
+
+/// A single line of a todo.txt file, see https://github.com/todotxt/todo.txt
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Task {
+    pub completed: Option<NaiveDate>,
+    /// Like 'A', completed tasks keep theirs in a `pri:` key
+    pub priority: Option<char>,
+    pub created: Option<NaiveDate>,
+    /// Words that aren't a project, context or known key
+    pub title: String,
+    pub projects: Vec<String>,
+    pub contexts: Vec<String>,
+    pub due: Option<NaiveDate>,
+    /// Like "1w" or "+3d"
+    pub rec: Option<String>,
+    pub id: Option<String>,
+}
+
+impl Task {
+    pub fn parse(line: &str) -> Task {
+        let mut task = Task::default();
+        let mut words = line.split_whitespace().peekable();
+        if words.peek() == Some(&"x") {
+            words.next();
+            // The completion date is optional
+            task.completed = Some(
+                words
+                    .next_if(|word| is_date(word))
+                    .map_or(Local::now().date_naive(), parse_date),
+            );
+        }
+        if task.completed.is_none() {
+            if let Some(word) = words.next_if(|word| parse_priority(word).is_some()) {
+                task.priority = parse_priority(word);
+            }
+        }
+        task.created = words.next_if(|word| is_date(word)).map(parse_date);
+        let mut title_words = Vec::new();
+        for word in words {
+            if let Some(project) = word.strip_prefix('+').filter(|name| !name.is_empty()) {
+                task.projects.push(project.to_string());
+            } else if let Some(context) = word.strip_prefix('@').filter(|name| !name.is_empty()) {
+                task.contexts.push(context.to_string());
+            } else if let Some(due) = word.strip_prefix("due:").filter(|date| is_date(date)) {
+                task.due = Some(parse_date(due));
+            } else if let Some(rec) = word.strip_prefix("rec:").filter(|rec| !rec.is_empty()) {
+                task.rec = Some(rec.to_string());
+            } else if let Some(id) = word.strip_prefix("id:").filter(|id| !id.is_empty()) {
+                task.id = Some(id.to_string());
+            } else if let Some(priority) = word
+                .strip_prefix("pri:")
+                .and_then(|priority| parse_priority(&format!("({})", priority)))
+            {
+                task.priority = Some(priority);
+            } else {
+                title_words.push(word);
+            }
+        }
+        task.title = title_words.join(" ");
+        task
+    }
+
+    pub fn from_todo(todo: &TodoJson) -> Task {
+        let priority = match todo.priority.parse::<Priority>() {
+            Ok(Priority::High) => Some('A'),
+            Ok(Priority::Medium) => Some('B'),
+            Ok(Priority::Low) => Some('C'),
+            _ => None,
+        };
+        Task {
+            completed: todo.completed.map(local_date),
+            priority,
+            created: Some(local_date(todo.created)),
+            title: todo.title.clone(),
+            projects: todo.project.iter().map(|name| no_spaces(name)).collect(),
+            contexts: todo.tags.iter().map(|tag| no_spaces(tag)).collect(),
+            due: todo.due.map(local_date),
+            rec: todo
+                .recurrence
+                .as_ref()
+                .and_then(|rule| rule.parse::<Recurrence>().ok())
+                .map(|recurrence| to_rec(&recurrence)),
+            id: Some(todo.id.clone()),
+        }
+    }
+
+    pub fn priority_level(&self) -> Priority {
+        match self.priority {
+            Some('A') => Priority::High,
+            Some('B') => Priority::Medium,
+            Some(_) => Priority::Low,
+            None => Priority::Unset,
+        }
+    }
+
+    pub fn recurrence(&self) -> Result<Option<Recurrence>, String> {
+        self.rec.as_deref().map(parse_rec).transpose()
+    }
+
+    /// Due dates only have a day, so TODOs are due at the end of it
+    pub fn due_timestamp(&self) -> Option<DateTime<Utc>> {
+        self.due
+            .map(|day| local_timestamp(day, NaiveTime::from_hms_opt(23, 59, 0).unwrap()))
+    }
+
+    pub fn to_imported(&self) -> Result<ImportedTodo, String> {
+        if self.title.is_empty() {
+            return Err("title is empty".to_string());
+        }
+        let mut projects = self.projects.iter();
+        Ok(ImportedTodo {
+            source_id: self.id.clone(),
+            title: self.title.clone(),
+            created: self.created.map(|day| local_timestamp(day, NaiveTime::MIN)),
+            completed: self
+                .completed
+                .map(|day| local_timestamp(day, NaiveTime::MIN)),
+            due: self.due_timestamp(),
+            priority: self.priority.map(|_| self.priority_level()),
+            project: projects.next().cloned(),
+            // Only one project fits, keep the others around as tags
+            tags: self.contexts.iter().chain(projects).cloned().collect(),
+            recurrence: self.recurrence()?,
+            ..Default::default()
+        })
+    }
+}
+
+impl fmt::Display for Task {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut words: Vec<String> = Vec::new();
+        if let Some(completed) = self.completed {
+            words.push("x".to_string());
+            words.push(completed.to_string());
+        } else if let Some(priority) = self.priority {
+            words.push(format!("({})", priority));
+        }
+        if let Some(created) = self.created {
+            words.push(created.to_string());
+        }
+        words.push(self.title.clone());
+        words.extend(self.projects.iter().map(|project| format!("+{}", project)));
+        words.extend(self.contexts.iter().map(|context| format!("@{}", context)));
+        if let Some(due) = self.due {
+            words.push(format!("due:{}", due));
+        }
+        if let Some(rec) = &self.rec {
+            words.push(format!("rec:{}", rec));
+        }
+        if let Some(id) = &self.id {
+            words.push(format!("id:{}", id));
+        }
+        if let (Some(_), Some(priority)) = (self.completed, self.priority) {
+            words.push(format!("pri:{}", priority));
+        }
+        write!(f, "{}", words.join(" "))
+    }
+}
+
+/// Reads every non-empty line of a todo.txt file, `id:` keys become the source id
+pub fn read_todos(content: &str) -> Vec<ImportRow> {
+    content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| ImportRow {
+            line: index + 1,
+            todo: Task::parse(line).to_imported(),
+        })
+        .collect()
+}
+
+pub fn write_todos(todos: &[TodoJson]) -> String {
+    todos
+        .iter()
+        .map(|todo| format!("{}\n", Task::from_todo(todo)))
+        .collect()
+}
+
+fn is_date(word: &str) -> bool {
+    NaiveDate::parse_from_str(word, "%Y-%m-%d").is_ok()
+}
+
+fn parse_date(word: &str) -> NaiveDate {
+    NaiveDate::parse_from_str(word, "%Y-%m-%d").unwrap()
+}
+
+fn parse_priority(word: &str) -> Option<char> {
+    let mut chars = word.chars();
+    match (chars.next(), chars.next(), chars.next(), chars.next()) {
+        (Some('('), Some(priority), Some(')'), None) if priority.is_ascii_uppercase() => {
+            Some(priority)
+        }
+        _ => None,
+    }
+}
+
+fn local_date(ts: DateTime<Utc>) -> NaiveDate {
+    ts.with_timezone(&Local).date_naive()
+}
+
+/// `time` on `day` in local time. A time in a daylight saving gap is moved an hour later, past
+/// the gap, like clocks are.
+fn local_timestamp(day: NaiveDate, time: NaiveTime) -> DateTime<Utc> {
+    let naive = day.and_time(time);
+    naive
+        .and_local_timezone(Local)
+        .earliest()
+        .or_else(|| {
+            (naive + TimeDelta::hours(1))
+                .and_local_timezone(Local)
+                .earliest()
+        })
+        .map_or_else(|| naive.and_utc(), |ts| ts.to_utc())
+}
+
+/// Names as they appear after `+` or `@`, todo.txt splits words on whitespace
+pub fn no_spaces(name: &str) -> String {
+    name.split_whitespace().collect::<Vec<&str>>().join("-")
+}
+
+fn to_rec(recurrence: &Recurrence) -> String {
+    let unit = match recurrence.frequency {
+        Frequency::Daily => 'd',
+        Frequency::Weekly => 'w',
+        Frequency::Monthly => 'm',
+        Frequency::Yearly => 'y',
+    };
+    format!("{}{}", recurrence.interval, unit)
+}
+
+/// Parses `rec:` values like "1w" or "+3d", the strict "+" prefix is ignored
+fn parse_rec(rec: &str) -> Result<Recurrence, String> {
+    let rec = rec.strip_prefix('+').unwrap_or(rec);
+    let unknown = || format!("unknown rec:{}", rec);
+    let (count, unit) = rec.split_at(rec.len() - rec.chars().last().map_or(0, char::len_utf8));
+    let frequency = match unit {
+        "d" => Frequency::Daily,
+        "w" => Frequency::Weekly,
+        "m" => Frequency::Monthly,
+        "y" => Frequency::Yearly,
+        _ => return Err(unknown()),
+    };
+    let interval: u32 = if count.is_empty() {
+        1
+    } else {
+        count.parse().map_err(|_| unknown())?
+    };
+    if interval == 0 {
+        return Err(unknown());
+    }
+    Ok(Recurrence::every(interval, frequency))
+}
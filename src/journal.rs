@@ -0,0 +1,331 @@
+use std::collections::BTreeMap;
+
+use chrono::Utc;
+use diesel::prelude::*;
+use diesel::sqlite::SqliteConnection;
+use serde::{Deserialize, Serialize};
+
+use crate::models::{
+    NewOperation, NewTag, Operation, Project, Session, Tag, TodoDependency, TodoTag, Todos, View,
+};
+use crate::schema::{
+    operations, projects, sessions, tags, todo_dependencies, todo_tags, todos, views,
+};
+
+/// How many operations are kept, older ones can't be undone anymore
+pub const JOURNAL_LENGTH: i32 = 100;
+
+/// A row the journal keeps track of
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Key {
+    Todo(i32),
+    Project(i32),
+    View(i32),
+}
+
+/// A TODO along with the rows that belong to it
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct TodoSnapshot {
+    pub todo: Todos,
+    pub tags: Vec<String>,
+    /// Ids of the TODOs it's blocked by, the TODOs it blocks have those rows in their snapshots
+    pub blockers: Vec<i32>,
+    pub sessions: Vec<Session>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub enum Snapshot {
+    Todo(TodoSnapshot),
+    Project(Project),
+    View(View),
+}
+
+/// How a row looked before and after an operation, `None` when it didn't exist
+#[derive(Serialize, Deserialize)]
+pub struct Change {
+    pub key: Key,
+    pub before: Option<Snapshot>,
+    pub after: Option<Snapshot>,
+}
+
+/// Highest ids in use, anything above them was created by the operation in progress
+struct MaxIds {
+    todo: i32,
+    project: i32,
+    view: i32,
+}
+
+impl MaxIds {
+    fn load(conn: &mut SqliteConnection) -> QueryResult<Self> {
+        Ok(MaxIds {
+            todo: todos::table
+                .select(diesel::dsl::max(todos::id))
+                .first::<Option<i32>>(conn)?
+                .unwrap_or(0),
+            project: projects::table
+                .select(diesel::dsl::max(projects::id))
+                .first::<Option<i32>>(conn)?
+                .unwrap_or(0),
+            view: views::table
+                .select(diesel::dsl::max(views::id))
+                .first::<Option<i32>>(conn)?
+                .unwrap_or(0),
+        })
+    }
+
+    fn created(&self, key: Key) -> bool {
+        match key {
+            Key::Todo(id) => id > self.todo,
+            Key::Project(id) => id > self.project,
+            Key::View(id) => id > self.view,
+        }
+    }
+}
+
+/// The operation in progress. Rows are snapshotted the first time they're about to change, rows
+/// that didn't exist before are found once it's done by their ids.
+pub(crate) struct PendingOperation {
+    description: String,
+    before: BTreeMap<Key, Option<Snapshot>>,
+    max_ids: MaxIds,
+}
+
+impl PendingOperation {
+    pub(crate) fn start(conn: &mut SqliteConnection, description: String) -> QueryResult<Self> {
+        Ok(PendingOperation {
+            description,
+            before: BTreeMap::new(),
+            max_ids: MaxIds::load(conn)?,
+        })
+    }
+
+    pub(crate) fn track(&mut self, conn: &mut SqliteConnection, key: Key) -> QueryResult<()> {
+        if !self.before.contains_key(&key) {
+            let before = if self.max_ids.created(key) {
+                None
+            } else {
+                snapshot(conn, key)?
+            };
+            self.before.insert(key, before);
+        }
+        Ok(())
+    }
+
+    /// Writes the operation to the journal, unless it didn't change anything. Operations that
+    /// were undone can't be redone after a new one.
+    pub(crate) fn finish(mut self, conn: &mut SqliteConnection) -> QueryResult<()> {
+        let created_keys = todos::table
+            .filter(todos::id.gt(self.max_ids.todo))
+            .select(todos::id)
+            .load::<i32>(conn)?
+            .into_iter()
+            .map(Key::Todo)
+            .chain(
+                projects::table
+                    .filter(projects::id.gt(self.max_ids.project))
+                    .select(projects::id)
+                    .load::<i32>(conn)?
+                    .into_iter()
+                    .map(Key::Project),
+            )
+            .chain(
+                views::table
+                    .filter(views::id.gt(self.max_ids.view))
+                    .select(views::id)
+                    .load::<i32>(conn)?
+                    .into_iter()
+                    .map(Key::View),
+            );
+        for key in created_keys.collect::<Vec<Key>>() {
+            self.before.entry(key).or_insert(None);
+        }
+        let mut changes = Vec::new();
+        for (key, before) in self.before {
+            let after = snapshot(conn, key)?;
+            if before != after {
+                changes.push(Change { key, before, after });
+            }
+        }
+        if changes.is_empty() {
+            return Ok(());
+        }
+        let changes = serde_json::to_string(&changes)
+            .map_err(|e| diesel::result::Error::SerializationError(Box::new(e)))?;
+        diesel::delete(operations::table.filter(operations::undone.eq(true))).execute(conn)?;
+        let operation_id = diesel::insert_into(operations::table)
+            .values(NewOperation {
+                created: Utc::now(),
+                description: &self.description,
+                changes,
+            })
+            .returning(operations::id)
+            .get_result::<i32>(conn)?;
+        diesel::delete(operations::table.filter(operations::id.le(operation_id - JOURNAL_LENGTH)))
+            .execute(conn)?;
+        Ok(())
+    }
+}
+
+fn snapshot(conn: &mut SqliteConnection, key: Key) -> QueryResult<Option<Snapshot>> {
+    match key {
+        Key::Todo(todo_id) => {
+            let Some(todo) = todos::table
+                .find(todo_id)
+                .select(Todos::as_select())
+                .first(conn)
+                .optional()?
+            else {
+                return Ok(None);
+            };
+            Ok(Some(Snapshot::Todo(TodoSnapshot {
+                todo,
+                tags: todo_tags::table
+                    .inner_join(tags::table)
+                    .filter(todo_tags::todo_id.eq(todo_id))
+                    .select(tags::name)
+                    .order_by(tags::name)
+                    .load(conn)?,
+                blockers: todo_dependencies::table
+                    .filter(todo_dependencies::todo_id.eq(todo_id))
+                    .select(todo_dependencies::blocker_id)
+                    .order_by(todo_dependencies::blocker_id)
+                    .load(conn)?,
+                sessions: sessions::table
+                    .filter(sessions::todo_id.eq(todo_id))
+                    .select(Session::as_select())
+                    .order_by(sessions::id)
+                    .load(conn)?,
+            })))
+        }
+        Key::Project(project_id) => Ok(projects::table
+            .find(project_id)
+            .select(Project::as_select())
+            .first(conn)
+            .optional()?
+            .map(Snapshot::Project)),
+        Key::View(view_id) => Ok(views::table
+            .find(view_id)
+            .select(View::as_select())
+            .first(conn)
+            .optional()?
+            .map(Snapshot::View)),
+    }
+}
+
+fn remove(conn: &mut SqliteConnection, key: Key) -> QueryResult<()> {
+    match key {
+        Key::Todo(todo_id) => {
+            diesel::delete(todo_tags::table.filter(todo_tags::todo_id.eq(todo_id)))
+                .execute(conn)?;
+            diesel::delete(todo_dependencies::table.filter(todo_dependencies::todo_id.eq(todo_id)))
+                .execute(conn)?;
+            diesel::delete(sessions::table.filter(sessions::todo_id.eq(todo_id))).execute(conn)?;
+            diesel::delete(todos::table.find(todo_id)).execute(conn)?;
+        }
+        Key::Project(project_id) => {
+            diesel::delete(projects::table.find(project_id)).execute(conn)?;
+        }
+        Key::View(view_id) => {
+            diesel::delete(views::table.find(view_id)).execute(conn)?;
+        }
+    }
+    Ok(())
+}
+
+fn insert(conn: &mut SqliteConnection, snapshot: &Snapshot) -> QueryResult<()> {
+    match snapshot {
+        Snapshot::Todo(snapshot) => {
+            diesel::insert_into(todos::table)
+                .values(&snapshot.todo)
+                .execute(conn)?;
+            for tag_name in &snapshot.tags {
+                diesel::insert_or_ignore_into(tags::table)
+                    .values(NewTag { name: tag_name })
+                    .execute(conn)?;
+                let tag = tags::table
+                    .select(Tag::as_select())
+                    .filter(tags::name.eq(tag_name))
+                    .first(conn)?;
+                diesel::insert_into(todo_tags::table)
+                    .values(TodoTag {
+                        todo_id: snapshot.todo.id,
+                        tag_id: tag.id,
+                    })
+                    .execute(conn)?;
+            }
+            for blocker_id in &snapshot.blockers {
+                diesel::insert_into(todo_dependencies::table)
+                    .values(TodoDependency {
+                        todo_id: snapshot.todo.id,
+                        blocker_id: *blocker_id,
+                    })
+                    .execute(conn)?;
+            }
+            for session in &snapshot.sessions {
+                diesel::insert_into(sessions::table)
+                    .values(session)
+                    .execute(conn)?;
+            }
+        }
+        Snapshot::Project(project) => {
+            diesel::insert_into(projects::table)
+                .values(project)
+                .execute(conn)?;
+        }
+        Snapshot::View(view) => {
+            diesel::insert_into(views::table)
+                .values(view)
+                .execute(conn)?;
+        }
+    }
+    Ok(())
+}
+
+/// Puts every row of an operation back how it was before it, or after it when redoing. Every row
+/// is removed before any is inserted, so that rows that are unique together, like the active
+/// session, don't get in each other's way.
+fn apply(conn: &mut SqliteConnection, operation: &Operation, redo: bool) -> QueryResult<()> {
+    let changes: Vec<Change> = serde_json::from_str(&operation.changes)
+        .map_err(|e| diesel::result::Error::DeserializationError(Box::new(e)))?;
+    for change in &changes {
+        remove(conn, change.key)?;
+    }
+    for change in &changes {
+        let side = if redo { &change.after } else { &change.before };
+        if let Some(snapshot) = side {
+            insert(conn, snapshot)?;
+        }
+    }
+    diesel::update(operations::table.find(operation.id))
+        .set(operations::undone.eq(!redo))
+        .execute(conn)?;
+    Ok(())
+}
+
+/// Reverts the last `n` operations that weren't undone yet, newest first, and returns them
+pub(crate) fn undo(conn: &mut SqliteConnection, n: usize) -> QueryResult<Vec<Operation>> {
+    let undone: Vec<Operation> = operations::table
+        .filter(operations::undone.eq(false))
+        .select(Operation::as_select())
+        .order_by(operations::id.desc())
+        .limit(n.try_into().unwrap_or(i64::MAX))
+        .load(conn)?;
+    for operation in &undone {
+        apply(conn, operation, false)?;
+    }
+    Ok(undone)
+}
+
+/// Applies the first `n` undone operations again, oldest first, and returns them
+pub(crate) fn redo(conn: &mut SqliteConnection, n: usize) -> QueryResult<Vec<Operation>> {
+    let redone: Vec<Operation> = operations::table
+        .filter(operations::undone.eq(true))
+        .select(Operation::as_select())
+        .order_by(operations::id)
+        .limit(n.try_into().unwrap_or(i64::MAX))
+        .load(conn)?;
+    for operation in &redone {
+        apply(conn, operation, true)?;
+    }
+    Ok(redone)
+}
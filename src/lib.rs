@@ -1,32 +1,43 @@
 pub mod cli;
+pub mod config;
 pub mod constants;
+pub mod error;
+pub mod filter;
+pub mod formats;
+pub mod journal;
 pub mod models;
+pub mod output;
+pub mod recurrence;
+pub mod report;
 pub mod schema;
+pub mod search;
+pub mod storage;
+pub mod store;
+pub mod sync;
+pub mod urgency;
 
 use chrono::*;
-use colored::Colorize;
-use diesel::prelude::*;
 use diesel::sqlite::SqliteConnection;
-use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
+use diesel_migrations::{embed_migrations, EmbeddedMigrations};
 use dirs::data_dir;
-use models::{NewTodo, Todos};
+use models::{NewTodo, NewView, Priority, Project, Session, Todos, View};
 use sqids::Sqids;
-use std::{
-    io::{Read, Write},
-    str::FromStr,
-};
+use std::io::{Read, Write};
+
+pub use self::error::Error;
+pub use self::storage::Storage;
+pub use self::store::TodoStore;
 
 use self::constants::{BIN, DEFAULT_EDITOR};
-use self::schema::todos;
 
 // Constants only used in this file
 pub const COMMENT_DISCLAIMER: &str = "# This is a comment, lines starting with a # will be ignored";
 pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations");
 
-fn parse_due_str(s: &String) -> DateTime<Utc> {
+fn parse_due_str(s: &str) -> Result<DateTime<Utc>, Error> {
     chrono_english::parse_date_string(s, Local::now(), chrono_english::Dialect::Us)
-        .expect("Parsing due date didn't work")
-        .to_utc()
+        .map(|ts| ts.to_utc())
+        .map_err(|_| Error::BadDate(s.to_string()))
 }
 
 fn create_sqids_encoder_with_custom_alphabet() -> Sqids {
@@ -37,50 +48,94 @@ fn create_sqids_encoder_with_custom_alphabet() -> Sqids {
         .expect("Failed to create Sqids encoder with custom alphabet configuration")
 }
 
+/// Ids come from the database, so encoding them can't fail
 pub fn encode_id(i: u64) -> String {
     create_sqids_encoder_with_custom_alphabet()
-        .encode(&vec![i])
+        .encode(&[i])
         .expect("Problem encoding id")
 }
 
-pub fn decode_id(s: &str) -> i32 {
-    // TODO can I make this nicer?
-    (*create_sqids_encoder_with_custom_alphabet()
+pub fn decode_id(s: &str) -> Result<i32, Error> {
+    create_sqids_encoder_with_custom_alphabet()
         .decode(s)
         .first()
-        .expect("Couldn't decode id"))
-    .try_into()
-    .unwrap()
+        .and_then(|id| i32::try_from(*id).ok())
+        .ok_or_else(|| Error::InvalidId(s.to_string()))
+}
+
+/// Turns the number of rows an update changed into `NotFound` when there wasn't a TODO to change
+fn check_updated(rows: usize, show_id: &str) -> Result<(), Error> {
+    if rows == 0 {
+        return Err(Error::todo_not_found(show_id));
+    }
+    Ok(())
+}
+
+/// Turns an estimate into the minutes it is stored as
+fn estimate_minutes(estimate: Option<TimeDelta>) -> Result<Option<i32>, Error> {
+    estimate
+        .map(|duration| {
+            duration
+                .num_minutes()
+                .try_into()
+                .map_err(|_| Error::Conflict(format!("An estimate of {} is too long", duration)))
+        })
+        .transpose()
+}
+
+fn parse_recurrence(show_id: &str, rule: &str) -> Result<recurrence::Recurrence, Error> {
+    rule.parse().map_err(|e| {
+        Error::Conflict(format!(
+            "TODO: {} has an invalid recurrence: {}",
+            show_id, e
+        ))
+    })
+}
+
+/// When the occurrence after `ts` is due, for TODOs that were saved with a huge interval
+fn next_occurrence(
+    show_id: &str,
+    recurrence: &recurrence::Recurrence,
+    ts: DateTime<Utc>,
+) -> Result<DateTime<Utc>, Error> {
+    recurrence
+        .next_after(ts)
+        .map_err(|e| Error::Conflict(format!("TODO: {} can't repeat anymore, {}", show_id, e)))
 }
 
 // Path-related functions
-pub fn get_project_data_folder() -> std::path::PathBuf {
+pub fn get_project_data_folder() -> Result<std::path::PathBuf, Error> {
     let env_var_name = format!("{}_data_dir", BIN).to_uppercase();
     match std::env::var(env_var_name) {
-        Ok(dd) => std::path::PathBuf::from_str(&dd).expect("Env var data dir is not a valid path"),
+        Ok(dd) => Ok(std::path::PathBuf::from(dd)),
         Err(_) => {
-            let mut data_folder = data_dir().expect("Couldn't get data dir");
+            let mut data_folder = data_dir().ok_or_else(|| Error::Io {
+                path: std::path::PathBuf::from(BIN),
+                source: std::io::Error::other(format!(
+                    "there's no data folder for this user, set {}_DATA_DIR",
+                    BIN.to_uppercase()
+                )),
+            })?;
             data_folder.push(BIN);
             if !data_folder.exists() {
-                std::fs::create_dir_all(data_folder.as_path())
-                    .expect("Wasn't able to create the folder {data_folder}");
+                std::fs::create_dir_all(data_folder.as_path()).map_err(Error::io(&data_folder))?;
             }
-            data_folder
+            Ok(data_folder)
         }
     }
 }
 
-pub fn get_db_file() -> std::path::PathBuf {
-    let mut db_file = get_project_data_folder();
+pub fn get_db_file() -> Result<std::path::PathBuf, Error> {
+    let mut db_file = get_project_data_folder()?;
     db_file.push("todos.sqlite3");
-    db_file
+    Ok(db_file)
 }
 
-pub fn get_todoeditmsg_file() -> std::path::PathBuf {
-    let mut todo_file = get_project_data_folder();
+pub fn get_todoeditmsg_file() -> Result<std::path::PathBuf, Error> {
+    let mut todo_file = get_project_data_folder()?;
     // TODO: we should clean this up if it's left behind at startup
     todo_file.push("TODO_EDITMSG");
-    todo_file
+    Ok(todo_file)
 }
 
 pub fn get_editor() -> String {
@@ -92,21 +147,15 @@ pub fn get_editor() -> String {
 }
 
 // Database operations
-pub fn establish_connection() -> SqliteConnection {
-    let database_url = get_db_file().display().to_string();
-    let mut conn = SqliteConnection::establish(&database_url)
-        .unwrap_or_else(|_| panic!("Error connecting to {}", database_url));
-    //TODO: a match here could perform log a message for successful migrations
-    conn.run_pending_migrations(MIGRATIONS)
-        .expect("Migrations couldn't be run");
-    conn
+pub fn establish_connection() -> Result<SqliteConnection, Error> {
+    TodoStore::open().map(TodoStore::into_connection)
 }
 
 pub fn create_temp_todo_file_open_and_then_read_remove_process(
     fp: &std::path::Path,
     title: String,
     notes: String,
-) -> (String, String) {
+) -> Result<(String, String), Error> {
     let body = format!(
             "{}
 {}
@@ -122,26 +171,29 @@ pub fn create_temp_todo_file_open_and_then_read_remove_process(
     let mut full_notes = notes;
     let editor = get_editor();
     if editor != "-" {
-        let mut file = std::fs::File::create(fp)
-            .unwrap_or_else(|_| panic!("File {} couldn't be created", fp.display()));
-        file.write_all(body.as_bytes())
-            .unwrap_or_else(|_| panic!("the body couldn't be written to {}", fp.display()));
-        std::process::Command::new(get_editor())
+        let mut file = std::fs::File::create(fp).map_err(Error::io(fp))?;
+        file.write_all(body.as_bytes()).map_err(Error::io(fp))?;
+        std::process::Command::new(&editor)
             .arg(fp)
             .status()
-            .unwrap_or_else(|_| panic!("opening editor for {} failed", fp.display()));
+            .map_err(|e| {
+                Error::Editor(format!(
+                    "Opening {} with {} failed: {}",
+                    fp.display(),
+                    editor,
+                    e
+                ))
+            })?;
         let mut buf = String::new();
         std::fs::File::open(fp)
-            .unwrap_or_else(|_| panic!("opening {} for reading after editing failed", fp.display()))
-            .read_to_string(&mut buf)
-            .unwrap_or_else(|_| panic!("reading {} after editing failed", fp.display()));
-        std::fs::remove_file(fp)
-            .unwrap_or_else(|_| panic!("{} couldn't be removed once it was read", fp.display()));
+            .and_then(|mut file| file.read_to_string(&mut buf))
+            .map_err(Error::io(fp))?;
+        std::fs::remove_file(fp).map_err(Error::io(fp))?;
         // TODO: maybe rename notes to body?
         let mut not_comments = buf.lines().filter(|e| !e.trim_start().starts_with("#"));
         final_title = not_comments
             .next()
-            .expect("Couldn't find title of new TODO")
+            .ok_or_else(|| Error::Editor("Couldn't find the title of the TODO".to_string()))?
             .to_string();
         let notes: Vec<&str> = not_comments.collect();
         full_notes = notes.join("\n");
@@ -152,111 +204,207 @@ pub fn create_temp_todo_file_open_and_then_read_remove_process(
         // TODO: what if file had nothing in it? What if I removed title, maybe cancel?
     }
 
-    (final_title.to_string(), full_notes)
-}
-
-// High-level database operations
-pub fn add_todo(todo: &NewTodo) -> Todos {
-    let connection = &mut establish_connection();
-    diesel::insert_into(todos::table)
-        .values(todo)
-        .returning(Todos::as_returning())
-        .get_result(connection)
-        .expect("Error saving new TODO")
-}
-
-pub fn get_todo(get_id: &String) -> Todos {
-    use self::schema::todos::dsl::*;
-    let connection = &mut establish_connection();
-    let decoded_id = decode_id(get_id);
-    todos
-        .select(Todos::as_select())
-        .filter(id.eq(decoded_id))
-        .first(connection)
-        .unwrap_or_else(|_| panic!("Single TODO couldn't be found with id {}", get_id))
-}
-
-pub fn get_todos() -> Vec<Todos> {
-    use self::schema::todos::dsl::*;
-    let connection = &mut establish_connection();
-    todos
-        .select(Todos::as_select())
-        .load(connection)
-        .expect("Was unable to get all TODOs")
-}
-
-pub fn complete_todo(show_id: &String, ts: Option<DateTime<Utc>>) {
-    use self::schema::todos::dsl::*;
-    let connection = &mut establish_connection();
-    let decoded_id = decode_id(show_id);
-    let completion_ts = ts.unwrap_or_else(|| Utc::now());
-    diesel::update(todos.find(decoded_id))
-        .set(completed.eq(completion_ts))
-        .execute(connection)
-        .unwrap_or_else(|_| panic!("TODO: {} couldn't be completed", show_id));
-}
-
-pub fn set_due(show_id: &String, ts: Option<DateTime<Utc>>) {
-    use self::schema::todos::dsl::*;
-    let connection = &mut establish_connection();
-    let decoded_id = decode_id(show_id);
-    diesel::update(todos.find(decoded_id))
-        .set(due.eq(ts))
-        .execute(connection)
-        .unwrap_or_else(|_| {
-            panic!(
-                "TODO: {}'s due couldn't be set to {}",
-                show_id,
-                match ts {
-                    Some(due_ts) => due_ts.format("%c").to_string(),
-                    None => "none".to_string(),
-                }
-            )
-        });
-}
-
-pub fn set_todo_title(update_id: &String, new_title: &String) {
-    use self::schema::todos::dsl::*;
-    let connection = &mut establish_connection();
-    let decoded_id = decode_id(update_id);
-    diesel::update(todos.find(decoded_id))
-        .set(title.eq(new_title))
-        .execute(connection)
-        .unwrap_or_else(|_| panic!("title of TODO: {} couldn't be updated", update_id));
-}
-
-pub fn set_todo_notes(update_id: &String, new_notes: &String) {
-    use self::schema::todos::dsl::*;
-    let connection = &mut establish_connection();
-    let decoded_id = decode_id(update_id);
-    diesel::update(todos.find(decoded_id))
-        .set(notes.eq(new_notes))
-        .execute(connection)
-        .unwrap_or_else(|_| panic!("notes of TODO: {} couldn't be updated", update_id));
-}
-
-pub fn reopen_todo(show_id: &String) {
-    use self::schema::todos::dsl::*;
-    use chrono::DateTime;
-    let connection = &mut establish_connection();
-    let decoded_id = decode_id(show_id);
-    diesel::update(todos.find(decoded_id))
-        .set(completed.eq(None::<DateTime<Utc>>))
-        .execute(connection)
-        .expect("TODO couldn't be reopened");
-    println!(
-        "{} reopened, if this was a mistake complete with `{} complete {}`",
-        show_id.yellow(),
-        BIN,
-        show_id
-    )
-}
-
-pub fn delete_todo(delete_id: &String) {
-    use self::schema::todos::dsl::*;
-    let connection = &mut establish_connection();
-    let decoded_id = decode_id(delete_id);
-    diesel::delete(todos.filter(id.eq(decoded_id)))
-        .execute(connection)
-        .expect("Error loading posts");
+    Ok((final_title.to_string(), full_notes))
+}
+
+// High-level database operations, each one opens its own connection. Use a [`TodoStore`]
+// directly to run several of them on one connection or in a transaction.
+pub fn add_todo(todo: &NewTodo) -> Result<Todos, Error> {
+    TodoStore::open()?.add_todo(todo)
+}
+
+pub fn get_todo(get_id: &str) -> Result<Todos, Error> {
+    TodoStore::open()?.get_todo(get_id)
+}
+
+pub fn get_todos() -> Result<Vec<Todos>, Error> {
+    TodoStore::open()?.get_todos()
+}
+
+pub fn get_todos_with_tags(tag_names: &[String]) -> Result<Vec<Todos>, Error> {
+    TodoStore::open()?.get_todos_with_tags(tag_names)
+}
+
+pub fn get_todos_where(filter: Option<&filter::Filter>) -> Result<Vec<Todos>, Error> {
+    TodoStore::open()?.get_todos_where(filter)
+}
+
+pub fn search_todos(query: &str) -> Result<Vec<search::SearchMatch>, Error> {
+    TodoStore::open()?.search_todos(query)
+}
+
+pub fn count_tags_by_todo() -> Result<std::collections::HashMap<i32, i64>, Error> {
+    TodoStore::open()?.count_tags_by_todo()
+}
+
+pub fn get_tags_by_todo() -> Result<std::collections::HashMap<i32, Vec<String>>, Error> {
+    TodoStore::open()?.get_tags_by_todo()
+}
+
+pub fn get_todo_tags(show_id: &str) -> Result<Vec<String>, Error> {
+    TodoStore::open()?.get_todo_tags(show_id)
+}
+
+pub fn add_tags(update_id: &str, tag_names: &[String]) -> Result<(), Error> {
+    TodoStore::open()?.add_tags(update_id, tag_names)
+}
+
+pub fn remove_tags(update_id: &str, tag_names: &[String]) -> Result<(), Error> {
+    TodoStore::open()?.remove_tags(update_id, tag_names)
+}
+
+pub fn set_todo_project(update_id: &str, project_name: Option<&str>) -> Result<(), Error> {
+    TodoStore::open()?.set_todo_project(update_id, project_name)
+}
+
+pub fn complete_todo(show_id: &str, ts: Option<DateTime<Utc>>) -> Result<Option<Todos>, Error> {
+    TodoStore::open()?.complete_todo(show_id, ts)
+}
+
+pub fn set_parent(update_id: &str, parent: Option<&str>) -> Result<(), Error> {
+    TodoStore::open()?.set_parent(update_id, parent)
+}
+
+pub fn get_subtasks(show_id: &str) -> Result<Vec<Todos>, Error> {
+    TodoStore::open()?.get_subtasks(show_id)
+}
+
+pub fn count_subtasks_by_parent() -> Result<std::collections::HashMap<i32, (i64, i64)>, Error> {
+    TodoStore::open()?.count_subtasks_by_parent()
+}
+
+pub fn add_dependency(blocked_show_id: &str, blocker_show_id: &str) -> Result<(), Error> {
+    TodoStore::open()?.add_dependency(blocked_show_id, blocker_show_id)
+}
+
+pub fn remove_dependency(blocked_show_id: &str, blocker_show_id: &str) -> Result<(), Error> {
+    TodoStore::open()?.remove_dependency(blocked_show_id, blocker_show_id)
+}
+
+pub fn get_blockers(show_id: &str) -> Result<Vec<Todos>, Error> {
+    TodoStore::open()?.get_blockers(show_id)
+}
+
+pub fn get_dependents(show_id: &str) -> Result<Vec<Todos>, Error> {
+    TodoStore::open()?.get_dependents(show_id)
+}
+
+pub fn get_blocked_todo_ids() -> Result<std::collections::HashSet<i32>, Error> {
+    TodoStore::open()?.get_blocked_todo_ids()
+}
+
+pub fn set_recurrence(
+    update_id: &str,
+    rule: Option<&crate::recurrence::Recurrence>,
+) -> Result<(), Error> {
+    TodoStore::open()?.set_recurrence(update_id, rule)
+}
+
+pub fn set_due(show_id: &str, ts: Option<DateTime<Utc>>) -> Result<(), Error> {
+    TodoStore::open()?.set_due(show_id, ts)
+}
+
+pub fn set_priority(update_id: &str, new_priority: Priority) -> Result<(), Error> {
+    TodoStore::open()?.set_priority(update_id, new_priority)
+}
+
+pub fn set_estimate(update_id: &str, new_estimate: Option<TimeDelta>) -> Result<(), Error> {
+    TodoStore::open()?.set_estimate(update_id, new_estimate)
+}
+
+pub fn set_todo_title(update_id: &str, new_title: &str) -> Result<(), Error> {
+    TodoStore::open()?.set_todo_title(update_id, new_title)
+}
+
+pub fn set_todo_notes(update_id: &str, new_notes: &str) -> Result<(), Error> {
+    TodoStore::open()?.set_todo_notes(update_id, new_notes)
+}
+
+pub fn reopen_todo(show_id: &str) -> Result<(), Error> {
+    TodoStore::open()?.reopen_todo(show_id)
+}
+
+pub fn delete_todo(delete_id: &str) -> Result<(), Error> {
+    TodoStore::open()?.delete_todo(delete_id)
+}
+
+pub fn add_project(name: &str) -> Result<Project, Error> {
+    TodoStore::open()?.add_project(name)
+}
+
+pub fn get_project(name: &str) -> Result<Project, Error> {
+    TodoStore::open()?.get_project(name)
+}
+
+pub fn get_active_project(name: &str) -> Result<Project, Error> {
+    TodoStore::open()?.get_active_project(name)
+}
+
+pub fn get_projects() -> Result<Vec<Project>, Error> {
+    TodoStore::open()?.get_projects()
+}
+
+pub fn count_open_todos_by_project() -> Result<std::collections::HashMap<Option<i32>, i64>, Error> {
+    TodoStore::open()?.count_open_todos_by_project()
+}
+
+pub fn rename_project(old_name: &str, new_name: &str) -> Result<(), Error> {
+    TodoStore::open()?.rename_project(old_name, new_name)
+}
+
+pub fn archive_project(name: &str) -> Result<(), Error> {
+    TodoStore::open()?.archive_project(name)
+}
+
+pub fn save_view(view: &NewView) -> Result<View, Error> {
+    TodoStore::open()?.save_view(view)
+}
+
+pub fn get_view(name: &str) -> Result<Option<View>, Error> {
+    TodoStore::open()?.get_view(name)
+}
+
+pub fn get_views() -> Result<Vec<View>, Error> {
+    TodoStore::open()?.get_views()
+}
+
+pub fn delete_view(name: &str) -> Result<bool, Error> {
+    TodoStore::open()?.delete_view(name)
+}
+
+pub fn get_active_session() -> Result<Option<Session>, Error> {
+    TodoStore::open()?.get_active_session()
+}
+
+pub fn start_session(show_id: &str) -> Result<(Session, Option<Session>), Error> {
+    TodoStore::open()?.start_session(show_id)
+}
+
+pub fn stop_session() -> Result<Option<Session>, Error> {
+    TodoStore::open()?.stop_session()
+}
+
+pub fn get_sessions(show_id: &str) -> Result<Vec<Session>, Error> {
+    TodoStore::open()?.get_sessions(show_id)
+}
+
+pub fn get_sessions_between(
+    since: DateTime<Utc>,
+    until: DateTime<Utc>,
+) -> Result<Vec<Session>, Error> {
+    TodoStore::open()?.get_sessions_between(since, until)
+}
+
+pub fn tracked_time_by_todo(
+    now: DateTime<Utc>,
+) -> Result<std::collections::HashMap<i32, TimeDelta>, Error> {
+    TodoStore::open()?.tracked_time_by_todo(now)
+}
+
+pub fn undo(n: usize) -> Result<Vec<models::Operation>, Error> {
+    TodoStore::open()?.undo(n)
+}
+
+pub fn redo(n: usize) -> Result<Vec<models::Operation>, Error> {
+    TodoStore::open()?.redo(n)
 }
@@ -1,7 +1,21 @@
 use chrono::{DateTime, Utc};
 use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
 
-#[derive(Queryable, Selectable, Identifiable, AsChangeset)]
+#[derive(
+    Clone,
+    Debug,
+    PartialEq,
+    Queryable,
+    Selectable,
+    Identifiable,
+    Insertable,
+    AsChangeset,
+    Serialize,
+    Deserialize,
+)]
 #[diesel(table_name = crate::schema::todos)]
 #[diesel(check_for_backend(diesel::sqlite::Sqlite))]
 #[diesel(treat_none_as_null = true)]
@@ -9,10 +23,25 @@ pub struct Todos {
     pub id: i32,
     pub title: String,
     pub notes: String,
-    // TODO: time tracking
     pub created: DateTime<Utc>,
     pub completed: Option<DateTime<Utc>>,
     pub due: Option<DateTime<Utc>>,
+    pub project_id: Option<i32>,
+    pub priority: i32,
+    /// RRULE style recurrence, see [`crate::recurrence::Recurrence`]
+    pub recurrence: Option<String>,
+    /// The TODO that recurring instances were spawned from
+    pub template_id: Option<i32>,
+    pub parent_id: Option<i32>,
+    /// How long the TODO is expected to take, in minutes
+    pub estimate: Option<i32>,
+}
+
+impl Todos {
+    pub fn estimate_duration(&self) -> Option<chrono::TimeDelta> {
+        self.estimate
+            .map(|minutes| chrono::TimeDelta::minutes(minutes.into()))
+    }
 }
 
 #[derive(Insertable)]
@@ -22,3 +51,199 @@ pub struct NewTodo<'a> {
     pub notes: &'a str,
     pub created: DateTime<Utc>,
 }
+
+/// How important a TODO is, stored as its level in the `priority` column
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    Unset = 0,
+    Low = 1,
+    Medium = 2,
+    High = 3,
+}
+
+impl Priority {
+    pub fn from_level(level: i32) -> Priority {
+        match level {
+            i32::MIN..=0 => Priority::Unset,
+            1 => Priority::Low,
+            2 => Priority::Medium,
+            _ => Priority::High,
+        }
+    }
+
+    pub fn level(self) -> i32 {
+        self as i32
+    }
+}
+
+impl FromStr for Priority {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "none" | "n" | "0" => Ok(Priority::Unset),
+            "low" | "l" | "1" => Ok(Priority::Low),
+            "medium" | "m" | "2" => Ok(Priority::Medium),
+            "high" | "h" | "3" => Ok(Priority::High),
+            _ => Err(format!(
+                "unknown priority {}, expected one of: none, low, medium, high",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Priority {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let label = match self {
+            Priority::Unset => "none",
+            Priority::Low => "low",
+            Priority::Medium => "medium",
+            Priority::High => "high",
+        };
+        write!(f, "{}", label)
+    }
+}
+
+#[derive(Queryable, Selectable, Identifiable)]
+#[diesel(table_name = crate::schema::tags)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct Tag {
+    pub id: i32,
+    pub name: String,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::tags)]
+pub struct NewTag<'a> {
+    pub name: &'a str,
+}
+
+#[derive(Queryable, Selectable, Insertable)]
+#[diesel(table_name = crate::schema::todo_tags)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct TodoTag {
+    pub todo_id: i32,
+    pub tag_id: i32,
+}
+
+/// A stretch of time spent working on a TODO, `stopped` is `None` while it's still going on
+#[derive(
+    Clone, Debug, PartialEq, Queryable, Selectable, Identifiable, Insertable, Serialize, Deserialize,
+)]
+#[diesel(table_name = crate::schema::sessions)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct Session {
+    pub id: i32,
+    pub todo_id: i32,
+    pub started: DateTime<Utc>,
+    pub stopped: Option<DateTime<Utc>>,
+}
+
+impl Session {
+    /// How long the session lasted, or has lasted until `now` if it's still active
+    pub fn duration(&self, now: DateTime<Utc>) -> chrono::TimeDelta {
+        self.stopped.unwrap_or(now) - self.started
+    }
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::sessions)]
+pub struct NewSession {
+    pub todo_id: i32,
+    pub started: DateTime<Utc>,
+}
+
+/// `todo_id` can't be worked on until `blocker_id` is completed
+#[derive(Queryable, Selectable, Insertable)]
+#[diesel(table_name = crate::schema::todo_dependencies)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct TodoDependency {
+    pub todo_id: i32,
+    pub blocker_id: i32,
+}
+
+#[derive(
+    Clone, Debug, PartialEq, Queryable, Selectable, Identifiable, Insertable, Serialize, Deserialize,
+)]
+#[diesel(table_name = crate::schema::projects)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct Project {
+    pub id: i32,
+    pub name: String,
+    pub created: DateTime<Utc>,
+    pub archived: Option<DateTime<Utc>>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::projects)]
+pub struct NewProject<'a> {
+    pub name: &'a str,
+    pub created: DateTime<Utc>,
+}
+
+/// Options of `list` saved under a name, see `workingon view save`
+#[derive(
+    Clone, Debug, PartialEq, Queryable, Selectable, Identifiable, Insertable, Serialize, Deserialize,
+)]
+#[diesel(table_name = crate::schema::views)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct View {
+    pub id: i32,
+    pub name: String,
+    /// open, completed or all
+    pub status: String,
+    /// JSON array of tag names, see [`View::tag_names`]
+    pub tags: String,
+    /// A `list --where` filter as it was typed
+    pub filter: Option<String>,
+    pub sort: Option<String>,
+    pub show_time: bool,
+    /// Comma separated `list --columns`
+    pub list_columns: Option<String>,
+    pub wide: bool,
+}
+
+impl View {
+    pub fn tag_names(&self) -> Result<Vec<String>, crate::Error> {
+        serde_json::from_str(&self.tags).map_err(|e| crate::Error::Db {
+            context: format!("The tags of view {} aren't a JSON array", self.name),
+            source: Box::new(e),
+        })
+    }
+}
+
+#[derive(Insertable, AsChangeset)]
+#[diesel(table_name = crate::schema::views)]
+#[diesel(treat_none_as_null = true)]
+pub struct NewView<'a> {
+    pub name: &'a str,
+    pub status: &'a str,
+    pub tags: String,
+    pub filter: Option<&'a str>,
+    pub sort: Option<&'a str>,
+    pub show_time: bool,
+    pub list_columns: Option<&'a str>,
+    pub wide: bool,
+}
+
+/// A change done through the library, kept so that it can be undone, see `workingon undo`
+#[derive(Queryable, Selectable, Identifiable)]
+#[diesel(table_name = crate::schema::operations)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct Operation {
+    pub id: i32,
+    pub created: DateTime<Utc>,
+    pub description: String,
+    /// JSON array of [`crate::journal::Change`]
+    pub changes: String,
+    pub undone: bool,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::operations)]
+pub struct NewOperation<'a> {
+    pub created: DateTime<Utc>,
+    pub description: &'a str,
+    pub changes: String,
+}
@@ -0,0 +1,298 @@
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+use crate::models::{Operation, Priority, Project, Session, Todos, View};
+use crate::urgency::Urgency;
+use crate::{Error, TodoStore};
+
+/// Version of the JSON printed with `--format json|jsonl`. Fields may be added within a version,
+/// renaming or removing one, or changing what it means, bumps it.
+pub const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// human readable tables and messages
+    Table,
+    /// a single JSON object
+    Json,
+    /// one JSON object per line
+    Jsonl,
+}
+
+impl OutputFormat {
+    pub fn is_json(self) -> bool {
+        matches!(self, OutputFormat::Json | OutputFormat::Jsonl)
+    }
+}
+
+#[derive(Serialize)]
+pub struct TodoJson {
+    pub id: String,
+    pub numeric_id: i32,
+    pub title: String,
+    pub notes: String,
+    pub created: DateTime<Utc>,
+    pub completed: Option<DateTime<Utc>>,
+    pub due: Option<DateTime<Utc>>,
+    pub priority: String,
+    pub project: Option<String>,
+    pub tags: Vec<String>,
+    /// RRULE style, like "FREQ=WEEKLY;INTERVAL=1"
+    pub recurrence: Option<String>,
+    pub estimate_minutes: Option<i32>,
+    pub tracked_seconds: i64,
+    pub parent: Option<String>,
+    pub occurrence_of: Option<String>,
+    pub blocked: bool,
+}
+
+/// Everything besides the TODO itself that goes into [`TodoJson`], loaded once so that listing
+/// many TODOs doesn't query the database for each of them
+pub struct TodoJsonContext {
+    tags_by_todo: HashMap<i32, Vec<String>>,
+    project_names: HashMap<i32, String>,
+    tracked_times: HashMap<i32, chrono::TimeDelta>,
+    blocked_ids: HashSet<i32>,
+}
+
+impl TodoJsonContext {
+    pub fn load() -> Result<Self, Error> {
+        Self::load_from(&mut TodoStore::open()?)
+    }
+
+    pub fn load_from(store: &mut TodoStore) -> Result<Self, Error> {
+        Ok(TodoJsonContext {
+            tags_by_todo: store.get_tags_by_todo()?,
+            project_names: store
+                .get_projects()?
+                .into_iter()
+                .map(|project| (project.id, project.name))
+                .collect(),
+            tracked_times: store.tracked_time_by_todo(Utc::now())?,
+            blocked_ids: store.get_blocked_todo_ids()?,
+        })
+    }
+
+    pub fn todo_json(&self, todo: &Todos) -> TodoJson {
+        TodoJson {
+            id: encode(todo.id),
+            numeric_id: todo.id,
+            title: todo.title.clone(),
+            notes: todo.notes.clone(),
+            created: todo.created,
+            completed: todo.completed,
+            due: todo.due,
+            priority: Priority::from_level(todo.priority).to_string(),
+            project: todo
+                .project_id
+                .and_then(|project_id| self.project_names.get(&project_id).cloned()),
+            tags: self.tags_by_todo.get(&todo.id).cloned().unwrap_or_default(),
+            recurrence: todo.recurrence.clone(),
+            estimate_minutes: todo.estimate,
+            tracked_seconds: self
+                .tracked_times
+                .get(&todo.id)
+                .map_or(0, |tracked| tracked.num_seconds()),
+            parent: todo.parent_id.map(encode),
+            occurrence_of: todo.template_id.map(encode),
+            blocked: self.blocked_ids.contains(&todo.id),
+        }
+    }
+}
+
+/// A single TODO, for commands that show or change just one
+pub fn todo_json(show_id: &str) -> Result<TodoJson, Error> {
+    todo_json_from(&mut TodoStore::open()?, show_id)
+}
+
+pub fn todo_json_from(store: &mut TodoStore, show_id: &str) -> Result<TodoJson, Error> {
+    Ok(TodoJsonContext::load_from(store)?.todo_json(&store.get_todo(show_id)?))
+}
+
+#[derive(Serialize)]
+pub struct UrgencyFactorJson {
+    pub reason: String,
+    pub score: f64,
+}
+
+#[derive(Serialize)]
+pub struct NextTodoJson {
+    #[serde(flatten)]
+    pub todo: TodoJson,
+    pub urgency: f64,
+    pub urgency_factors: Vec<UrgencyFactorJson>,
+}
+
+impl NextTodoJson {
+    pub fn new(todo: TodoJson, urgency: Urgency) -> Self {
+        NextTodoJson {
+            todo,
+            urgency: urgency.total(),
+            urgency_factors: urgency
+                .factors
+                .into_iter()
+                .map(|factor| UrgencyFactorJson {
+                    reason: factor.reason,
+                    score: factor.score,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct SearchMatchJson {
+    #[serde(flatten)]
+    pub todo: TodoJson,
+    /// The part of the notes that matched, if they did
+    pub snippet: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct CompletedTodoJson {
+    #[serde(flatten)]
+    pub todo: TodoJson,
+    pub next_occurrence: Option<TodoJson>,
+    /// TODOs that were waiting on this one and can be worked on now
+    pub unblocked: Vec<TodoJson>,
+}
+
+#[derive(Serialize)]
+pub struct DeletedTodoJson {
+    pub id: String,
+    pub numeric_id: i32,
+    pub deleted: bool,
+}
+
+#[derive(Serialize)]
+pub struct SessionJson {
+    pub numeric_id: i32,
+    pub todo_id: String,
+    pub todo_numeric_id: i32,
+    pub started: DateTime<Utc>,
+    pub stopped: Option<DateTime<Utc>>,
+    pub duration_seconds: i64,
+}
+
+#[derive(Serialize)]
+pub struct OperationJson {
+    pub numeric_id: i32,
+    pub description: String,
+    pub created: DateTime<Utc>,
+    pub undone: bool,
+}
+
+impl OperationJson {
+    pub fn new(operation: &Operation) -> Self {
+        OperationJson {
+            numeric_id: operation.id,
+            description: operation.description.clone(),
+            created: operation.created,
+            undone: operation.undone,
+        }
+    }
+}
+
+impl SessionJson {
+    pub fn new(session: &Session) -> Self {
+        SessionJson {
+            numeric_id: session.id,
+            todo_id: encode(session.todo_id),
+            todo_numeric_id: session.todo_id,
+            started: session.started,
+            stopped: session.stopped,
+            duration_seconds: session.duration(Utc::now()).num_seconds(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct SessionsJson {
+    /// The session being tracked, or that was just stopped
+    pub session: Option<SessionJson>,
+    /// The session that was stopped to start a new one
+    pub stopped: Option<SessionJson>,
+}
+
+#[derive(Serialize)]
+pub struct ProjectJson {
+    pub numeric_id: i32,
+    pub name: String,
+    pub created: DateTime<Utc>,
+    pub archived: Option<DateTime<Utc>>,
+    pub open_todos: i64,
+}
+
+impl ProjectJson {
+    pub fn new(project: Project, open_todos: i64) -> Self {
+        ProjectJson {
+            numeric_id: project.id,
+            name: project.name,
+            created: project.created,
+            archived: project.archived,
+            open_todos,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct ViewJson {
+    pub name: String,
+    /// open, completed or all
+    pub status: String,
+    pub tags: Vec<String>,
+    #[serde(rename = "where")]
+    pub filter: Option<String>,
+    pub sort: Option<String>,
+    pub time: bool,
+    pub columns: Option<Vec<String>>,
+    pub wide: bool,
+}
+
+impl ViewJson {
+    pub fn new(view: View) -> Result<Self, Error> {
+        Ok(ViewJson {
+            tags: view.tag_names()?,
+            name: view.name,
+            status: view.status,
+            filter: view.filter,
+            sort: view.sort,
+            time: view.show_time,
+            columns: view
+                .list_columns
+                .map(|columns| columns.split(',').map(str::to_string).collect()),
+            wide: view.wide,
+        })
+    }
+}
+
+fn encode(numeric_id: i32) -> String {
+    crate::encode_id(numeric_id.try_into().unwrap())
+}
+
+fn with_schema_version<T: Serialize>(value: &T) -> serde_json::Value {
+    let mut value = serde_json::to_value(value).expect("Couldn't serialize output");
+    if let serde_json::Value::Object(fields) = &mut value {
+        fields.insert("schema_version".to_string(), SCHEMA_VERSION.into());
+    }
+    value
+}
+
+/// Prints one object, along with the schema version
+pub fn print_one<T: Serialize>(value: &T) {
+    println!("{}", with_schema_version(value));
+}
+
+/// Prints `{"schema_version": .., key: [..]}` as JSON, or each item on its own line along with the
+/// schema version as JSONL
+pub fn print_many<T: Serialize>(format: OutputFormat, key: &str, items: &[T]) {
+    if format == OutputFormat::Jsonl {
+        for item in items {
+            print_one(item);
+        }
+    } else {
+        print_one(&serde_json::json!({ key: items }));
+    }
+}
@@ -0,0 +1,183 @@
+use chrono::{DateTime, Days, Local, Months, Utc};
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+impl Frequency {
+    fn rrule_name(self) -> &'static str {
+        match self {
+            Frequency::Daily => "DAILY",
+            Frequency::Weekly => "WEEKLY",
+            Frequency::Monthly => "MONTHLY",
+            Frequency::Yearly => "YEARLY",
+        }
+    }
+
+    fn unit(self) -> &'static str {
+        match self {
+            Frequency::Daily => "day",
+            Frequency::Weekly => "week",
+            Frequency::Monthly => "month",
+            Frequency::Yearly => "year",
+        }
+    }
+
+    fn from_unit(unit: &str) -> Option<Frequency> {
+        match unit.trim_end_matches('s') {
+            "day" => Some(Frequency::Daily),
+            "week" => Some(Frequency::Weekly),
+            "month" => Some(Frequency::Monthly),
+            "year" => Some(Frequency::Yearly),
+            _ => None,
+        }
+    }
+}
+
+/// How often a TODO repeats. Parsed from plain English like "week", "every 3 days" or "monthly",
+/// or from the `FREQ` and `INTERVAL` parts of an RFC 5545 RRULE, which is also how it's stored.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Recurrence {
+    pub frequency: Frequency,
+    pub interval: u32,
+}
+
+impl Recurrence {
+    pub fn every(interval: u32, frequency: Frequency) -> Self {
+        Recurrence {
+            frequency,
+            interval,
+        }
+    }
+
+    /// The next due time after `ts`. Calendar math is done in local time, so that a TODO due at
+    /// 9am stays due at 9am across daylight saving changes. Fails when it's past the dates chrono
+    /// can represent.
+    pub fn next_after(&self, ts: DateTime<Utc>) -> Result<DateTime<Utc>, String> {
+        let local_ts = ts.with_timezone(&Local);
+        let next = match self.frequency {
+            Frequency::Daily => local_ts.checked_add_days(Days::new(self.interval.into())),
+            Frequency::Weekly => local_ts.checked_add_days(Days::new(7 * u64::from(self.interval))),
+            Frequency::Monthly => local_ts.checked_add_months(Months::new(self.interval)),
+            Frequency::Yearly => self
+                .interval
+                .checked_mul(12)
+                .and_then(|months| local_ts.checked_add_months(Months::new(months))),
+        };
+        next.map(|next| next.to_utc()).ok_or_else(|| {
+            format!(
+                "the occurrence {} after {} is out of range",
+                self.describe(),
+                ts.to_rfc3339()
+            )
+        })
+    }
+
+    /// Human readable description, like "every week" or "every 3 days"
+    pub fn describe(&self) -> String {
+        if self.interval == 1 {
+            format!("every {}", self.frequency.unit())
+        } else {
+            format!("every {} {}s", self.interval, self.frequency.unit())
+        }
+    }
+
+    fn parse_rrule(s: &str) -> Result<Self, String> {
+        let mut frequency = None;
+        let mut interval = 1;
+        for part in s.split(';').filter(|part| !part.is_empty()) {
+            let (key, value) = part
+                .split_once('=')
+                .ok_or_else(|| format!("malformed RRULE part {}", part))?;
+            match key.to_uppercase().as_str() {
+                "FREQ" => {
+                    frequency = Some(match value.to_uppercase().as_str() {
+                        "DAILY" => Frequency::Daily,
+                        "WEEKLY" => Frequency::Weekly,
+                        "MONTHLY" => Frequency::Monthly,
+                        "YEARLY" => Frequency::Yearly,
+                        _ => return Err(format!("unsupported RRULE frequency {}", value)),
+                    })
+                }
+                "INTERVAL" => {
+                    interval = value
+                        .parse()
+                        .map_err(|_| format!("invalid RRULE interval {}", value))?
+                }
+                _ => return Err(format!("unsupported RRULE part {}", key)),
+            }
+        }
+        Ok(Recurrence {
+            frequency: frequency.ok_or("RRULE is missing FREQ")?,
+            interval,
+        })
+    }
+}
+
+impl FromStr for Recurrence {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let upper = s.to_uppercase();
+        let recurrence = if upper.starts_with("FREQ=") || upper.starts_with("RRULE:") {
+            Recurrence::parse_rrule(s.split_once(':').map_or(s, |(_, rule)| rule))?
+        } else {
+            let lower = s.to_lowercase();
+            let words: Vec<&str> = lower
+                .strip_prefix("every ")
+                .unwrap_or(&lower)
+                .split_whitespace()
+                .collect();
+            match words.as_slice() {
+                ["daily"] => Recurrence::every(1, Frequency::Daily),
+                ["weekly"] => Recurrence::every(1, Frequency::Weekly),
+                ["monthly"] => Recurrence::every(1, Frequency::Monthly),
+                ["yearly"] | ["annually"] => Recurrence::every(1, Frequency::Yearly),
+                [unit] => {
+                    Recurrence::every(1, Frequency::from_unit(unit).ok_or_else(|| unknown(s))?)
+                }
+                [count, unit] => Recurrence::every(
+                    count.parse().map_err(|_| unknown(s))?,
+                    Frequency::from_unit(unit).ok_or_else(|| unknown(s))?,
+                ),
+                _ => return Err(unknown(s)),
+            }
+        };
+        if recurrence.interval == 0 {
+            return Err("recurrence interval has to be at least 1".to_string());
+        }
+        if recurrence.next_after(Utc::now()).is_err() {
+            return Err(format!(
+                "recurrence interval {} is too large",
+                recurrence.interval
+            ));
+        }
+        Ok(recurrence)
+    }
+}
+
+fn unknown(s: &str) -> String {
+    format!(
+        "unknown recurrence {}, try something like: daily, week, every 3 days, FREQ=MONTHLY;INTERVAL=2",
+        s
+    )
+}
+
+impl fmt::Display for Recurrence {
+    /// Formats as an RRULE, which is how recurrences are stored
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "FREQ={};INTERVAL={}",
+            self.frequency.rrule_name(),
+            self.interval
+        )
+    }
+}
@@ -0,0 +1,164 @@
+use chrono::{DateTime, Datelike, Days, Local, NaiveDate, NaiveTime, TimeDelta, Utc, Weekday};
+use std::collections::BTreeMap;
+
+use crate::models::Session;
+use crate::Error;
+
+/// Parses a human readable day like "today", "yesterday" or "2026-10-12" relative to `today`.
+/// A bare weekday like "monday" means the most recent one, so that `--since monday` covers this
+/// week instead of jumping to next Monday.
+pub fn parse_day(s: &str, today: NaiveDate) -> Result<NaiveDate, Error> {
+    let base = day_start(today).with_timezone(&Local);
+    let parsed = chrono_english::parse_date_string(s, base, chrono_english::Dialect::Us)
+        .map_err(|_| Error::BadDate(s.to_string()))?
+        .date_naive();
+    if s.trim().parse::<Weekday>().is_ok() && parsed > today {
+        return Ok(parsed - Days::new(7));
+    }
+    Ok(parsed)
+}
+
+/// Parses a duration like "90m", "2h", "1h30m" or "1.5 hours". A bare number is in minutes.
+pub fn parse_duration(s: &str) -> Result<TimeDelta, String> {
+    let mut rest = s.trim();
+    let mut total = TimeDelta::zero();
+    if let Ok(minutes) = rest.parse::<f64>() {
+        total = to_duration(minutes, 60.0, s)?;
+        rest = "";
+    }
+    while !rest.is_empty() {
+        let number_len = rest
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(rest.len());
+        let number: f64 = rest[..number_len]
+            .parse()
+            .map_err(|_| format!("unknown duration \"{}\"", s))?;
+        rest = rest[number_len..].trim_start();
+        let unit_len = rest
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(rest.len());
+        let seconds_per_unit = match &rest[..unit_len] {
+            "h" | "hr" | "hrs" | "hour" | "hours" => 60.0 * 60.0,
+            "m" | "min" | "mins" | "minute" | "minutes" => 60.0,
+            _ => return Err(format!("unknown duration \"{}\", use hours or minutes", s)),
+        };
+        total += to_duration(number, seconds_per_unit, s)?;
+        rest = rest[unit_len..].trim_start();
+    }
+    if total <= TimeDelta::zero() {
+        return Err(format!("duration \"{}\" has to be longer than 0", s));
+    }
+    Ok(total)
+}
+
+fn to_duration(number: f64, seconds_per_unit: f64, s: &str) -> Result<TimeDelta, String> {
+    TimeDelta::try_seconds((number * seconds_per_unit).round() as i64)
+        .ok_or(format!("duration \"{}\" is too long", s))
+}
+
+/// How far the time tracked on a completed TODO was from its estimate
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EstimateAccuracy {
+    pub todo_id: i32,
+    pub estimate: TimeDelta,
+    pub actual: TimeDelta,
+}
+
+impl EstimateAccuracy {
+    pub fn error(&self) -> f64 {
+        estimate_error(self.actual, self.estimate)
+    }
+}
+
+/// How much longer (positive) or shorter (negative) something took, relative to its estimate
+pub fn estimate_error(actual: TimeDelta, estimate: TimeDelta) -> f64 {
+    (actual - estimate).num_seconds() as f64 / estimate.num_seconds() as f64
+}
+
+/// Average of how far off estimates were, no matter in which direction
+pub fn mean_absolute_error(accuracies: &[EstimateAccuracy]) -> Option<f64> {
+    if accuracies.is_empty() {
+        return None;
+    }
+    Some(
+        accuracies
+            .iter()
+            .fold(0.0, |total, accuracy| total + accuracy.error().abs())
+            / accuracies.len() as f64,
+    )
+}
+
+/// The moment `day` starts in local time. Where a daylight saving gap covers midnight the day
+/// starts once the gap is over.
+pub fn day_start(day: NaiveDate) -> DateTime<Utc> {
+    let midnight = day.and_time(NaiveTime::MIN);
+    midnight
+        .and_local_timezone(Local)
+        .earliest()
+        .or_else(|| {
+            (midnight + TimeDelta::hours(1))
+                .and_local_timezone(Local)
+                .earliest()
+        })
+        .map_or_else(|| midnight.and_utc(), |start| start.to_utc())
+}
+
+/// The Monday of the week `day` is in
+pub fn week_start(day: NaiveDate) -> NaiveDate {
+    day - Days::new(day.weekday().num_days_from_monday().into())
+}
+
+/// Time worked on a TODO within a single local day
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TimeEntry {
+    pub todo_id: i32,
+    pub day: NaiveDate,
+    pub duration: TimeDelta,
+}
+
+/// Clips sessions to `since..until` and splits them at local midnights, an active session counts
+/// up until `now`
+pub fn time_entries(
+    sessions: &[Session],
+    since: DateTime<Utc>,
+    until: DateTime<Utc>,
+    now: DateTime<Utc>,
+) -> Vec<TimeEntry> {
+    let mut entries = Vec::new();
+    for session in sessions {
+        let mut start = session.started.max(since);
+        let end = session.stopped.unwrap_or(now).min(until);
+        while start < end {
+            let day = start.with_timezone(&Local).date_naive();
+            let next_day = day_start(day + Days::new(1)).min(end);
+            entries.push(TimeEntry {
+                todo_id: session.todo_id,
+                day,
+                duration: next_day - start,
+            });
+            start = next_day;
+        }
+    }
+    entries
+}
+
+/// Sums entries under the keys returned by `keys`. An entry with more than one key is counted
+/// under each of them, so the groups can add up to more than the total.
+pub fn group_entries<K: Ord, F: Fn(&TimeEntry) -> Vec<K>>(
+    entries: &[TimeEntry],
+    keys: F,
+) -> Vec<(K, TimeDelta)> {
+    let mut groups: BTreeMap<K, TimeDelta> = BTreeMap::new();
+    for entry in entries {
+        for key in keys(entry) {
+            *groups.entry(key).or_insert(TimeDelta::zero()) += entry.duration;
+        }
+    }
+    groups.into_iter().collect()
+}
+
+pub fn total(entries: &[TimeEntry]) -> TimeDelta {
+    entries
+        .iter()
+        .fold(TimeDelta::zero(), |total, entry| total + entry.duration)
+}
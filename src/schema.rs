@@ -1,3 +1,52 @@
+diesel::table! {
+    operations (id) {
+        id -> Integer,
+        created -> diesel::sql_types::TimestamptzSqlite,
+        description -> Text,
+        changes -> Text,
+        undone -> Bool,
+    }
+}
+
+diesel::table! {
+    projects (id) {
+        id -> Integer,
+        name -> Text,
+        created -> diesel::sql_types::TimestamptzSqlite,
+        archived -> diesel::sql_types::Nullable<diesel::sql_types::TimestamptzSqlite>,
+    }
+}
+
+diesel::table! {
+    sessions (id) {
+        id -> Integer,
+        todo_id -> Integer,
+        started -> diesel::sql_types::TimestamptzSqlite,
+        stopped -> diesel::sql_types::Nullable<diesel::sql_types::TimestamptzSqlite>,
+    }
+}
+
+diesel::table! {
+    tags (id) {
+        id -> Integer,
+        name -> Text,
+    }
+}
+
+diesel::table! {
+    todo_dependencies (todo_id, blocker_id) {
+        todo_id -> Integer,
+        blocker_id -> Integer,
+    }
+}
+
+diesel::table! {
+    todo_tags (todo_id, tag_id) {
+        todo_id -> Integer,
+        tag_id -> Integer,
+    }
+}
+
 diesel::table! {
     todos (id) {
         id -> Integer,
@@ -6,5 +55,40 @@ diesel::table! {
         created -> diesel::sql_types::TimestamptzSqlite,
         completed -> diesel::sql_types::Nullable<diesel::sql_types::TimestamptzSqlite>,
         due -> diesel::sql_types::Nullable<diesel::sql_types::TimestamptzSqlite>,
+        project_id -> Nullable<Integer>,
+        priority -> Integer,
+        recurrence -> Nullable<Text>,
+        template_id -> Nullable<Integer>,
+        parent_id -> Nullable<Integer>,
+        estimate -> Nullable<Integer>,
+    }
+}
+
+diesel::table! {
+    views (id) {
+        id -> Integer,
+        name -> Text,
+        status -> Text,
+        tags -> Text,
+        filter -> Nullable<Text>,
+        sort -> Nullable<Text>,
+        show_time -> Bool,
+        list_columns -> Nullable<Text>,
+        wide -> Bool,
     }
 }
+
+diesel::joinable!(sessions -> todos (todo_id));
+diesel::joinable!(todo_tags -> tags (tag_id));
+diesel::joinable!(todo_tags -> todos (todo_id));
+diesel::joinable!(todos -> projects (project_id));
+
+diesel::allow_tables_to_appear_in_same_query!(
+    operations,
+    projects,
+    tags,
+    todo_dependencies,
+    todo_tags,
+    todos,
+    views,
+);
@@ -0,0 +1,64 @@
+use crate::models::Todos;
+
+/// Wrap matched words in highlighted titles and snippets, so that callers can style them
+pub const HIGHLIGHT_START: &str = "\u{2}";
+pub const HIGHLIGHT_END: &str = "\u{3}";
+
+/// A TODO that matched a search, best matches come first
+pub struct SearchMatch {
+    pub todo: Todos,
+    /// The title with matched words highlighted
+    pub title: String,
+    /// The part of the notes around the matched words, highlighted, empty if only the title
+    /// matched
+    pub snippet: String,
+}
+
+/// Turns what was typed into an FTS5 query. Words are quoted so that punctuation can't break the
+/// query syntax, "quoted phrases" are kept together and a trailing * matches words by prefix, like
+/// `"release notes" deploy*`. Every word or phrase has to match. Returns `None` when there's
+/// nothing to search for.
+pub fn match_query(query: &str) -> Option<String> {
+    let mut terms = Vec::new();
+    let mut chars = query.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c.is_whitespace() {
+            continue;
+        }
+        let mut term = String::new();
+        if c == '"' {
+            for c in chars.by_ref() {
+                if c == '"' {
+                    break;
+                }
+                term.push(c);
+            }
+        } else {
+            term.push(c);
+            while let Some(c) = chars.next_if(|c| !c.is_whitespace() && *c != '"') {
+                term.push(c);
+            }
+        }
+        let prefix = term.ends_with('*') || chars.next_if_eq(&'*').is_some();
+        let term = term.trim_end_matches('*').trim();
+        // Only punctuation would be an empty phrase, which FTS5 rejects
+        if !term.chars().any(char::is_alphanumeric) {
+            continue;
+        }
+        terms.push(format!(
+            "\"{}\"{}",
+            term.replace('"', "\"\""),
+            if prefix { "*" } else { "" }
+        ));
+    }
+    if terms.is_empty() {
+        None
+    } else {
+        Some(terms.join(" "))
+    }
+}
+
+/// Removes the highlight markers, for output that can't be styled
+pub fn strip_highlights(text: &str) -> String {
+    text.replace(HIGHLIGHT_START, "").replace(HIGHLIGHT_END, "")
+}
@@ -0,0 +1,122 @@
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, TimeDelta, Utc};
+
+use crate::models::{NewTodo, Priority, Todos};
+use crate::recurrence::Recurrence;
+use crate::storage::{MemoryStorage, Storage};
+use crate::Error;
+
+/// Keeps TODOs in a JSON file. The whole file is read when it's opened and written again after
+/// every change, which is fine for the handful of TODOs a test or a small app has.
+pub struct JsonStorage {
+    path: PathBuf,
+    data: MemoryStorage,
+}
+
+impl JsonStorage {
+    /// Reads the file at `path`, a missing file is an empty storage that's created on the first
+    /// change
+    pub fn open(path: &Path) -> Result<Self, Error> {
+        let data = match std::fs::read_to_string(path) {
+            Ok(content) => serde_json::from_str(&content).map_err(|e| Error::Io {
+                path: path.to_path_buf(),
+                source: e.into(),
+            })?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => MemoryStorage::default(),
+            Err(e) => {
+                return Err(Error::Io {
+                    path: path.to_path_buf(),
+                    source: e,
+                })
+            }
+        };
+        Ok(JsonStorage {
+            path: path.to_path_buf(),
+            data,
+        })
+    }
+
+    /// Applies a change to a copy and writes the file, the copy is only kept once it's written so
+    /// a failed change or write leaves the storage as it was
+    fn change<T>(
+        &mut self,
+        f: impl FnOnce(&mut MemoryStorage) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        let mut data = self.data.clone();
+        let value = f(&mut data)?;
+        let content = serde_json::to_string_pretty(&data).map_err(|e| Error::Io {
+            path: self.path.clone(),
+            source: e.into(),
+        })?;
+        std::fs::write(&self.path, content).map_err(Error::io(&self.path))?;
+        self.data = data;
+        Ok(value)
+    }
+}
+
+impl Storage for JsonStorage {
+    fn add_todo(&mut self, todo: &NewTodo) -> Result<Todos, Error> {
+        self.change(|data| data.add_todo(todo))
+    }
+
+    fn get_todo(&mut self, show_id: &str) -> Result<Todos, Error> {
+        self.data.get_todo(show_id)
+    }
+
+    fn get_todos(&mut self) -> Result<Vec<Todos>, Error> {
+        self.data.get_todos()
+    }
+
+    fn set_todo_title(&mut self, show_id: &str, title: &str) -> Result<(), Error> {
+        self.change(|data| data.set_todo_title(show_id, title))
+    }
+
+    fn set_todo_notes(&mut self, show_id: &str, notes: &str) -> Result<(), Error> {
+        self.change(|data| data.set_todo_notes(show_id, notes))
+    }
+
+    fn set_due(&mut self, show_id: &str, ts: Option<DateTime<Utc>>) -> Result<(), Error> {
+        self.change(|data| data.set_due(show_id, ts))
+    }
+
+    fn set_priority(&mut self, show_id: &str, priority: Priority) -> Result<(), Error> {
+        self.change(|data| data.set_priority(show_id, priority))
+    }
+
+    fn set_estimate(&mut self, show_id: &str, estimate: Option<TimeDelta>) -> Result<(), Error> {
+        self.change(|data| data.set_estimate(show_id, estimate))
+    }
+
+    fn set_recurrence(&mut self, show_id: &str, rule: Option<&Recurrence>) -> Result<(), Error> {
+        self.change(|data| data.set_recurrence(show_id, rule))
+    }
+
+    fn complete_todo(
+        &mut self,
+        show_id: &str,
+        ts: Option<DateTime<Utc>>,
+    ) -> Result<Option<Todos>, Error> {
+        self.change(|data| data.complete_todo(show_id, ts))
+    }
+
+    fn reopen_todo(&mut self, show_id: &str) -> Result<(), Error> {
+        self.change(|data| data.reopen_todo(show_id))
+    }
+
+    fn delete_todo(&mut self, show_id: &str) -> Result<(), Error> {
+        self.change(|data| data.delete_todo(show_id))
+    }
+
+    fn get_todo_tags(&mut self, show_id: &str) -> Result<Vec<String>, Error> {
+        self.data.get_todo_tags(show_id)
+    }
+
+    fn add_tags(&mut self, show_id: &str, tag_names: &[String]) -> Result<(), Error> {
+        self.change(|data| data.add_tags(show_id, tag_names))
+    }
+
+    fn remove_tags(&mut self, show_id: &str, tag_names: &[String]) -> Result<(), Error> {
+        self.change(|data| data.remove_tags(show_id, tag_names))
+    }
+}
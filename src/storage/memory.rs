@@ -0,0 +1,184 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::models::{NewTodo, Priority, Todos};
+use crate::recurrence::Recurrence;
+use crate::storage::Storage;
+use crate::{decode_id, estimate_minutes, next_occurrence, parse_recurrence, Error};
+
+/// Keeps TODOs in memory, they're gone once it's dropped. Ids are handed out like SQLite does,
+/// one past the highest id in use.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct MemoryStorage {
+    todos: BTreeMap<i32, Todos>,
+    tags: BTreeMap<i32, BTreeSet<String>>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn todo_mut(&mut self, show_id: &str) -> Result<&mut Todos, Error> {
+        let decoded_id = decode_id(show_id)?;
+        self.todos
+            .get_mut(&decoded_id)
+            .ok_or_else(|| Error::todo_not_found(show_id))
+    }
+
+    fn next_id(&self) -> i32 {
+        self.todos.keys().next_back().map_or(1, |id| id + 1)
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn add_todo(&mut self, todo: &NewTodo) -> Result<Todos, Error> {
+        let new_todo = Todos {
+            id: self.next_id(),
+            title: todo.title.to_string(),
+            notes: todo.notes.to_string(),
+            created: todo.created,
+            completed: None,
+            due: None,
+            project_id: None,
+            priority: Priority::Unset.level(),
+            recurrence: None,
+            template_id: None,
+            parent_id: None,
+            estimate: None,
+        };
+        self.todos.insert(new_todo.id, new_todo.clone());
+        Ok(new_todo)
+    }
+
+    fn get_todo(&mut self, show_id: &str) -> Result<Todos, Error> {
+        self.todo_mut(show_id).map(|todo| todo.clone())
+    }
+
+    fn get_todos(&mut self) -> Result<Vec<Todos>, Error> {
+        Ok(self.todos.values().cloned().collect())
+    }
+
+    fn set_todo_title(&mut self, show_id: &str, title: &str) -> Result<(), Error> {
+        self.todo_mut(show_id)?.title = title.to_string();
+        Ok(())
+    }
+
+    fn set_todo_notes(&mut self, show_id: &str, notes: &str) -> Result<(), Error> {
+        self.todo_mut(show_id)?.notes = notes.to_string();
+        Ok(())
+    }
+
+    fn set_due(&mut self, show_id: &str, ts: Option<DateTime<Utc>>) -> Result<(), Error> {
+        self.todo_mut(show_id)?.due = ts;
+        Ok(())
+    }
+
+    fn set_priority(&mut self, show_id: &str, priority: Priority) -> Result<(), Error> {
+        self.todo_mut(show_id)?.priority = priority.level();
+        Ok(())
+    }
+
+    fn set_estimate(&mut self, show_id: &str, estimate: Option<TimeDelta>) -> Result<(), Error> {
+        let minutes = estimate_minutes(estimate)?;
+        self.todo_mut(show_id)?.estimate = minutes;
+        Ok(())
+    }
+
+    fn set_recurrence(&mut self, show_id: &str, rule: Option<&Recurrence>) -> Result<(), Error> {
+        self.todo_mut(show_id)?.recurrence = rule.map(|r| r.to_string());
+        Ok(())
+    }
+
+    fn complete_todo(
+        &mut self,
+        show_id: &str,
+        ts: Option<DateTime<Utc>>,
+    ) -> Result<Option<Todos>, Error> {
+        let completion_ts = ts.unwrap_or_else(Utc::now);
+        let found_todo = self.get_todo(show_id)?;
+        if found_todo.completed.is_some() {
+            return Ok(None);
+        }
+        let next_due = found_todo
+            .recurrence
+            .as_ref()
+            .map(|rule| parse_recurrence(show_id, rule))
+            .transpose()?
+            .map(|recurrence| {
+                next_occurrence(
+                    show_id,
+                    &recurrence,
+                    found_todo.due.unwrap_or(completion_ts),
+                )
+            })
+            .transpose()?;
+        self.todo_mut(show_id)?.completed = Some(completion_ts);
+        let Some(next_due) = next_due else {
+            return Ok(None);
+        };
+        let next_todo = Todos {
+            id: self.next_id(),
+            created: Utc::now(),
+            completed: None,
+            due: Some(next_due),
+            template_id: Some(found_todo.template_id.unwrap_or(found_todo.id)),
+            ..found_todo.clone()
+        };
+        if let Some(found_tags) = self.tags.get(&found_todo.id).cloned() {
+            self.tags.insert(next_todo.id, found_tags);
+        }
+        self.todos.insert(next_todo.id, next_todo.clone());
+        Ok(Some(next_todo))
+    }
+
+    fn reopen_todo(&mut self, show_id: &str) -> Result<(), Error> {
+        self.todo_mut(show_id)?.completed = None;
+        Ok(())
+    }
+
+    fn delete_todo(&mut self, show_id: &str) -> Result<(), Error> {
+        let decoded_id = decode_id(show_id)?;
+        if self.todos.remove(&decoded_id).is_none() {
+            return Err(Error::todo_not_found(show_id));
+        }
+        self.tags.remove(&decoded_id);
+        for todo in self.todos.values_mut() {
+            if todo.template_id == Some(decoded_id) {
+                todo.template_id = None;
+            }
+            if todo.parent_id == Some(decoded_id) {
+                todo.parent_id = None;
+            }
+        }
+        Ok(())
+    }
+
+    fn get_todo_tags(&mut self, show_id: &str) -> Result<Vec<String>, Error> {
+        let decoded_id = decode_id(show_id)?;
+        Ok(self
+            .tags
+            .get(&decoded_id)
+            .map(|tags| tags.iter().cloned().collect())
+            .unwrap_or_default())
+    }
+
+    fn add_tags(&mut self, show_id: &str, tag_names: &[String]) -> Result<(), Error> {
+        let decoded_id = self.todo_mut(show_id)?.id;
+        self.tags
+            .entry(decoded_id)
+            .or_default()
+            .extend(tag_names.iter().cloned());
+        Ok(())
+    }
+
+    fn remove_tags(&mut self, show_id: &str, tag_names: &[String]) -> Result<(), Error> {
+        let decoded_id = self.todo_mut(show_id)?.id;
+        if let Some(tags) = self.tags.get_mut(&decoded_id) {
+            tags.retain(|tag| !tag_names.contains(tag));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,105 @@
+pub mod json;
+pub mod memory;
+
+use chrono::{DateTime, TimeDelta, Utc};
+
+use crate::models::{NewTodo, Priority, Todos};
+use crate::recurrence::Recurrence;
+use crate::{Error, TodoStore};
+
+pub use self::json::JsonStorage;
+pub use self::memory::MemoryStorage;
+
+/// The TODO operations every backend supports. [`TodoStore`] keeps TODOs in SQLite along with
+/// everything else, like projects and sessions. [`MemoryStorage`] and [`JsonStorage`] only keep
+/// TODOs and their tags, for tests and apps that don't want a database.
+pub trait Storage {
+    fn add_todo(&mut self, todo: &NewTodo) -> Result<Todos, Error>;
+    fn get_todo(&mut self, show_id: &str) -> Result<Todos, Error>;
+    fn get_todos(&mut self) -> Result<Vec<Todos>, Error>;
+    fn set_todo_title(&mut self, show_id: &str, title: &str) -> Result<(), Error>;
+    fn set_todo_notes(&mut self, show_id: &str, notes: &str) -> Result<(), Error>;
+    fn set_due(&mut self, show_id: &str, ts: Option<DateTime<Utc>>) -> Result<(), Error>;
+    fn set_priority(&mut self, show_id: &str, priority: Priority) -> Result<(), Error>;
+    fn set_estimate(&mut self, show_id: &str, estimate: Option<TimeDelta>) -> Result<(), Error>;
+    fn set_recurrence(&mut self, show_id: &str, rule: Option<&Recurrence>) -> Result<(), Error>;
+    /// Completes a TODO. If it's recurring the next occurrence is spawned and returned, a TODO
+    /// that's already completed keeps its completion time and nothing is spawned.
+    fn complete_todo(
+        &mut self,
+        show_id: &str,
+        ts: Option<DateTime<Utc>>,
+    ) -> Result<Option<Todos>, Error>;
+    fn reopen_todo(&mut self, show_id: &str) -> Result<(), Error>;
+    fn delete_todo(&mut self, show_id: &str) -> Result<(), Error>;
+    /// Tag names of a TODO, sorted
+    fn get_todo_tags(&mut self, show_id: &str) -> Result<Vec<String>, Error>;
+    fn add_tags(&mut self, show_id: &str, tag_names: &[String]) -> Result<(), Error>;
+    fn remove_tags(&mut self, show_id: &str, tag_names: &[String]) -> Result<(), Error>;
+}
+
+impl Storage for TodoStore {
+    fn add_todo(&mut self, todo: &NewTodo) -> Result<Todos, Error> {
+        TodoStore::add_todo(self, todo)
+    }
+
+    fn get_todo(&mut self, show_id: &str) -> Result<Todos, Error> {
+        TodoStore::get_todo(self, show_id)
+    }
+
+    fn get_todos(&mut self) -> Result<Vec<Todos>, Error> {
+        TodoStore::get_todos(self)
+    }
+
+    fn set_todo_title(&mut self, show_id: &str, title: &str) -> Result<(), Error> {
+        TodoStore::set_todo_title(self, show_id, title)
+    }
+
+    fn set_todo_notes(&mut self, show_id: &str, notes: &str) -> Result<(), Error> {
+        TodoStore::set_todo_notes(self, show_id, notes)
+    }
+
+    fn set_due(&mut self, show_id: &str, ts: Option<DateTime<Utc>>) -> Result<(), Error> {
+        TodoStore::set_due(self, show_id, ts)
+    }
+
+    fn set_priority(&mut self, show_id: &str, priority: Priority) -> Result<(), Error> {
+        TodoStore::set_priority(self, show_id, priority)
+    }
+
+    fn set_estimate(&mut self, show_id: &str, estimate: Option<TimeDelta>) -> Result<(), Error> {
+        TodoStore::set_estimate(self, show_id, estimate)
+    }
+
+    fn set_recurrence(&mut self, show_id: &str, rule: Option<&Recurrence>) -> Result<(), Error> {
+        TodoStore::set_recurrence(self, show_id, rule)
+    }
+
+    fn complete_todo(
+        &mut self,
+        show_id: &str,
+        ts: Option<DateTime<Utc>>,
+    ) -> Result<Option<Todos>, Error> {
+        TodoStore::complete_todo(self, show_id, ts)
+    }
+
+    fn reopen_todo(&mut self, show_id: &str) -> Result<(), Error> {
+        TodoStore::reopen_todo(self, show_id)
+    }
+
+    fn delete_todo(&mut self, show_id: &str) -> Result<(), Error> {
+        TodoStore::delete_todo(self, show_id)
+    }
+
+    fn get_todo_tags(&mut self, show_id: &str) -> Result<Vec<String>, Error> {
+        TodoStore::get_todo_tags(self, show_id)
+    }
+
+    fn add_tags(&mut self, show_id: &str, tag_names: &[String]) -> Result<(), Error> {
+        TodoStore::add_tags(self, show_id, tag_names)
+    }
+
+    fn remove_tags(&mut self, show_id: &str, tag_names: &[String]) -> Result<(), Error> {
+        TodoStore::remove_tags(self, show_id, tag_names)
+    }
+}
@@ -0,0 +1,1163 @@
+use chrono::*;
+use diesel::connection::{AnsiTransactionManager, TransactionManager};
+use diesel::prelude::*;
+use diesel::sqlite::SqliteConnection;
+use diesel_migrations::MigrationHarness;
+
+use crate::journal::{self, Key, PendingOperation};
+use crate::models::{
+    NewProject, NewSession, NewTag, NewTodo, NewView, Operation, Priority, Project, Session, Tag,
+    TodoDependency, TodoTag, Todos, View,
+};
+use crate::schema::{projects, sessions, tags, todo_dependencies, todo_tags, todos, views};
+use crate::{
+    check_updated, decode_id, encode_id, estimate_minutes, filter, next_occurrence,
+    parse_recurrence, search, Error, MIGRATIONS,
+};
+
+/// Every database operation, on a single connection that is opened and migrated once. The free
+/// functions of the crate open a store per call, batch commands and embedding apps should keep one
+/// around instead.
+pub struct TodoStore {
+    connection: SqliteConnection,
+    /// The operation being journaled, see [`TodoStore::operation`]
+    pending: Option<PendingOperation>,
+}
+
+impl TodoStore {
+    /// Opens the database in the data folder, see [`crate::get_db_file`]
+    pub fn open() -> Result<Self, Error> {
+        Self::open_at(&crate::get_db_file()?)
+    }
+
+    /// Opens, or creates, the database at `path` and runs pending migrations on it
+    pub fn open_at(path: &std::path::Path) -> Result<Self, Error> {
+        let database_url = path.display().to_string();
+        let mut connection = SqliteConnection::establish(&database_url).map_err(|e| Error::Db {
+            context: format!("Error connecting to {}", database_url),
+            source: Box::new(e),
+        })?;
+        //TODO: a match here could perform log a message for successful migrations
+        connection
+            .run_pending_migrations(MIGRATIONS)
+            .map_err(|source| Error::Db {
+                context: "Migrations couldn't be run".to_string(),
+                source,
+            })?;
+        Ok(TodoStore {
+            connection,
+            pending: None,
+        })
+    }
+
+    pub fn into_connection(self) -> SqliteConnection {
+        self.connection
+    }
+
+    /// Runs `f` in a transaction, which is rolled back if it returns an error. Transactions can be
+    /// nested, inner ones use savepoints.
+    pub fn transaction<T, E, F>(&mut self, f: F) -> Result<T, E>
+    where
+        F: FnOnce(&mut TodoStore) -> Result<T, E>,
+        E: From<Error>,
+    {
+        AnsiTransactionManager::begin_transaction(&mut self.connection)
+            .map_err(Error::db("Transaction couldn't be started"))?;
+        match f(self) {
+            Ok(value) => {
+                AnsiTransactionManager::commit_transaction(&mut self.connection)
+                    .map_err(Error::db("Transaction couldn't be committed"))?;
+                Ok(value)
+            }
+            Err(e) => {
+                AnsiTransactionManager::rollback_transaction(&mut self.connection)
+                    .map_err(Error::db("Transaction couldn't be rolled back"))?;
+                Err(e)
+            }
+        }
+    }
+
+    /// Runs `f` in a transaction as a single operation of the journal, so that everything it
+    /// changes is undone at once. Every change done through the store is journaled, on its own
+    /// unless it's part of an operation like this one.
+    pub fn operation<T, E, F>(&mut self, description: impl Into<String>, f: F) -> Result<T, E>
+    where
+        F: FnOnce(&mut TodoStore) -> Result<T, E>,
+        E: From<Error>,
+    {
+        self.journaled(description.into(), f)
+    }
+
+    fn journaled<T, E, F>(&mut self, description: String, f: F) -> Result<T, E>
+    where
+        F: FnOnce(&mut TodoStore) -> Result<T, E>,
+        E: From<Error>,
+    {
+        let outermost = self.pending.is_none();
+        let result = self.transaction(|store| {
+            if outermost {
+                store.pending = Some(
+                    PendingOperation::start(&mut store.connection, description)
+                        .map_err(Error::db("Journal couldn't be read"))?,
+                );
+            }
+            let value = f(store)?;
+            // Nested calls leave the operation for the outermost one to finish
+            if outermost {
+                if let Some(pending) = store.pending.take() {
+                    pending
+                        .finish(&mut store.connection)
+                        .map_err(Error::db("Journal couldn't be written"))?;
+                }
+            }
+            Ok(value)
+        });
+        if outermost {
+            self.pending = None;
+        }
+        result
+    }
+
+    /// Keeps how a row looks before the operation in progress changes it
+    fn track(&mut self, key: Key) -> Result<(), Error> {
+        match &mut self.pending {
+            Some(pending) => pending
+                .track(&mut self.connection, key)
+                .map_err(Error::db("Journal couldn't be read")),
+            None => Ok(()),
+        }
+    }
+
+    /// Tracks a TODO along with the ones that refer to it, which are changed when it's deleted
+    fn track_linked_todos(&mut self, todo_id: i32) -> Result<(), Error> {
+        let linked_ids: Vec<i32> = todos::table
+            .filter(
+                todos::parent_id
+                    .eq(todo_id)
+                    .or(todos::template_id.eq(todo_id)),
+            )
+            .select(todos::id)
+            .load(&mut self.connection)
+            .map_err(Error::db("Was unable to load linked TODOs"))?;
+        let dependent_ids: Vec<i32> = todo_dependencies::table
+            .filter(todo_dependencies::blocker_id.eq(todo_id))
+            .select(todo_dependencies::todo_id)
+            .load(&mut self.connection)
+            .map_err(Error::db("Was unable to load dependencies"))?;
+        for id in std::iter::once(todo_id)
+            .chain(linked_ids)
+            .chain(dependent_ids)
+        {
+            self.track(Key::Todo(id))?;
+        }
+        Ok(())
+    }
+
+    fn track_view(&mut self, name: &str) -> Result<(), Error> {
+        if let Some(view) = self.get_view(name)? {
+            self.track(Key::View(view.id))?;
+        }
+        Ok(())
+    }
+
+    fn track_active_session(&mut self) -> Result<(), Error> {
+        if let Some(session) = self.get_active_session()? {
+            self.track(Key::Todo(session.todo_id))?;
+        }
+        Ok(())
+    }
+
+    /// Reverts the last `n` operations that weren't undone yet, newest first, and returns them
+    pub fn undo(&mut self, n: usize) -> Result<Vec<Operation>, Error> {
+        self.transaction(|store| {
+            journal::undo(&mut store.connection, n)
+                .map_err(Error::db("Operations couldn't be undone"))
+        })
+    }
+
+    /// Applies the first `n` undone operations again, oldest first, and returns them
+    pub fn redo(&mut self, n: usize) -> Result<Vec<Operation>, Error> {
+        self.transaction(|store| {
+            journal::redo(&mut store.connection, n)
+                .map_err(Error::db("Operations couldn't be redone"))
+        })
+    }
+
+    pub fn add_todo(&mut self, todo: &NewTodo) -> Result<Todos, Error> {
+        self.journaled(format!("add \"{}\"", todo.title), |store| {
+            let connection = &mut store.connection;
+            diesel::insert_into(todos::table)
+                .values(todo)
+                .returning(Todos::as_returning())
+                .get_result(connection)
+                .map_err(Error::db("Error saving new TODO"))
+        })
+    }
+
+    pub fn get_todo(&mut self, get_id: &str) -> Result<Todos, Error> {
+        use crate::schema::todos::dsl::*;
+        let decoded_id = decode_id(get_id)?;
+        let connection = &mut self.connection;
+        todos
+            .select(Todos::as_select())
+            .filter(id.eq(decoded_id))
+            .first(connection)
+            .optional()
+            .map_err(Error::db(format!("TODO {} couldn't be loaded", get_id)))?
+            .ok_or_else(|| Error::todo_not_found(get_id))
+    }
+
+    pub fn get_todos(&mut self) -> Result<Vec<Todos>, Error> {
+        use crate::schema::todos::dsl::*;
+        let connection = &mut self.connection;
+        todos
+            .select(Todos::as_select())
+            .load(connection)
+            .map_err(Error::db("Was unable to get all TODOs"))
+    }
+
+    pub fn get_todos_with_tags(&mut self, tag_names: &[String]) -> Result<Vec<Todos>, Error> {
+        let connection = &mut self.connection;
+        let mut query = todos::table.select(Todos::as_select()).into_boxed();
+        // Every requested tag has to be present on a TODO for it to be returned
+        for tag_name in tag_names {
+            query = query.filter(
+                todos::id.eq_any(
+                    todo_tags::table
+                        .inner_join(tags::table)
+                        .filter(tags::name.eq(tag_name.clone()))
+                        .select(todo_tags::todo_id),
+                ),
+            );
+        }
+        query
+            .load(connection)
+            .map_err(Error::db("Was unable to get TODOs by tags"))
+    }
+
+    /// TODOs matching the filter, or every TODO without one
+    pub fn get_todos_where(
+        &mut self,
+        filter: Option<&filter::Filter>,
+    ) -> Result<Vec<Todos>, Error> {
+        let connection = &mut self.connection;
+        let mut query = todos::table
+            .select(Todos::as_select())
+            .order_by(todos::id)
+            .into_boxed();
+        if let Some(filter) = filter {
+            query = query.filter(filter.to_sql());
+        }
+        query
+            .load(connection)
+            .map_err(Error::db("Was unable to get filtered TODOs"))
+    }
+
+    /// TODOs whose title or notes match the query, see [`search::match_query`] for its syntax
+    pub fn search_todos(&mut self, query: &str) -> Result<Vec<search::SearchMatch>, Error> {
+        use diesel::sql_types::{Integer, Text};
+
+        #[derive(QueryableByName)]
+        struct SearchRow {
+            #[diesel(sql_type = Integer)]
+            id: i32,
+            #[diesel(sql_type = Text)]
+            title: String,
+            #[diesel(sql_type = Text)]
+            snippet: String,
+        }
+
+        let Some(match_query) = search::match_query(query) else {
+            return Ok(Vec::new());
+        };
+        let connection = &mut self.connection;
+        let rows: Vec<SearchRow> = diesel::sql_query(
+            "SELECT `rowid` AS `id`, \
+             highlight(`todos_search`, 0, ?1, ?2) AS `title`, \
+             snippet(`todos_search`, 1, ?1, ?2, '…', 12) AS `snippet` \
+             FROM `todos_search` WHERE `todos_search` MATCH ?3 ORDER BY `rank`",
+        )
+        .bind::<Text, _>(search::HIGHLIGHT_START)
+        .bind::<Text, _>(search::HIGHLIGHT_END)
+        .bind::<Text, _>(&match_query)
+        .load(connection)
+        .map_err(Error::db("Was unable to search TODOs"))?;
+        let mut found: std::collections::HashMap<i32, Todos> = todos::table
+            .filter(todos::id.eq_any(rows.iter().map(|row| row.id)))
+            .select(Todos::as_select())
+            .load(connection)
+            .map_err(Error::db("Was unable to get searched TODOs"))?
+            .into_iter()
+            .map(|todo| (todo.id, todo))
+            .collect();
+        Ok(rows
+            .into_iter()
+            .filter_map(|row| {
+                let todo = found.remove(&row.id)?;
+                // The snippet falls back to the start of the notes when only the title matched
+                let snippet = if row.snippet.contains(search::HIGHLIGHT_START) {
+                    row.snippet
+                } else {
+                    String::new()
+                };
+                Some(search::SearchMatch {
+                    todo,
+                    title: row.title,
+                    snippet,
+                })
+            })
+            .collect())
+    }
+
+    /// Number of tags per TODO, TODOs without tags are missing from the map
+    pub fn count_tags_by_todo(&mut self) -> Result<std::collections::HashMap<i32, i64>, Error> {
+        let connection = &mut self.connection;
+        Ok(todo_tags::table
+            .group_by(todo_tags::todo_id)
+            .select((todo_tags::todo_id, diesel::dsl::count_star()))
+            .load::<(i32, i64)>(connection)
+            .map_err(Error::db("Was unable to count tags by TODO"))?
+            .into_iter()
+            .collect())
+    }
+
+    /// Tag names of every TODO, sorted. TODOs without tags are missing from the map
+    pub fn get_tags_by_todo(
+        &mut self,
+    ) -> Result<std::collections::HashMap<i32, Vec<String>>, Error> {
+        let connection = &mut self.connection;
+        let mut tags_by_todo: std::collections::HashMap<i32, Vec<String>> =
+            std::collections::HashMap::new();
+        for (todo_id, tag_name) in todo_tags::table
+            .inner_join(tags::table)
+            .select((todo_tags::todo_id, tags::name))
+            .order_by(tags::name)
+            .load::<(i32, String)>(connection)
+            .map_err(Error::db("Was unable to get tags by TODO"))?
+        {
+            tags_by_todo.entry(todo_id).or_default().push(tag_name);
+        }
+        Ok(tags_by_todo)
+    }
+
+    pub fn get_todo_tags(&mut self, show_id: &str) -> Result<Vec<String>, Error> {
+        let decoded_id = decode_id(show_id)?;
+        let connection = &mut self.connection;
+        todo_tags::table
+            .inner_join(tags::table)
+            .filter(todo_tags::todo_id.eq(decoded_id))
+            .select(tags::name)
+            .order_by(tags::name)
+            .load(connection)
+            .map_err(Error::db(format!(
+                "Tags of TODO: {} couldn't be loaded",
+                show_id
+            )))
+    }
+
+    pub fn add_tags(&mut self, update_id: &str, tag_names: &[String]) -> Result<(), Error> {
+        self.journaled(format!("tag {}", update_id), |store| {
+            let decoded_id = store.get_todo(update_id)?.id;
+            store.track(Key::Todo(decoded_id))?;
+            let connection = &mut store.connection;
+            for tag_name in tag_names {
+                diesel::insert_or_ignore_into(tags::table)
+                    .values(NewTag { name: tag_name })
+                    .execute(connection)
+                    .map_err(Error::db(format!("Tag {} couldn't be created", tag_name)))?;
+                let tag = tags::table
+                    .select(Tag::as_select())
+                    .filter(tags::name.eq(tag_name))
+                    .first(connection)
+                    .map_err(Error::db(format!("Tag {} couldn't be found", tag_name)))?;
+                diesel::insert_or_ignore_into(todo_tags::table)
+                    .values(TodoTag {
+                        todo_id: decoded_id,
+                        tag_id: tag.id,
+                    })
+                    .execute(connection)
+                    .map_err(Error::db(format!(
+                        "TODO: {} couldn't be tagged with {}",
+                        update_id, tag_name
+                    )))?;
+            }
+            Ok(())
+        })
+    }
+
+    pub fn remove_tags(&mut self, update_id: &str, tag_names: &[String]) -> Result<(), Error> {
+        self.journaled(format!("tag {}", update_id), |store| {
+            let decoded_id = store.get_todo(update_id)?.id;
+            store.track(Key::Todo(decoded_id))?;
+            let connection = &mut store.connection;
+            diesel::delete(
+                todo_tags::table
+                    .filter(todo_tags::todo_id.eq(decoded_id))
+                    .filter(
+                        todo_tags::tag_id.eq_any(
+                            tags::table
+                                .filter(tags::name.eq_any(tag_names))
+                                .select(tags::id),
+                        ),
+                    ),
+            )
+            .execute(connection)
+            .map_err(Error::db(format!(
+                "Tags of TODO: {} couldn't be removed",
+                update_id
+            )))?;
+            Ok(())
+        })
+    }
+
+    pub fn set_todo_project(
+        &mut self,
+        update_id: &str,
+        project_name: Option<&str>,
+    ) -> Result<(), Error> {
+        self.journaled(format!("set project of {}", update_id), |store| {
+            let new_project_id = project_name
+                .map(|name| store.get_active_project(name).map(|project| project.id))
+                .transpose()?;
+            let decoded_id = decode_id(update_id)?;
+            store.track(Key::Todo(decoded_id))?;
+            let connection = &mut store.connection;
+            let rows = diesel::update(todos::table.find(decoded_id))
+                .set(todos::project_id.eq(new_project_id))
+                .execute(connection)
+                .map_err(Error::db(format!(
+                    "project of TODO: {} couldn't be updated",
+                    update_id
+                )))?;
+            check_updated(rows, update_id)
+        })
+    }
+
+    /// Completes a TODO. If it's recurring, the next occurrence is spawned and returned. A TODO
+    /// that's already completed keeps its completion time and nothing is spawned.
+    pub fn complete_todo(
+        &mut self,
+        show_id: &str,
+        ts: Option<DateTime<Utc>>,
+    ) -> Result<Option<Todos>, Error> {
+        self.journaled(format!("complete {}", show_id), |store| {
+            use crate::schema::todos::dsl::*;
+            let found_todo = store.get_todo(show_id)?;
+            if found_todo.completed.is_some() {
+                return Ok(None);
+            }
+            store.track(Key::Todo(found_todo.id))?;
+            let connection = &mut store.connection;
+            let completion_ts = ts.unwrap_or_else(Utc::now);
+            diesel::update(todos.find(found_todo.id))
+                .set(completed.eq(completion_ts))
+                .execute(connection)
+                .map_err(Error::db(format!(
+                    "TODO: {} couldn't be completed",
+                    show_id
+                )))?;
+            // There's no point in tracking time for a completed TODO
+            if store
+                .get_active_session()?
+                .is_some_and(|session| session.todo_id == found_todo.id)
+            {
+                store.stop_session()?;
+            }
+            let Some(rule) = found_todo.recurrence.as_ref() else {
+                return Ok(None);
+            };
+            let parsed_rule = parse_recurrence(show_id, rule)?;
+            let next_due = next_occurrence(
+                show_id,
+                &parsed_rule,
+                found_todo.due.unwrap_or(completion_ts),
+            )?;
+            store.spawn_next_occurrence(&found_todo, next_due).map(Some)
+        })
+    }
+
+    fn spawn_next_occurrence(
+        &mut self,
+        found_todo: &Todos,
+        next_due: DateTime<Utc>,
+    ) -> Result<Todos, Error> {
+        use crate::schema::todos::dsl::*;
+        let next_todo = self.add_todo(&NewTodo {
+            title: &found_todo.title,
+            notes: &found_todo.notes,
+            created: Utc::now(),
+        })?;
+        let connection = &mut self.connection;
+        diesel::update(todos.find(next_todo.id))
+            .set((
+                due.eq(next_due),
+                project_id.eq(found_todo.project_id),
+                priority.eq(found_todo.priority),
+                parent_id.eq(found_todo.parent_id),
+                estimate.eq(found_todo.estimate),
+                recurrence.eq(&found_todo.recurrence),
+                template_id.eq(found_todo.template_id.unwrap_or(found_todo.id)),
+            ))
+            .execute(connection)
+            .map_err(Error::db(
+                "Next occurrence of recurring TODO couldn't be created",
+            ))?;
+        let next_id = encode_id(next_todo.id.try_into().unwrap());
+        let found_tags = self.get_todo_tags(&encode_id(found_todo.id.try_into().unwrap()))?;
+        self.add_tags(&next_id, &found_tags)?;
+        self.get_todo(&next_id)
+    }
+
+    /// Makes a TODO a subtask of another one, or a top level TODO again when `parent` is `None`
+    pub fn set_parent(&mut self, update_id: &str, parent: Option<&str>) -> Result<(), Error> {
+        self.journaled(format!("set parent of {}", update_id), |store| {
+            use crate::schema::todos::dsl::*;
+            let decoded_id = decode_id(update_id)?;
+            store.track(Key::Todo(decoded_id))?;
+            let new_parent_id = match parent {
+                Some(parent_show_id) => {
+                    // Walk up from the new parent to make sure the TODO isn't one of its ancestors
+                    let mut ancestor = Some(store.get_todo(parent_show_id)?);
+                    while let Some(ancestor_todo) = ancestor {
+                        if ancestor_todo.id == decoded_id {
+                            return Err(Error::Conflict(format!(
+                                "TODO: {} can't be a subtask of {}, that would create a cycle",
+                                update_id, parent_show_id
+                            )));
+                        }
+                        ancestor = ancestor_todo
+                            .parent_id
+                            .map(|ancestor_id| {
+                                store.get_todo(&encode_id(ancestor_id.try_into().unwrap()))
+                            })
+                            .transpose()?;
+                    }
+                    Some(decode_id(parent_show_id)?)
+                }
+                None => None,
+            };
+            let connection = &mut store.connection;
+            let rows = diesel::update(todos.find(decoded_id))
+                .set(parent_id.eq(new_parent_id))
+                .execute(connection)
+                .map_err(Error::db(format!(
+                    "parent of TODO: {} couldn't be updated",
+                    update_id
+                )))?;
+            check_updated(rows, update_id)
+        })
+    }
+
+    pub fn get_subtasks(&mut self, show_id: &str) -> Result<Vec<Todos>, Error> {
+        use crate::schema::todos::dsl::*;
+        let decoded_id = decode_id(show_id)?;
+        let connection = &mut self.connection;
+        todos
+            .select(Todos::as_select())
+            .filter(parent_id.eq(decoded_id))
+            .load(connection)
+            .map_err(Error::db(format!(
+                "Subtasks of TODO: {} couldn't be loaded",
+                show_id
+            )))
+    }
+
+    /// Number of completed and total subtasks per parent TODO, TODOs without subtasks are missing
+    /// from the map
+    pub fn count_subtasks_by_parent(
+        &mut self,
+    ) -> Result<std::collections::HashMap<i32, (i64, i64)>, Error> {
+        use crate::schema::todos::dsl::*;
+        let connection = &mut self.connection;
+        let mut counts = std::collections::HashMap::new();
+        let subtasks: Vec<(Option<i32>, Option<DateTime<Utc>>)> = todos
+            .select((parent_id, completed))
+            .filter(parent_id.is_not_null())
+            .load(connection)
+            .map_err(Error::db("Was unable to count subtasks"))?;
+        for (subtask_parent_id, subtask_completed) in subtasks {
+            let (done, total) = counts.entry(subtask_parent_id.unwrap()).or_insert((0, 0));
+            if subtask_completed.is_some() {
+                *done += 1;
+            }
+            *total += 1;
+        }
+        Ok(counts)
+    }
+
+    /// Marks `blocked_show_id` as blocked until `blocker_show_id` is completed
+    pub fn add_dependency(
+        &mut self,
+        blocked_show_id: &str,
+        blocker_show_id: &str,
+    ) -> Result<(), Error> {
+        self.journaled(
+            format!("block {} on {}", blocked_show_id, blocker_show_id),
+            |store| {
+                let blocked_id = store.get_todo(blocked_show_id)?.id;
+                let blocker_id = store.get_todo(blocker_show_id)?.id;
+                store.track(Key::Todo(blocked_id))?;
+                let connection = &mut store.connection;
+                let mut blockers_of: std::collections::HashMap<i32, Vec<i32>> =
+                    std::collections::HashMap::new();
+                for dependency in todo_dependencies::table
+                    .select(TodoDependency::as_select())
+                    .load(connection)
+                    .map_err(Error::db("Was unable to load dependencies"))?
+                {
+                    blockers_of
+                        .entry(dependency.todo_id)
+                        .or_default()
+                        .push(dependency.blocker_id);
+                }
+                // The new dependency closes a cycle if the blocker is already (transitively) blocked by the
+                // blocked TODO
+                let mut visited = std::collections::HashSet::new();
+                let mut stack = vec![blocker_id];
+                while let Some(current_id) = stack.pop() {
+                    if current_id == blocked_id {
+                        return Err(Error::Conflict(format!(
+                            "TODO: {} can't be blocked on {}, that would create a cycle",
+                            blocked_show_id, blocker_show_id
+                        )));
+                    }
+                    if visited.insert(current_id) {
+                        stack.extend(blockers_of.get(&current_id).into_iter().flatten());
+                    }
+                }
+                diesel::insert_or_ignore_into(todo_dependencies::table)
+                    .values(TodoDependency {
+                        todo_id: blocked_id,
+                        blocker_id,
+                    })
+                    .execute(connection)
+                    .map_err(Error::db(format!(
+                        "TODO: {} couldn't be blocked on {}",
+                        blocked_show_id, blocker_show_id
+                    )))?;
+                Ok(())
+            },
+        )
+    }
+
+    pub fn remove_dependency(
+        &mut self,
+        blocked_show_id: &str,
+        blocker_show_id: &str,
+    ) -> Result<(), Error> {
+        self.journaled(
+            format!("unblock {} from {}", blocked_show_id, blocker_show_id),
+            |store| {
+                let blocked_id = store.get_todo(blocked_show_id)?.id;
+                let blocker_id = store.get_todo(blocker_show_id)?.id;
+                store.track(Key::Todo(blocked_id))?;
+                let connection = &mut store.connection;
+                let rows = diesel::delete(
+                    todo_dependencies::table
+                        .filter(todo_dependencies::todo_id.eq(blocked_id))
+                        .filter(todo_dependencies::blocker_id.eq(blocker_id)),
+                )
+                .execute(connection)
+                .map_err(Error::db(format!(
+                    "TODO: {} couldn't be unblocked from {}",
+                    blocked_show_id, blocker_show_id
+                )))?;
+                if rows == 0 {
+                    return Err(Error::NotFound {
+                        kind: "dependency",
+                        name: format!("{} on {}", blocked_show_id, blocker_show_id),
+                    });
+                }
+                Ok(())
+            },
+        )
+    }
+
+    /// TODOs that `show_id` is blocked by, including completed ones
+    pub fn get_blockers(&mut self, show_id: &str) -> Result<Vec<Todos>, Error> {
+        let decoded_id = decode_id(show_id)?;
+        let connection = &mut self.connection;
+        todos::table
+            .select(Todos::as_select())
+            .filter(
+                todos::id.eq_any(
+                    todo_dependencies::table
+                        .filter(todo_dependencies::todo_id.eq(decoded_id))
+                        .select(todo_dependencies::blocker_id),
+                ),
+            )
+            .load(connection)
+            .map_err(Error::db(format!(
+                "Blockers of TODO: {} couldn't be loaded",
+                show_id
+            )))
+    }
+
+    /// TODOs that are blocked by `show_id`
+    pub fn get_dependents(&mut self, show_id: &str) -> Result<Vec<Todos>, Error> {
+        let decoded_id = decode_id(show_id)?;
+        let connection = &mut self.connection;
+        todos::table
+            .select(Todos::as_select())
+            .filter(
+                todos::id.eq_any(
+                    todo_dependencies::table
+                        .filter(todo_dependencies::blocker_id.eq(decoded_id))
+                        .select(todo_dependencies::todo_id),
+                ),
+            )
+            .load(connection)
+            .map_err(Error::db(format!(
+                "Dependents of TODO: {} couldn't be loaded",
+                show_id
+            )))
+    }
+
+    /// Ids of every TODO that has at least one open blocker
+    pub fn get_blocked_todo_ids(&mut self) -> Result<std::collections::HashSet<i32>, Error> {
+        let connection = &mut self.connection;
+        Ok(todo_dependencies::table
+            .filter(
+                todo_dependencies::blocker_id.eq_any(
+                    todos::table
+                        .filter(todos::completed.is_null())
+                        .select(todos::id),
+                ),
+            )
+            .select(todo_dependencies::todo_id)
+            .load::<i32>(connection)
+            .map_err(Error::db("Was unable to load blocked TODOs"))?
+            .into_iter()
+            .collect())
+    }
+
+    pub fn set_recurrence(
+        &mut self,
+        update_id: &str,
+        rule: Option<&crate::recurrence::Recurrence>,
+    ) -> Result<(), Error> {
+        self.journaled(format!("set recurrence of {}", update_id), |store| {
+            use crate::schema::todos::dsl::*;
+            let decoded_id = decode_id(update_id)?;
+            store.track(Key::Todo(decoded_id))?;
+            let connection = &mut store.connection;
+            let rows = diesel::update(todos.find(decoded_id))
+                .set(recurrence.eq(rule.map(|r| r.to_string())))
+                .execute(connection)
+                .map_err(Error::db(format!(
+                    "recurrence of TODO: {} couldn't be updated",
+                    update_id
+                )))?;
+            check_updated(rows, update_id)
+        })
+    }
+
+    pub fn set_due(&mut self, show_id: &str, ts: Option<DateTime<Utc>>) -> Result<(), Error> {
+        self.journaled(format!("set due of {}", show_id), |store| {
+            use crate::schema::todos::dsl::*;
+            let decoded_id = decode_id(show_id)?;
+            store.track(Key::Todo(decoded_id))?;
+            let connection = &mut store.connection;
+            let rows = diesel::update(todos.find(decoded_id))
+                .set(due.eq(ts))
+                .execute(connection)
+                .map_err(Error::db(format!(
+                    "TODO: {}'s due couldn't be set to {}",
+                    show_id,
+                    match ts {
+                        Some(due_ts) => due_ts.format("%c").to_string(),
+                        None => "none".to_string(),
+                    }
+                )))?;
+            check_updated(rows, show_id)
+        })
+    }
+
+    pub fn set_priority(&mut self, update_id: &str, new_priority: Priority) -> Result<(), Error> {
+        self.journaled(format!("set priority of {}", update_id), |store| {
+            use crate::schema::todos::dsl::*;
+            let decoded_id = decode_id(update_id)?;
+            store.track(Key::Todo(decoded_id))?;
+            let connection = &mut store.connection;
+            let rows = diesel::update(todos.find(decoded_id))
+                .set(priority.eq(new_priority.level()))
+                .execute(connection)
+                .map_err(Error::db(format!(
+                    "priority of TODO: {} couldn't be updated",
+                    update_id
+                )))?;
+            check_updated(rows, update_id)
+        })
+    }
+
+    /// Stored in whole minutes, anything shorter is dropped
+    pub fn set_estimate(
+        &mut self,
+        update_id: &str,
+        new_estimate: Option<TimeDelta>,
+    ) -> Result<(), Error> {
+        self.journaled(format!("set estimate of {}", update_id), |store| {
+            use crate::schema::todos::dsl::*;
+            let decoded_id = decode_id(update_id)?;
+            store.track(Key::Todo(decoded_id))?;
+            let connection = &mut store.connection;
+            let minutes = estimate_minutes(new_estimate)?;
+            let rows = diesel::update(todos.find(decoded_id))
+                .set(estimate.eq(minutes))
+                .execute(connection)
+                .map_err(Error::db(format!(
+                    "estimate of TODO: {} couldn't be updated",
+                    update_id
+                )))?;
+            check_updated(rows, update_id)
+        })
+    }
+
+    pub fn set_todo_title(&mut self, update_id: &str, new_title: &str) -> Result<(), Error> {
+        self.journaled(format!("set title of {}", update_id), |store| {
+            use crate::schema::todos::dsl::*;
+            let decoded_id = decode_id(update_id)?;
+            store.track(Key::Todo(decoded_id))?;
+            let connection = &mut store.connection;
+            let rows = diesel::update(todos.find(decoded_id))
+                .set(title.eq(new_title))
+                .execute(connection)
+                .map_err(Error::db(format!(
+                    "title of TODO: {} couldn't be updated",
+                    update_id
+                )))?;
+            check_updated(rows, update_id)
+        })
+    }
+
+    pub fn set_todo_notes(&mut self, update_id: &str, new_notes: &str) -> Result<(), Error> {
+        self.journaled(format!("set notes of {}", update_id), |store| {
+            use crate::schema::todos::dsl::*;
+            let decoded_id = decode_id(update_id)?;
+            store.track(Key::Todo(decoded_id))?;
+            let connection = &mut store.connection;
+            let rows = diesel::update(todos.find(decoded_id))
+                .set(notes.eq(new_notes))
+                .execute(connection)
+                .map_err(Error::db(format!(
+                    "notes of TODO: {} couldn't be updated",
+                    update_id
+                )))?;
+            check_updated(rows, update_id)
+        })
+    }
+
+    pub fn reopen_todo(&mut self, show_id: &str) -> Result<(), Error> {
+        self.journaled(format!("reopen {}", show_id), |store| {
+            use crate::schema::todos::dsl::*;
+            use chrono::DateTime;
+            let decoded_id = decode_id(show_id)?;
+            store.track(Key::Todo(decoded_id))?;
+            let connection = &mut store.connection;
+            let rows = diesel::update(todos.find(decoded_id))
+                .set(completed.eq(None::<DateTime<Utc>>))
+                .execute(connection)
+                .map_err(Error::db("TODO couldn't be reopened"))?;
+            check_updated(rows, show_id)
+        })
+    }
+
+    pub fn delete_todo(&mut self, delete_id: &str) -> Result<(), Error> {
+        self.journaled(format!("delete {}", delete_id), |store| {
+            use crate::schema::todos::dsl::*;
+            let decoded_id = decode_id(delete_id)?;
+            store.track_linked_todos(decoded_id)?;
+            let connection = &mut store.connection;
+            // SQLite doesn't enforce foreign keys by default, so clean up the join tables by hand
+            diesel::delete(todo_tags::table.filter(todo_tags::todo_id.eq(decoded_id)))
+                .execute(connection)
+                .map_err(Error::db("Error removing tags of TODO"))?;
+            diesel::delete(sessions::table.filter(sessions::todo_id.eq(decoded_id)))
+                .execute(connection)
+                .map_err(Error::db("Error removing sessions of TODO"))?;
+            diesel::delete(
+                todo_dependencies::table.filter(
+                    todo_dependencies::todo_id
+                        .eq(decoded_id)
+                        .or(todo_dependencies::blocker_id.eq(decoded_id)),
+                ),
+            )
+            .execute(connection)
+            .map_err(Error::db("Error removing dependencies of TODO"))?;
+            diesel::update(todos.filter(template_id.eq(decoded_id)))
+                .set(template_id.eq(None::<i32>))
+                .execute(connection)
+                .map_err(Error::db("Error unlinking occurrences of TODO"))?;
+            diesel::update(todos.filter(parent_id.eq(decoded_id)))
+                .set(parent_id.eq(None::<i32>))
+                .execute(connection)
+                .map_err(Error::db("Error unlinking subtasks of TODO"))?;
+            let rows = diesel::delete(todos.filter(id.eq(decoded_id)))
+                .execute(connection)
+                .map_err(Error::db("Error deleting TODO"))?;
+            check_updated(rows, delete_id)
+        })
+    }
+
+    // Project operations
+    pub fn add_project(&mut self, name: &str) -> Result<Project, Error> {
+        self.journaled(format!("add project {}", name), |store| {
+            let connection = &mut store.connection;
+            diesel::insert_into(projects::table)
+                .values(NewProject {
+                    name,
+                    created: Utc::now(),
+                })
+                .returning(Project::as_returning())
+                .get_result(connection)
+                .map_err(Error::db(format!("Project {} couldn't be created", name)))
+        })
+    }
+
+    pub fn get_project(&mut self, name: &str) -> Result<Project, Error> {
+        let connection = &mut self.connection;
+        projects::table
+            .select(Project::as_select())
+            .filter(projects::name.eq(name))
+            .first(connection)
+            .optional()
+            .map_err(Error::db(format!("Project {} couldn't be loaded", name)))?
+            .ok_or_else(|| Error::NotFound {
+                kind: "project",
+                name: name.to_string(),
+            })
+    }
+
+    /// Like [`TodoStore::get_project`], but refuses archived projects
+    pub fn get_active_project(&mut self, name: &str) -> Result<Project, Error> {
+        let project = self.get_project(name)?;
+        if project.archived.is_some() {
+            return Err(Error::Conflict(format!(
+                "Project {} is archived, TODOs can't be added to it",
+                name
+            )));
+        }
+        Ok(project)
+    }
+
+    pub fn get_projects(&mut self) -> Result<Vec<Project>, Error> {
+        let connection = &mut self.connection;
+        projects::table
+            .select(Project::as_select())
+            .order_by(projects::name)
+            .load(connection)
+            .map_err(Error::db("Was unable to get all projects"))
+    }
+
+    /// Number of open TODOs per project, TODOs without a project are counted under `None`
+    pub fn count_open_todos_by_project(
+        &mut self,
+    ) -> Result<std::collections::HashMap<Option<i32>, i64>, Error> {
+        let connection = &mut self.connection;
+        Ok(todos::table
+            .filter(todos::completed.is_null())
+            .group_by(todos::project_id)
+            .select((todos::project_id, diesel::dsl::count_star()))
+            .load::<(Option<i32>, i64)>(connection)
+            .map_err(Error::db("Was unable to count open TODOs by project"))?
+            .into_iter()
+            .collect())
+    }
+
+    pub fn rename_project(&mut self, old_name: &str, new_name: &str) -> Result<(), Error> {
+        self.journaled(
+            format!("rename project {} to {}", old_name, new_name),
+            |store| {
+                let project = store.get_project(old_name)?;
+                store.track(Key::Project(project.id))?;
+                let connection = &mut store.connection;
+                diesel::update(projects::table.find(project.id))
+                    .set(projects::name.eq(new_name))
+                    .execute(connection)
+                    .map_err(Error::db(format!(
+                        "Project {} couldn't be renamed to {}",
+                        old_name, new_name
+                    )))?;
+                Ok(())
+            },
+        )
+    }
+
+    pub fn archive_project(&mut self, name: &str) -> Result<(), Error> {
+        self.journaled(format!("archive project {}", name), |store| {
+            let project = store.get_project(name)?;
+            store.track(Key::Project(project.id))?;
+            let connection = &mut store.connection;
+            diesel::update(projects::table.find(project.id))
+                .set(projects::archived.eq(Utc::now()))
+                .execute(connection)
+                .map_err(Error::db(format!("Project {} couldn't be archived", name)))?;
+            Ok(())
+        })
+    }
+
+    /// Saves a view, replacing the one with the same name if there's one
+    pub fn save_view(&mut self, view: &NewView) -> Result<View, Error> {
+        self.journaled(format!("save view {}", view.name), |store| {
+            store.track_view(view.name)?;
+            let connection = &mut store.connection;
+            diesel::insert_into(views::table)
+                .values(view)
+                .on_conflict(views::name)
+                .do_update()
+                .set(view)
+                .returning(View::as_returning())
+                .get_result(connection)
+                .map_err(Error::db(format!("View {} couldn't be saved", view.name)))
+        })
+    }
+
+    pub fn get_view(&mut self, name: &str) -> Result<Option<View>, Error> {
+        let connection = &mut self.connection;
+        views::table
+            .select(View::as_select())
+            .filter(views::name.eq(name))
+            .first(connection)
+            .optional()
+            .map_err(Error::db("Was unable to get view"))
+    }
+
+    pub fn get_views(&mut self) -> Result<Vec<View>, Error> {
+        let connection = &mut self.connection;
+        views::table
+            .select(View::as_select())
+            .order_by(views::name)
+            .load(connection)
+            .map_err(Error::db("Was unable to get all views"))
+    }
+
+    /// Returns whether there was a view with that name
+    pub fn delete_view(&mut self, name: &str) -> Result<bool, Error> {
+        self.journaled(format!("delete view {}", name), |store| {
+            store.track_view(name)?;
+            let connection = &mut store.connection;
+            Ok(diesel::delete(views::table.filter(views::name.eq(name)))
+                .execute(connection)
+                .map_err(Error::db(format!("View {} couldn't be deleted", name)))?
+                > 0)
+        })
+    }
+
+    // Time tracking operations
+    pub fn get_active_session(&mut self) -> Result<Option<Session>, Error> {
+        let connection = &mut self.connection;
+        sessions::table
+            .select(Session::as_select())
+            .filter(sessions::stopped.is_null())
+            .first(connection)
+            .optional()
+            .map_err(Error::db("Was unable to get active session"))
+    }
+
+    /// Starts tracking time on a TODO. The active session, if any, is stopped first and returned
+    /// along with the new one. Starting the TODO that is already being tracked keeps the active
+    /// session going.
+    pub fn start_session(&mut self, show_id: &str) -> Result<(Session, Option<Session>), Error> {
+        self.journaled(format!("start {}", show_id), |store| {
+            let found_todo = store.get_todo(show_id)?;
+            store.track(Key::Todo(found_todo.id))?;
+            store.track_active_session()?;
+            let connection = &mut store.connection;
+            connection
+                .transaction::<_, diesel::result::Error, _>(|conn| {
+                    let now = Utc::now();
+                    let active_session = sessions::table
+                        .select(Session::as_select())
+                        .filter(sessions::stopped.is_null())
+                        .first(conn)
+                        .optional()?;
+                    let stopped_session = match active_session {
+                        Some(active_session) if active_session.todo_id == found_todo.id => {
+                            return Ok((active_session, None));
+                        }
+                        Some(active_session) => Some(
+                            diesel::update(sessions::table.find(active_session.id))
+                                .set(sessions::stopped.eq(now))
+                                .returning(Session::as_returning())
+                                .get_result(conn)?,
+                        ),
+                        None => None,
+                    };
+                    let new_session = diesel::insert_into(sessions::table)
+                        .values(NewSession {
+                            todo_id: found_todo.id,
+                            started: now,
+                        })
+                        .returning(Session::as_returning())
+                        .get_result(conn)?;
+                    Ok((new_session, stopped_session))
+                })
+                .map_err(Error::db(format!(
+                    "Session for TODO: {} couldn't be started",
+                    show_id
+                )))
+        })
+    }
+
+    /// Stops the active session and returns it, if there was one
+    pub fn stop_session(&mut self) -> Result<Option<Session>, Error> {
+        self.journaled("stop".to_string(), |store| {
+            store.track_active_session()?;
+            let connection = &mut store.connection;
+            diesel::update(sessions::table.filter(sessions::stopped.is_null()))
+                .set(sessions::stopped.eq(Utc::now()))
+                .returning(Session::as_returning())
+                .get_result(connection)
+                .optional()
+                .map_err(Error::db("Active session couldn't be stopped"))
+        })
+    }
+
+    pub fn get_sessions(&mut self, show_id: &str) -> Result<Vec<Session>, Error> {
+        let decoded_id = decode_id(show_id)?;
+        let connection = &mut self.connection;
+        sessions::table
+            .select(Session::as_select())
+            .filter(sessions::todo_id.eq(decoded_id))
+            .order_by(sessions::started)
+            .load(connection)
+            .map_err(Error::db(format!(
+                "Sessions of TODO: {} couldn't be loaded",
+                show_id
+            )))
+    }
+
+    /// Sessions that overlap with `since..until`, including the active one
+    pub fn get_sessions_between(
+        &mut self,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<Vec<Session>, Error> {
+        let connection = &mut self.connection;
+        sessions::table
+            .select(Session::as_select())
+            .filter(sessions::started.lt(until))
+            .filter(sessions::stopped.is_null().or(sessions::stopped.gt(since)))
+            .order_by(sessions::started)
+            .load(connection)
+            .map_err(Error::db("Was unable to load sessions"))
+    }
+
+    /// Total tracked time per TODO, the active session counts up until `now`. TODOs without sessions
+    /// are missing from the map.
+    pub fn tracked_time_by_todo(
+        &mut self,
+        now: DateTime<Utc>,
+    ) -> Result<std::collections::HashMap<i32, TimeDelta>, Error> {
+        let connection = &mut self.connection;
+        let mut totals = std::collections::HashMap::new();
+        for session in sessions::table
+            .select(Session::as_select())
+            .load(connection)
+            .map_err(Error::db("Was unable to load sessions"))?
+        {
+            *totals.entry(session.todo_id).or_insert(TimeDelta::zero()) += session.duration(now);
+        }
+        Ok(totals)
+    }
+}
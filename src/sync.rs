@@ -0,0 +1,301 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use crate::formats::todotxt::{no_spaces, Task};
+use crate::models::Todos;
+use crate::output::TodoJsonContext;
+use crate::{Error, TodoStore};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SyncChangeKind {
+    AddedToWorkingon,
+    AddedToFile,
+    UpdatedWorkingon,
+    UpdatedFile,
+    DeletedFromWorkingon,
+    DeletedFromFile,
+    /// Both sides changed since the last sync, workingon's version was kept
+    Conflict,
+    /// The line couldn't be applied and was left as is
+    Skipped,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SyncChange {
+    pub kind: SyncChangeKind,
+    /// The sqids id, if the line has one
+    pub id: Option<String>,
+    /// Title of the TODO, or why the line was skipped
+    pub detail: String,
+}
+
+enum Line {
+    Verbatim(String),
+    Todo(String),
+}
+
+/// Where the todo.txt lines of the last sync are kept, to tell which side changed since
+fn get_sync_state_file() -> Result<PathBuf, Error> {
+    let mut path = crate::get_project_data_folder()?;
+    path.push("todotxt-sync.json");
+    Ok(path)
+}
+
+type SyncState = HashMap<String, HashMap<String, String>>;
+
+/// Reads the lines of earlier syncs, a missing file means nothing was synced yet. A state that
+/// can't be read stops the sync, without it every edit looks like a conflict.
+fn load_sync_state() -> Result<SyncState, Error> {
+    let path = get_sync_state_file()?;
+    match std::fs::read_to_string(&path) {
+        Ok(content) => serde_json::from_str(&content).map_err(|e| Error::Io {
+            path,
+            source: e.into(),
+        }),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(SyncState::default()),
+        Err(e) => Err(Error::Io { path, source: e }),
+    }
+}
+
+fn save_sync_state(state: &SyncState) -> Result<(), Error> {
+    let path = get_sync_state_file()?;
+    let content = serde_json::to_string(state).map_err(|e| Error::Io {
+        path: path.clone(),
+        source: e.into(),
+    })?;
+    std::fs::write(&path, content).map_err(Error::io(&path))
+}
+
+fn todos_by_show_id(store: &mut TodoStore) -> Result<HashMap<String, Todos>, Error> {
+    Ok(store
+        .get_todos()?
+        .into_iter()
+        .map(|todo| (crate::encode_id(todo.id.try_into().unwrap()), todo))
+        .collect())
+}
+
+/// Reconciles a todo.txt file with the database, matching lines by their `id:` key. Each side is
+/// compared with how the line looked after the last sync to tell which one changed:
+///
+/// - lines without an id are added as new TODOs and get one
+/// - a line changed in the file is applied to its TODO, a TODO changed in workingon rewrites its
+///   line, if both changed workingon wins
+/// - lines removed from the file delete their open TODO, completed ones are kept in workingon
+///   since todo.txt tools archive them into done.txt
+/// - TODOs deleted in workingon are removed from the file
+/// - open TODOs that were never synced are appended to the file
+pub fn sync_todotxt(path: &Path) -> Result<Vec<SyncChange>, Error> {
+    let content = if path.exists() {
+        std::fs::read_to_string(path).map_err(Error::io(path))?
+    } else {
+        String::new()
+    };
+    let state_key = std::path::absolute(path)
+        .map_err(Error::io(path))?
+        .to_string_lossy()
+        .to_string();
+    let mut state = load_sync_state()?;
+    let base = state.remove(&state_key).unwrap_or_default();
+    let store = &mut TodoStore::open()?;
+    // Everything the sync changes in workingon is undone at once. The file and the sync state are
+    // only written once that's committed, so they never describe changes that were rolled back.
+    let (changes, new_content, synced) =
+        store.operation(format!("sync {}", path.display()), |store| {
+            let context = TodoJsonContext::load_from(store)?;
+            let todos = todos_by_show_id(store)?;
+            let mut changes = Vec::new();
+            let mut lines = Vec::new();
+            let mut seen: HashSet<String> = HashSet::new();
+            let change = |kind, id: Option<&String>, detail: &str| SyncChange {
+                kind,
+                id: id.cloned(),
+                detail: detail.to_string(),
+            };
+
+            for line in content.lines() {
+                if line.trim().is_empty() {
+                    lines.push(Line::Verbatim(line.to_string()));
+                    continue;
+                }
+                let task = Task::parse(line);
+                let existing = task
+                    .id
+                    .as_ref()
+                    .and_then(|id| todos.get(id).map(|todo| (id, todo)));
+                if let Some((id, todo)) = existing {
+                    if !seen.insert(id.clone()) {
+                        // A second line with the same id, only the first one is synced
+                        lines.push(Line::Verbatim(line.to_string()));
+                        continue;
+                    }
+                    let db_line = Task::from_todo(&context.todo_json(todo)).to_string();
+                    let file_line = task.to_string();
+                    let base_line = base.get(id);
+                    if file_line != db_line {
+                        if base_line == Some(&file_line) {
+                            changes.push(change(
+                                SyncChangeKind::UpdatedFile,
+                                Some(id),
+                                &todo.title,
+                            ));
+                        } else if base_line == Some(&db_line) {
+                            changes.push(match apply_task(store, id, &task, todo)? {
+                                Ok(()) => {
+                                    change(SyncChangeKind::UpdatedWorkingon, Some(id), &task.title)
+                                }
+                                Err(e) => change(SyncChangeKind::Skipped, Some(id), &e),
+                            });
+                        } else {
+                            changes.push(change(SyncChangeKind::Conflict, Some(id), &todo.title));
+                        }
+                    }
+                    lines.push(Line::Todo(id.clone()));
+                } else if let Some(id) = task.id.as_ref().filter(|id| base.contains_key(*id)) {
+                    changes.push(change(
+                        SyncChangeKind::DeletedFromFile,
+                        Some(id),
+                        &task.title,
+                    ));
+                } else {
+                    match task.to_imported() {
+                        Ok(mut imported) => {
+                            // Ids from elsewhere don't mean anything here
+                            imported.source_id = None;
+                            let added = crate::formats::add_imported(store, &[imported])?;
+                            let id = crate::encode_id(added[0].id.try_into().unwrap());
+                            changes.push(change(
+                                SyncChangeKind::AddedToWorkingon,
+                                Some(&id),
+                                &added[0].title,
+                            ));
+                            seen.insert(id.clone());
+                            lines.push(Line::Todo(id));
+                        }
+                        Err(e) => {
+                            changes.push(change(SyncChangeKind::Skipped, None, &e));
+                            lines.push(Line::Verbatim(line.to_string()));
+                        }
+                    }
+                }
+            }
+
+            for id in base.keys().filter(|id| !seen.contains(*id)) {
+                if let Some(todo) = todos.get(id) {
+                    if todo.completed.is_none() {
+                        store.delete_todo(id)?;
+                        changes.push(change(
+                            SyncChangeKind::DeletedFromWorkingon,
+                            Some(id),
+                            &todo.title,
+                        ));
+                    }
+                }
+            }
+
+            // Reload, applying lines can have spawned next occurrences or changed tags
+            let context = TodoJsonContext::load_from(store)?;
+            let todos = todos_by_show_id(store)?;
+            let mut new_todos: Vec<&Todos> = todos
+                .iter()
+                .filter(|(id, todo)| {
+                    todo.completed.is_none() && !seen.contains(*id) && !base.contains_key(*id)
+                })
+                .map(|(_, todo)| todo)
+                .collect();
+            new_todos.sort_by_key(|todo| todo.id);
+            for todo in new_todos {
+                let id = crate::encode_id(todo.id.try_into().unwrap());
+                changes.push(change(SyncChangeKind::AddedToFile, Some(&id), &todo.title));
+                lines.push(Line::Todo(id));
+            }
+
+            let mut synced = HashMap::new();
+            let mut new_content = String::new();
+            for line in lines {
+                match line {
+                    Line::Verbatim(line) => new_content.push_str(&line),
+                    Line::Todo(id) => {
+                        let rendered = Task::from_todo(&context.todo_json(&todos[&id])).to_string();
+                        new_content.push_str(&rendered);
+                        synced.insert(id, rendered);
+                    }
+                }
+                new_content.push('\n');
+            }
+            Ok::<_, Error>((changes, new_content, synced))
+        })?;
+    std::fs::write(path, new_content).map_err(Error::io(path))?;
+    state.insert(state_key, synced);
+    save_sync_state(&state)?;
+    Ok(changes)
+}
+
+/// Updates a TODO to match its line, everything a todo.txt line can't hold is left alone. The
+/// inner error is why the line was skipped instead.
+fn apply_task(
+    store: &mut TodoStore,
+    show_id: &str,
+    task: &Task,
+    todo: &Todos,
+) -> Result<Result<(), String>, Error> {
+    let imported = match task.to_imported() {
+        Ok(imported) => imported,
+        Err(e) => return Ok(Err(e)),
+    };
+    let projects = store.get_projects()?;
+    let project = match &imported.project {
+        Some(name) => match projects
+            .iter()
+            .find(|project| no_spaces(&project.name) == *name)
+        {
+            Some(project) if project.archived.is_some() => {
+                return Ok(Err(format!("project {} is archived", project.name)))
+            }
+            Some(project) => Some(project.name.clone()),
+            None => Some(store.add_project(name)?.name),
+        },
+        None => None,
+    };
+    if imported.title != todo.title {
+        store.set_todo_title(show_id, &imported.title)?;
+    }
+    if task.priority_level().level() != todo.priority {
+        store.set_priority(show_id, task.priority_level())?;
+    }
+    let db_task = Task::from_todo(&crate::output::todo_json_from(store, show_id)?);
+    if task.due != db_task.due {
+        store.set_due(show_id, imported.due)?;
+    }
+    if task.projects.first() != db_task.projects.first() {
+        store.set_todo_project(show_id, project.as_deref())?;
+    }
+    let current_tags = store.get_todo_tags(show_id)?;
+    let to_remove: Vec<String> = current_tags
+        .iter()
+        .filter(|tag| !imported.tags.contains(&no_spaces(tag)))
+        .cloned()
+        .collect();
+    let to_add: Vec<String> = imported
+        .tags
+        .iter()
+        .filter(|tag| {
+            !current_tags
+                .iter()
+                .any(|current| no_spaces(current) == **tag)
+        })
+        .cloned()
+        .collect();
+    store.remove_tags(show_id, &to_remove)?;
+    store.add_tags(show_id, &to_add)?;
+    if task.rec != db_task.rec {
+        store.set_recurrence(show_id, imported.recurrence.as_ref())?;
+    }
+    match (imported.completed, todo.completed) {
+        (Some(completed), None) => {
+            store.complete_todo(show_id, Some(completed))?;
+        }
+        (None, Some(_)) => store.reopen_todo(show_id)?,
+        _ => {}
+    }
+    Ok(Ok(()))
+}
@@ -0,0 +1,139 @@
+use chrono::{DateTime, Utc};
+
+use crate::models::{Priority, Todos};
+use crate::Error;
+
+// How much each factor can contribute to the urgency of a TODO
+const DUE_OVERDUE: f64 = 12.0;
+const DUE_WITHIN_A_DAY: f64 = 9.0;
+const DUE_WITHIN_THREE_DAYS: f64 = 6.0;
+const DUE_WITHIN_A_WEEK: f64 = 3.0;
+const DUE_LATER: f64 = 1.0;
+const PRIORITY_HIGH: f64 = 6.0;
+const PRIORITY_MEDIUM: f64 = 3.9;
+const PRIORITY_LOW: f64 = 1.8;
+const AGE_MAX: f64 = 2.0;
+/// Age in days at which a TODO gets the full [`AGE_MAX`] score
+const AGE_MAX_DAYS: f64 = 90.0;
+const TAGS_MAX: f64 = 1.0;
+
+/// How close a due time is, ordered from most to least pressing
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DueBucket {
+    Overdue,
+    WithinADay,
+    WithinThreeDays,
+    WithinAWeek,
+    Later,
+    NoDueDate,
+}
+
+pub fn due_bucket(ts: Option<DateTime<Utc>>, now: DateTime<Utc>) -> DueBucket {
+    let Some(ts) = ts else {
+        return DueBucket::NoDueDate;
+    };
+    let offset = ts - now;
+    if offset.num_seconds() < 0 {
+        DueBucket::Overdue
+    } else if offset.num_seconds() < 60 * 60 * 24 {
+        // A day
+        DueBucket::WithinADay
+    } else if offset.num_seconds() < 60 * 60 * 24 * 3 {
+        // 3 days
+        DueBucket::WithinThreeDays
+    } else if offset.num_seconds() < 60 * 60 * 24 * 7 {
+        // A week
+        DueBucket::WithinAWeek
+    } else {
+        DueBucket::Later
+    }
+}
+
+/// A single reason why a TODO is urgent and how much it adds to its score
+#[derive(Debug, PartialEq)]
+pub struct UrgencyFactor {
+    pub reason: String,
+    pub score: f64,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Urgency {
+    pub factors: Vec<UrgencyFactor>,
+}
+
+impl Urgency {
+    pub fn total(&self) -> f64 {
+        self.factors
+            .iter()
+            .fold(0.0, |total, factor| total + factor.score)
+    }
+}
+
+/// Score how urgent a TODO is by combining how soon it's due, its priority, its age and how many
+/// tags it has. Factors that don't contribute are left out.
+pub fn urgency(todo: &Todos, tag_count: i64, now: DateTime<Utc>) -> Urgency {
+    let mut factors = Vec::new();
+
+    let (due_reason, due_score) = match due_bucket(todo.due, now) {
+        DueBucket::Overdue => ("overdue", DUE_OVERDUE),
+        DueBucket::WithinADay => ("due within a day", DUE_WITHIN_A_DAY),
+        DueBucket::WithinThreeDays => ("due within 3 days", DUE_WITHIN_THREE_DAYS),
+        DueBucket::WithinAWeek => ("due within a week", DUE_WITHIN_A_WEEK),
+        DueBucket::Later => ("due later", DUE_LATER),
+        DueBucket::NoDueDate => ("no due date", 0.0),
+    };
+    factors.push(UrgencyFactor {
+        reason: due_reason.to_string(),
+        score: due_score,
+    });
+
+    let priority = Priority::from_level(todo.priority);
+    let priority_score = match priority {
+        Priority::High => PRIORITY_HIGH,
+        Priority::Medium => PRIORITY_MEDIUM,
+        Priority::Low => PRIORITY_LOW,
+        Priority::Unset => 0.0,
+    };
+    factors.push(UrgencyFactor {
+        reason: format!("{} priority", priority),
+        score: priority_score,
+    });
+
+    let age_days = (now - todo.created).num_days().max(0);
+    factors.push(UrgencyFactor {
+        reason: format!("{} days old", age_days),
+        score: AGE_MAX * (age_days as f64 / AGE_MAX_DAYS).min(1.0),
+    });
+
+    // Having any tags bumps urgency a little, the more tags the closer to the maximum
+    let tags_score = match tag_count {
+        0 => 0.0,
+        1 => 0.8 * TAGS_MAX,
+        2 => 0.9 * TAGS_MAX,
+        _ => TAGS_MAX,
+    };
+    factors.push(UrgencyFactor {
+        reason: format!("{} tags", tag_count),
+        score: tags_score,
+    });
+
+    factors.retain(|factor| factor.score > 0.0);
+    Urgency { factors }
+}
+
+/// Score every TODO and order them from most to least urgent, ties keep their relative order
+pub fn sort_by_urgency(
+    todos: Vec<Todos>,
+    now: DateTime<Utc>,
+) -> Result<Vec<(Todos, Urgency)>, Error> {
+    let tag_counts = crate::count_tags_by_todo()?;
+    let mut scored: Vec<(Todos, Urgency)> = todos
+        .into_iter()
+        .map(|todo| {
+            let todo_urgency = urgency(&todo, *tag_counts.get(&todo.id).unwrap_or(&0), now);
+            (todo, todo_urgency)
+        })
+        .collect();
+    scored.sort_by(|(_, a), (_, b)| b.total().total_cmp(&a.total()));
+    Ok(scored)
+}
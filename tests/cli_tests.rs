@@ -1,3 +1,4 @@
+use assert_cmd::assert::Assert;
 use assert_cmd::Command;
 use chrono::Utc;
 use diesel::prelude::*;
@@ -7,11 +8,39 @@ use serial_test::serial;
 use tempdir::TempDir;
 use workingon::models::NewTodo;
 use workingon::schema::todos::dsl::*;
-use workingon::{encode_id, establish_connection, get_todo};
+use workingon::{encode_id, establish_connection, get_todo, TodoStore};
+
+/// A workingon command with its data in `dir`. The data dir is only given to the child process,
+/// so tests using it don't touch the environment and can run in parallel.
+fn workingon(dir: &TempDir) -> Command {
+    let mut command = Command::cargo_bin("workingon").unwrap();
+    command
+        .env("EDITOR", "-")
+        .env("WORKINGON_DATA_DIR", dir.path());
+    command
+}
+
+fn run(dir: &TempDir, args: &[&str]) -> Assert {
+    workingon(dir).args(args).assert()
+}
+
+/// The store `run` uses, to check what a command changed
+fn store(dir: &TempDir) -> TodoStore {
+    TodoStore::open_at(&dir.path().join("todos.sqlite3")).unwrap()
+}
+
+fn latest_todo(dir: &TempDir) -> Option<(String, workingon::models::Todos)> {
+    store(dir)
+        .get_todos()
+        .unwrap()
+        .into_iter()
+        .max_by_key(|todo| todo.id)
+        .map(|todo| (encode_id(todo.id.try_into().unwrap()), todo))
+}
 
 // Helper function to get the latest TODO from the database
 fn get_latest_todo() -> Option<(String, workingon::models::Todos)> {
-    let connection = &mut establish_connection();
+    let connection = &mut establish_connection().unwrap();
     let results = todos
         .select(workingon::models::Todos::as_select())
         .order_by(id.desc())
@@ -178,7 +207,8 @@ fn test_add_and_show_todo() {
         title: "First TODO",
         notes: "",
         created: Utc::now(),
-    });
+    })
+    .unwrap();
 
     // Get the TODO ID directly from the database
     let (todo_id, _todo) = get_latest_todo().expect("No todo found");
@@ -231,7 +261,8 @@ fn test_complete_and_reopen_todo() {
         title: "Complete and Reopen Test TODO",
         notes: "",
         created: Utc::now(),
-    });
+    })
+    .unwrap();
 
     // Get the TODO ID directly from the database
     let (todo_id, todo) = get_latest_todo().expect("No todo found");
@@ -252,7 +283,7 @@ fn test_complete_and_reopen_todo() {
         ));
 
     // Verify it's completed by checking the database
-    let connection = &mut workingon::establish_connection();
+    let connection = &mut workingon::establish_connection().unwrap();
     let completed_results = todos
         .select(workingon::models::Todos::as_select())
         .filter(id.eq(todo.id))
@@ -302,16 +333,18 @@ fn test_list_flags_precedence() {
         title: "Open TODO",
         notes: "",
         created: Utc::now(),
-    });
+    })
+    .unwrap();
 
     // Add a completed TODO
     workingon::add_todo(&NewTodo {
         title: "Completed TODO",
         notes: "",
         created: Utc::now(),
-    });
+    })
+    .unwrap();
     let (completed_todo_id, _) = get_latest_todo().expect("No todo found");
-    workingon::complete_todo(&completed_todo_id, None);
+    workingon::complete_todo(&completed_todo_id, None).unwrap();
 
     // Test 1: Default (no flags) should show only open TODOs
     Command::cargo_bin("workingon")
@@ -437,7 +470,8 @@ fn test_set_duetime() {
         title: "test_set_duetime",
         notes: "",
         created: Utc::now(),
-    });
+    })
+    .unwrap();
     // Set same due date as in help message to make sure the example works
     Command::cargo_bin("workingon")
         .unwrap()
@@ -451,7 +485,7 @@ fn test_set_duetime() {
         .assert()
         .success()
         .stdout(predicate::str::contains("is due at:"));
-    let updated_todo = get_todo(&crate::encode_id(created_todo.id.try_into().unwrap()));
+    let updated_todo = get_todo(&crate::encode_id(created_todo.id.try_into().unwrap())).unwrap();
     // Make sure command changed database
     assert!(updated_todo.due.is_some());
     // Set no due date
@@ -466,6 +500,1090 @@ fn test_set_duetime() {
         .assert()
         .success()
         .stdout(predicate::str::contains("is due at: no set time"));
-    let updated_todo = get_todo(&crate::encode_id(created_todo.id.try_into().unwrap()));
+    let updated_todo = get_todo(&crate::encode_id(created_todo.id.try_into().unwrap())).unwrap();
     assert!(updated_todo.due.is_none());
 }
+
+#[test]
+fn test_tags() {
+    let tmp_dir = TempDir::new("workingon_test").expect("cannot make temp directory for test");
+
+    run(
+        &tmp_dir,
+        &["add", "Tagged TODO", "--tag", "infra", "--tag", "oncall"],
+    )
+    .success();
+    let (todo_id, _) = latest_todo(&tmp_dir).expect("No todo found");
+    run(&tmp_dir, &["add", "Untagged TODO"]).success();
+
+    run(&tmp_dir, &["list", "--tag", "infra"])
+        .success()
+        .stdout(predicate::str::contains("Tagged TODO"))
+        .stdout(predicate::str::contains("Untagged TODO").not());
+
+    // Tags prefixed with - must not be mistaken for flags
+    run(&tmp_dir, &["tag", &todo_id, "+work", "-oncall"])
+        .success()
+        .stdout(predicate::str::contains("is tagged with: infra, work"));
+
+    run(&tmp_dir, &["show", &todo_id])
+        .success()
+        .stdout(predicate::str::contains("It's tagged with: infra, work"));
+}
+
+#[test]
+fn test_projects_grouped_list() {
+    let tmp_dir = TempDir::new("workingon_test").expect("cannot make temp directory for test");
+
+    run(&tmp_dir, &["project", "add", "infra"]).success();
+    run(&tmp_dir, &["add", "Project TODO", "--project", "infra"]).success();
+    run(&tmp_dir, &["add", "Loose TODO"]).success();
+
+    run(&tmp_dir, &["list"])
+        .success()
+        .stdout(predicate::str::is_match("(?s)infra \\(1 open\\).*Project TODO").unwrap())
+        .stdout(predicate::str::is_match("(?s)no project \\(1 open\\).*Loose TODO").unwrap());
+
+    run(&tmp_dir, &["project", "rename", "infra", "platform"]).success();
+    run(&tmp_dir, &["project", "archive", "platform"]).success();
+    run(&tmp_dir, &["project", "list"])
+        .success()
+        .stdout(predicate::str::contains("platform").not());
+    run(&tmp_dir, &["project", "list", "--all"])
+        .success()
+        .stdout(predicate::str::contains("platform"));
+
+    // Archived projects can't receive new TODOs
+    run(&tmp_dir, &["add", "Too late", "--project", "platform"]).failure();
+}
+
+#[test]
+fn test_priority_sort() {
+    let tmp_dir = TempDir::new("workingon_test").expect("cannot make temp directory for test");
+
+    run(&tmp_dir, &["add", "Unimportant TODO"]).success();
+    run(&tmp_dir, &["add", "Urgent TODO", "--priority", "high"]).success();
+    run(
+        &tmp_dir,
+        &["add", "Due TODO", "--due", "tomorrow", "--priority", "low"],
+    )
+    .success();
+
+    // Due dates come first, then within TODOs without due dates higher priorities go first
+    run(&tmp_dir, &["list"])
+        .success()
+        .stdout(predicate::str::is_match("(?s)Due TODO.*Urgent TODO.*Unimportant TODO").unwrap());
+
+    run(&tmp_dir, &["add", "Bumped TODO"]).success();
+    let stdout = run(&tmp_dir, &["list"])
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let bumped_id = String::from_utf8(stdout)
+        .unwrap()
+        .lines()
+        .find(|line| line.contains("Bumped TODO"))
+        .and_then(|line| line.split_whitespace().next())
+        .unwrap()
+        .to_string();
+    run(&tmp_dir, &["priority", &bumped_id, "medium"])
+        .success()
+        .stdout(predicate::str::contains("has priority:"));
+    run(&tmp_dir, &["list"]).success().stdout(
+        predicate::str::is_match("(?s)Urgent TODO.*Bumped TODO.*Unimportant TODO").unwrap(),
+    );
+}
+
+#[test]
+fn test_next() {
+    let tmp_dir = TempDir::new("workingon_test").expect("cannot make temp directory for test");
+
+    run(&tmp_dir, &["next"])
+        .success()
+        .stdout(predicate::str::contains("There's nothing to do currently"));
+    run(&tmp_dir, &["add", "Someday TODO"]).success();
+    run(&tmp_dir, &["add", "Important TODO", "--priority", "high"]).success();
+    run(
+        &tmp_dir,
+        &["add", "Done TODO", "--priority", "high", "--complete"],
+    )
+    .success();
+
+    run(&tmp_dir, &["next"])
+        .success()
+        .stdout(predicate::str::contains("Important TODO (urgency 6.0)"))
+        .stdout(predicate::str::contains("+6.0 high priority"))
+        .stdout(predicate::str::contains("Someday TODO").not());
+    run(&tmp_dir, &["next", "-n", "3"])
+        .success()
+        .stdout(predicate::str::is_match("(?s)Important TODO.*Someday TODO").unwrap())
+        .stdout(predicate::str::contains("Done TODO").not());
+    run(&tmp_dir, &["list", "--sort", "urgency"])
+        .success()
+        .stdout(predicate::str::is_match("(?s)Important TODO.*Someday TODO").unwrap());
+}
+
+#[test]
+fn test_recurring_todo() {
+    let tmp_dir = TempDir::new("workingon_test").expect("cannot make temp directory for test");
+
+    run(
+        &tmp_dir,
+        &["add", "Expenses", "--every", "month", "--complete"],
+    )
+    .success()
+    .stdout(predicate::str::contains(
+        "created and was subsequently completed",
+    ))
+    .stdout(predicate::str::contains("is the next occurrence, due:"));
+    let stdout = run(&tmp_dir, &["list"])
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let next_id = String::from_utf8(stdout)
+        .unwrap()
+        .lines()
+        .find(|line| line.contains("Expenses"))
+        .and_then(|line| line.split_whitespace().next())
+        .unwrap()
+        .to_string();
+    run(&tmp_dir, &["show", &next_id])
+        .success()
+        .stdout(predicate::str::contains("It repeats: every month"))
+        .stdout(predicate::str::contains("It's an occurrence of:"));
+
+    run(&tmp_dir, &["recur", &next_id, "every 2 weeks"])
+        .success()
+        .stdout(predicate::str::contains("repeats: every 2 weeks"));
+    run(&tmp_dir, &["recur", &next_id])
+        .success()
+        .stdout(predicate::str::contains("repeats: never"));
+    run(&tmp_dir, &["complete", &next_id])
+        .success()
+        .stdout(predicate::str::contains("is the next occurrence").not());
+}
+
+#[test]
+fn test_subtasks() {
+    let tmp_dir = TempDir::new("workingon_test").expect("cannot make temp directory for test");
+
+    run(&tmp_dir, &["add", "Parent TODO"]).success();
+    let (parent_todo_id, _) = latest_todo(&tmp_dir).expect("No todo found");
+    run(&tmp_dir, &["add", "Other TODO"]).success();
+    run(
+        &tmp_dir,
+        &["add", "First subtask", "--parent", &parent_todo_id],
+    )
+    .success();
+    let (subtask_id, _) = latest_todo(&tmp_dir).expect("No todo found");
+    run(
+        &tmp_dir,
+        &["add", "Second subtask", "--parent", &parent_todo_id],
+    )
+    .success();
+    run(&tmp_dir, &["complete", &subtask_id]).success();
+
+    // Subtasks are indented right below their parent, which shows the progress
+    run(&tmp_dir, &["list", "--all"]).success().stdout(
+        predicate::str::is_match(
+            "(?s)Parent TODO \\[1/2\\].*└ First subtask.*└ Second subtask.*Other TODO",
+        )
+        .unwrap(),
+    );
+
+    run(&tmp_dir, &["complete", &parent_todo_id])
+        .code(1)
+        .stderr(predicate::str::contains("still has 1 open subtasks"));
+    assert!(store(&tmp_dir)
+        .get_todo(&parent_todo_id)
+        .unwrap()
+        .completed
+        .is_none());
+    run(&tmp_dir, &["complete", "--force", &parent_todo_id])
+        .success()
+        .stdout(predicate::str::contains("completed"));
+}
+
+#[test]
+fn test_block_and_unblock() {
+    let tmp_dir = TempDir::new("workingon_test").expect("cannot make temp directory for test");
+
+    run(&tmp_dir, &["add", "Blocker TODO"]).success();
+    let (blocker_id, _) = latest_todo(&tmp_dir).expect("No todo found");
+    run(&tmp_dir, &["add", "Blocked TODO"]).success();
+    let (blocked_id, _) = latest_todo(&tmp_dir).expect("No todo found");
+
+    run(&tmp_dir, &["block", &blocked_id, "--on", &blocker_id])
+        .success()
+        .stdout(predicate::str::contains("is blocked by"));
+    run(&tmp_dir, &["block", &blocker_id, "--on", &blocked_id]).failure();
+    run(&tmp_dir, &["show", &blocked_id])
+        .success()
+        .stdout(predicate::str::contains("It's blocked by:"))
+        .stdout(predicate::str::contains("Blocker TODO"));
+    run(&tmp_dir, &["complete", &blocker_id])
+        .success()
+        .stdout(predicate::str::contains(format!(
+            "{} is now unblocked: Blocked TODO",
+            blocked_id
+        )));
+    // Completing it again doesn't unblock anything
+    run(&tmp_dir, &["complete", &blocker_id])
+        .success()
+        .stdout(predicate::str::contains("is now unblocked").not());
+
+    // A TODO blocked by several that are completed at once is listed once
+    run(&tmp_dir, &["reopen", &blocker_id]).success();
+    run(&tmp_dir, &["add", "Second blocker"]).success();
+    let (second_blocker_id, _) = latest_todo(&tmp_dir).expect("No todo found");
+    run(
+        &tmp_dir,
+        &["block", &blocked_id, "--on", &second_blocker_id],
+    )
+    .success();
+    run(&tmp_dir, &["complete", &blocker_id, &second_blocker_id])
+        .success()
+        .stdout(predicate::str::contains("is now unblocked").count(1));
+    run(&tmp_dir, &["reopen", &second_blocker_id]).success();
+    run(
+        &tmp_dir,
+        &["unblock", &blocked_id, "--on", &second_blocker_id],
+    )
+    .success();
+
+    run(&tmp_dir, &["reopen", &blocker_id]).success();
+    run(&tmp_dir, &["unblock", &blocked_id, "--on", &blocker_id])
+        .success()
+        .stdout(predicate::str::contains("is no longer blocked by"));
+    run(&tmp_dir, &["show", &blocked_id])
+        .success()
+        .stdout(predicate::str::contains("It's blocked by:").not());
+    run(&tmp_dir, &["unblock", &blocked_id, "--on", &blocker_id])
+        .code(3)
+        .stderr(predicate::str::contains("There's no dependency of"));
+}
+
+#[test]
+fn test_start_stop_current() {
+    let tmp_dir = TempDir::new("workingon_test").expect("cannot make temp directory for test");
+
+    run(&tmp_dir, &["add", "First TODO"]).success();
+    let (first_id, _) = latest_todo(&tmp_dir).expect("No todo found");
+    run(&tmp_dir, &["add", "Second TODO"]).success();
+    let (second_id, _) = latest_todo(&tmp_dir).expect("No todo found");
+
+    run(&tmp_dir, &["current"])
+        .success()
+        .stdout(predicate::str::contains("Nothing is being worked on"));
+    run(&tmp_dir, &["start", &first_id])
+        .success()
+        .stdout(predicate::str::contains(format!(
+            "Started working on {} First TODO",
+            first_id
+        )));
+    run(&tmp_dir, &["start", &first_id])
+        .success()
+        .stdout(predicate::str::contains("Already working on"));
+    run(&tmp_dir, &["start", &second_id])
+        .success()
+        .stdout(predicate::str::contains(format!(
+            "Stopped working on {} after",
+            first_id
+        )))
+        .stdout(predicate::str::contains("Started working on"));
+    run(&tmp_dir, &["current"])
+        .success()
+        .stdout(predicate::str::contains(format!(
+            "Working on {} Second TODO for",
+            second_id
+        )));
+    run(&tmp_dir, &["stop"])
+        .success()
+        .stdout(predicate::str::contains("Stopped working on"));
+    run(&tmp_dir, &["stop"])
+        .success()
+        .stdout(predicate::str::contains("Nothing is being worked on"));
+
+    run(&tmp_dir, &["list", "--time"])
+        .success()
+        .stdout(predicate::str::contains("tracked"));
+    run(&tmp_dir, &["show", &first_id])
+        .success()
+        .stdout(predicate::str::contains("It was worked on for:"));
+}
+
+#[test]
+fn test_report() {
+    let tmp_dir = TempDir::new("workingon_test").expect("cannot make temp directory for test");
+
+    run(&tmp_dir, &["report"])
+        .success()
+        .stdout(predicate::str::contains("No time was tracked"));
+
+    run(&tmp_dir, &["add", "Billable TODO", "--tag", "acme"]).success();
+    let (first_id, _) = latest_todo(&tmp_dir).expect("No todo found");
+    run(&tmp_dir, &["start", &first_id]).success();
+    run(&tmp_dir, &["stop"]).success();
+
+    run(&tmp_dir, &["report", "--since", "today"])
+        .success()
+        .stdout(predicate::str::contains("day"))
+        .stdout(predicate::str::contains("total"));
+    run(&tmp_dir, &["report", "--by", "todo"])
+        .success()
+        .stdout(predicate::str::contains(&first_id))
+        .stdout(predicate::str::contains("Billable TODO"));
+    run(&tmp_dir, &["report", "--by", "tag", "--format", "csv"])
+        .success()
+        .stdout(predicate::str::starts_with(
+            "tag,title,hours,seconds\nacme,,0.00,",
+        ));
+    run(&tmp_dir, &["report", "--by", "project", "--format", "json"])
+        .success()
+        .stdout(predicate::str::contains("\"key\":\"no project\""))
+        .stdout(predicate::str::contains("\"total\":"));
+    run(
+        &tmp_dir,
+        &["report", "--since", "today", "--until", "yesterday"],
+    )
+    .code(2)
+    .stderr(predicate::str::contains("before it starts"));
+}
+
+#[test]
+fn test_estimate_and_accuracy_report() {
+    let tmp_dir = TempDir::new("workingon_test").expect("cannot make temp directory for test");
+
+    run(&tmp_dir, &["add", "Estimated TODO", "--estimate", "1h30m"]).success();
+    let (first_id, first_todo) = latest_todo(&tmp_dir).expect("No todo found");
+    assert_eq!(first_todo.estimate, Some(90));
+    run(&tmp_dir, &["list"])
+        .success()
+        .stdout(predicate::str::contains("estimate"))
+        .stdout(predicate::str::contains("1h 30m"));
+    run(&tmp_dir, &["estimate", &first_id, "2h"])
+        .success()
+        .stdout(predicate::str::contains("is estimated at: 2h 00m"));
+    run(&tmp_dir, &["estimate", &first_id, "soon"])
+        .failure()
+        .stderr(predicate::str::contains("unknown duration"));
+
+    run(&tmp_dir, &["report", "accuracy"])
+        .success()
+        .stdout(predicate::str::contains("No completed TODOs"));
+    run(&tmp_dir, &["start", &first_id]).success();
+    run(&tmp_dir, &["stop"]).success();
+    run(&tmp_dir, &["show", &first_id])
+        .success()
+        .stdout(predicate::str::contains("It's estimated at: 2h 00m"))
+        .stdout(predicate::str::contains("of 2h 00m estimated (100% under)"));
+    run(&tmp_dir, &["complete", &first_id]).success();
+    run(&tmp_dir, &["report", "accuracy"])
+        .success()
+        .stdout(predicate::str::contains("Estimated TODO"))
+        .stdout(predicate::str::contains("off by 100% on average"));
+    run(&tmp_dir, &["report", "accuracy", "--format", "csv"])
+        .success()
+        .stdout(predicate::str::contains(
+            "id,title,estimate_hours,actual_hours,error_percent",
+        ))
+        .stdout(predicate::str::contains(",2.0,0.0,-100"));
+
+    run(&tmp_dir, &["estimate", &first_id])
+        .success()
+        .stdout(predicate::str::contains("no estimate"));
+}
+
+#[test]
+fn test_json_output() {
+    let tmp_dir = TempDir::new("workingon_test").expect("cannot make temp directory for test");
+
+    let json = |args: &[&str]| -> serde_json::Value {
+        let output = run(&tmp_dir, args).success().get_output().stdout.clone();
+        serde_json::from_slice(&output).expect("Output isn't JSON")
+    };
+
+    let added = json(&["--format", "json", "add", "JSON TODO", "--tag", "api"]);
+    let (first_id, first_todo) = latest_todo(&tmp_dir).expect("No todo found");
+    assert_eq!(added["schema_version"], workingon::output::SCHEMA_VERSION);
+    assert_eq!(added["id"], first_id);
+    assert_eq!(added["numeric_id"], first_todo.id);
+    assert_eq!(added["title"], "JSON TODO");
+    assert_eq!(added["tags"], serde_json::json!(["api"]));
+    assert_eq!(added["due"], serde_json::Value::Null);
+    assert_eq!(added["priority"], "none");
+
+    json(&["due", &first_id, "tomorrow", "--format", "json"]);
+    let shown = json(&["show", &first_id, "--format", "json"]);
+    let due_str = shown["due"].as_str().expect("due isn't set");
+    assert!(chrono::DateTime::parse_from_rfc3339(due_str).is_ok());
+
+    let listed = json(&["list", "--format", "json"]);
+    assert_eq!(listed["schema_version"], workingon::output::SCHEMA_VERSION);
+    assert_eq!(listed["todos"][0]["id"], first_id);
+
+    run(&tmp_dir, &["add", "Second JSON TODO"]).success();
+    let output = run(&tmp_dir, &["list", "--format", "jsonl"])
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let lines: Vec<serde_json::Value> = String::from_utf8(output)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).expect("Line isn't JSON"))
+        .collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines
+        .iter()
+        .all(|line| line["schema_version"] == workingon::output::SCHEMA_VERSION));
+
+    let completed_todo = json(&["complete", &first_id, "--format", "json"]);
+    assert!(completed_todo["completed"].is_string());
+    assert_eq!(completed_todo["next_occurrence"], serde_json::Value::Null);
+
+    let current = json(&["current", "--format", "json"]);
+    assert_eq!(current["session"], serde_json::Value::Null);
+
+    let deleted = json(&["delete", &first_id, "--format", "json"]);
+    assert_eq!(deleted["deleted"], true);
+
+    run(&tmp_dir, &["list", "--format", "csv"])
+        .failure()
+        .stderr(predicate::str::contains(
+            "invalid value 'csv' for '--format",
+        ));
+}
+
+#[test]
+fn test_csv_export_and_import() {
+    let tmp_dir = TempDir::new("workingon_test").expect("cannot make temp directory for test");
+
+    run(&tmp_dir, &["add", "Open TODO", "--tag", "sheet"]).success();
+    run(&tmp_dir, &["add", "Done TODO", "--complete"]).success();
+    run(&tmp_dir, &["export"])
+        .failure()
+        .stderr(predicate::str::contains("--format <FORMAT>"));
+    run(&tmp_dir, &["export", "--format", "csv"])
+        .success()
+        .stdout(predicate::str::starts_with("id,title,notes,created"))
+        .stdout(predicate::str::contains("Open TODO"))
+        .stdout(predicate::str::contains("Done TODO").not());
+    let output = run(&tmp_dir, &["export", "--format", "csv", "--all"])
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    let csv_path = tmp_dir.path().join("todos.csv");
+    let mut csv_content = String::from_utf8(output).unwrap();
+    csv_content.push_str("xyz,,no title,,,,,,,,,,\n");
+    std::fs::write(&csv_path, csv_content).unwrap();
+
+    run(
+        &tmp_dir,
+        &["import", csv_path.to_str().unwrap(), "--dry-run"],
+    )
+    .code(1)
+    .stdout(predicate::str::contains("2 TODOs would be imported"))
+    .stderr(predicate::str::contains("Line 4 couldn't be imported"));
+    run(&tmp_dir, &["list", "--all"])
+        .success()
+        .stdout(predicate::str::contains("Open TODO").count(1));
+
+    run(&tmp_dir, &["import", csv_path.to_str().unwrap()])
+        .failure()
+        .stdout(predicate::str::contains("2 TODOs imported"));
+    run(&tmp_dir, &["list", "--all"])
+        .success()
+        .stdout(predicate::str::contains("Open TODO").count(2))
+        .stdout(predicate::str::contains("Done TODO").count(2));
+}
+
+#[test]
+fn test_todotxt_export_import_and_sync() {
+    let tmp_dir = TempDir::new("workingon_test").expect("cannot make temp directory for test");
+
+    run(
+        &tmp_dir,
+        &["add", "Water plants", "--tag", "home", "--priority", "high"],
+    )
+    .success();
+    run(&tmp_dir, &["export", "--format", "todotxt"])
+        .success()
+        .stdout(
+            predicate::str::is_match(r"^\(A\) \d{4}-\d{2}-\d{2} Water plants @home id:\w+\n$")
+                .unwrap(),
+        );
+    run(&tmp_dir, &["list", "--format", "todotxt"])
+        .failure()
+        .stderr(predicate::str::contains(
+            "invalid value 'todotxt' for '--format",
+        ));
+
+    let txt_path = tmp_dir.path().join("todo.txt");
+    std::fs::write(
+        &txt_path,
+        "(C) Buy milk +errands due:2026-10-25\nx Done already\n",
+    )
+    .unwrap();
+    run(&tmp_dir, &["import", txt_path.to_str().unwrap()])
+        .success()
+        .stdout(predicate::str::contains("2 TODOs imported"));
+    run(&tmp_dir, &["list"])
+        .success()
+        .stdout(predicate::str::contains("Buy milk"))
+        .stdout(predicate::str::contains("errands"));
+
+    let sync_path = tmp_dir.path().join("sync.txt");
+    std::fs::write(&sync_path, "Added in the file\n").unwrap();
+    run(&tmp_dir, &["sync-todotxt", sync_path.to_str().unwrap()])
+        .success()
+        .stdout(predicate::str::contains("added: Added in the file"))
+        .stdout(predicate::str::contains(
+            "written to the file: Water plants",
+        ));
+    run(&tmp_dir, &["sync-todotxt", sync_path.to_str().unwrap()])
+        .success()
+        .stdout(predicate::str::contains("is already in sync"));
+    // A sync is undone at once
+    run(&tmp_dir, &["undo"])
+        .success()
+        .stdout(predicate::str::contains("Undid: sync"));
+    run(&tmp_dir, &["list"])
+        .success()
+        .stdout(predicate::str::contains("Added in the file").not());
+
+    std::fs::write(tmp_dir.path().join("todotxt-sync.json"), "not json").unwrap();
+    run(&tmp_dir, &["sync-todotxt", sync_path.to_str().unwrap()])
+        .code(8)
+        .stderr(predicate::str::contains("todotxt-sync.json"));
+}
+
+#[test]
+fn test_ics_export_and_import() {
+    let tmp_dir = TempDir::new("workingon_test").expect("cannot make temp directory for test");
+
+    run(&tmp_dir, &["add", "Renew passport", "--due", "tomorrow"]).success();
+    run(&tmp_dir, &["add", "Book flights", "--complete"]).success();
+    let output = run(&tmp_dir, &["export", "--format", "ics", "--all"])
+        .success()
+        .stdout(predicate::str::contains("SUMMARY:Renew passport"))
+        .stdout(predicate::str::contains("STATUS:COMPLETED"))
+        .get_output()
+        .stdout
+        .clone();
+    run(&tmp_dir, &["report", "--format", "ics"])
+        .failure()
+        .stderr(predicate::str::contains(
+            "invalid value 'ics' for '--format",
+        ));
+
+    let ics_path = tmp_dir.path().join("todos.ics");
+    std::fs::write(&ics_path, output).unwrap();
+    run(&tmp_dir, &["import", ics_path.to_str().unwrap()])
+        .success()
+        .stdout(predicate::str::contains("2 TODOs imported"));
+    run(&tmp_dir, &["list", "--all"])
+        .success()
+        .stdout(predicate::str::contains("Renew passport").count(2))
+        .stdout(predicate::str::contains("Book flights").count(2));
+}
+
+#[test]
+fn test_taskwarrior_import() {
+    let tmp_dir = TempDir::new("workingon_test").expect("cannot make temp directory for test");
+
+    let export_path = tmp_dir.path().join("export.json");
+    std::fs::write(
+        &export_path,
+        "[\n\
+         {\"description\":\"Migrated task\",\"status\":\"pending\",\"tags\":[\"tw\"],\"uuid\":\"a1\"},\n\
+         {\"description\":\"Deleted task\",\"status\":\"deleted\",\"uuid\":\"b2\"},\n\
+         {\"description\":\"Finished task\",\"status\":\"completed\",\"end\":\"20261001T100000Z\",\"uuid\":\"c3\"}\n\
+         ]\n",
+    )
+    .unwrap();
+
+    run(&tmp_dir, &["import", export_path.to_str().unwrap()])
+        .failure()
+        .stderr(predicate::str::contains("--from taskwarrior"));
+    run(
+        &tmp_dir,
+        &[
+            "import",
+            "--from",
+            "taskwarrior",
+            "--dry-run",
+            export_path.to_str().unwrap(),
+        ],
+    )
+    .success()
+    .stdout(predicate::str::contains(
+        "2 TODOs would be imported, 1 skipped",
+    ));
+    run(
+        &tmp_dir,
+        &[
+            "import",
+            "--from",
+            "taskwarrior",
+            export_path.to_str().unwrap(),
+        ],
+    )
+    .success()
+    .stdout(predicate::str::contains("2 TODOs imported, 1 skipped"));
+    run(&tmp_dir, &["list", "--all"])
+        .success()
+        .stdout(predicate::str::contains("Migrated task"))
+        .stdout(predicate::str::contains("Finished task"))
+        .stdout(predicate::str::contains("Deleted task").not());
+}
+
+#[test]
+fn test_markdown_and_org_export() {
+    let tmp_dir = TempDir::new("workingon_test").expect("cannot make temp directory for test");
+
+    run(&tmp_dir, &["add", "Open TODO"]).success();
+    run(&tmp_dir, &["add", "Done TODO", "--complete"]).success();
+    run(&tmp_dir, &["export", "--format", "markdown", "--all"])
+        .success()
+        .stdout("- [ ] Open TODO\n- [x] Done TODO\n");
+    run(&tmp_dir, &["export", "--format", "org"])
+        .success()
+        .stdout("* Open\n** TODO Open TODO\n");
+    run(&tmp_dir, &["list", "--format", "markdown"])
+        .failure()
+        .stderr(predicate::str::contains(
+            "invalid value 'markdown' for '--format",
+        ));
+    run(&tmp_dir, &["import", "todos.md", "--format", "markdown"])
+        .failure()
+        .stderr(predicate::str::contains(
+            "invalid value 'markdown' for '--format",
+        ));
+    run(&tmp_dir, &["import", "todos.md"])
+        .code(2)
+        .stderr(predicate::str::contains("import needs a --format"));
+}
+
+#[test]
+fn test_search() {
+    let tmp_dir = TempDir::new("workingon_test").expect("cannot make temp directory for test");
+
+    run(&tmp_dir, &["add", "Deploy the release"]).success();
+    run(&tmp_dir, &["add", "Write release notes"]).success();
+    run(&tmp_dir, &["add", "Release party", "--complete"]).success();
+
+    run(&tmp_dir, &["search", "release"])
+        .success()
+        .stdout(predicate::str::contains("Deploy the release"))
+        .stdout(predicate::str::contains("Write release notes"))
+        .stdout(predicate::str::contains("Release party").not());
+    run(&tmp_dir, &["search", "\"release notes\"", "--all"])
+        .success()
+        .stdout(predicate::str::contains("Write release notes"))
+        .stdout(predicate::str::contains("Deploy").not());
+    run(&tmp_dir, &["search", "part*", "--completed"])
+        .success()
+        .stdout(predicate::str::contains("Release party"));
+    run(&tmp_dir, &["search", "nothing"])
+        .success()
+        .stdout(predicate::str::contains("No TODOs match \"nothing\""));
+    run(&tmp_dir, &["search", "deploy", "--format", "json"])
+        .success()
+        .stdout(predicate::str::contains("\"title\":\"Deploy the release\""))
+        .stdout(predicate::str::contains("\"snippet\":null"));
+}
+
+#[test]
+fn test_list_where() {
+    let tmp_dir = TempDir::new("workingon_test").expect("cannot make temp directory for test");
+
+    run(
+        &tmp_dir,
+        &["add", "Team meeting", "--due", "tomorrow", "--tag", "work"],
+    )
+    .success();
+    run(
+        &tmp_dir,
+        &["add", "Fix bug", "--due", "tomorrow", "--tag", "work"],
+    )
+    .success();
+    run(
+        &tmp_dir,
+        &["add", "Old work", "--tag", "work", "--complete"],
+    )
+    .success();
+
+    run(
+        &tmp_dir,
+        &[
+            "list",
+            "--where",
+            "due<\"3 days\" and tag:work and not title~\"meeting\"",
+        ],
+    )
+    .success()
+    .stdout(predicate::str::contains("Fix bug"))
+    .stdout(predicate::str::contains("Team meeting").not());
+    run(
+        &tmp_dir,
+        &["list", "--all", "--where", "tag:work and due=none"],
+    )
+    .success()
+    .stdout(predicate::str::contains("Old work"))
+    .stdout(predicate::str::contains("Fix bug").not());
+    run(&tmp_dir, &["list", "--where", "tag:home"])
+        .success()
+        .stdout(predicate::str::contains("No TODOs match the filter"));
+    run(&tmp_dir, &["list", "--where", "color:red"])
+        .code(2)
+        .stderr(predicate::str::contains("unknown field color"));
+}
+
+#[test]
+fn test_views() {
+    let tmp_dir = TempDir::new("workingon_test").expect("cannot make temp directory for test");
+
+    run(
+        &tmp_dir,
+        &["add", "Fix bug", "--due", "today", "--tag", "work"],
+    )
+    .success();
+    run(
+        &tmp_dir,
+        &[
+            "add",
+            "Plan offsite",
+            "--due",
+            "2026-12-01",
+            "--tag",
+            "work",
+        ],
+    )
+    .success();
+    run(
+        &tmp_dir,
+        &["add", "Old work", "--tag", "work", "--complete"],
+    )
+    .success();
+
+    run(
+        &tmp_dir,
+        &[
+            "view",
+            "save",
+            "soon",
+            "--where",
+            "due<\"3 days\"",
+            "--sort",
+            "urgency",
+        ],
+    )
+    .success()
+    .stdout(predicate::str::contains("soon saved"));
+    run(
+        &tmp_dir,
+        &["view", "save", "work", "--tag", "work", "--all"],
+    )
+    .success();
+    run(&tmp_dir, &["view", "save", "add"])
+        .failure()
+        .stderr(predicate::str::contains("add is a command"));
+    run(&tmp_dir, &["view", "ls"])
+        .success()
+        .stdout(predicate::str::contains("due<\"3 days\""))
+        .stdout(predicate::str::contains("urgency"));
+
+    run(&tmp_dir, &["list", "soon"])
+        .success()
+        .stdout(predicate::str::contains("Fix bug"))
+        .stdout(predicate::str::contains("Plan offsite").not());
+    run(&tmp_dir, &["soon"])
+        .success()
+        .stdout(predicate::str::contains("Fix bug"))
+        .stdout(predicate::str::contains("Plan offsite").not());
+    run(&tmp_dir, &["work"])
+        .success()
+        .stdout(predicate::str::contains("Old work"));
+    // Flags are added to the view
+    run(&tmp_dir, &["work", "--open", "--where", "title~offsite"])
+        .success()
+        .stdout(predicate::str::contains("Plan offsite"))
+        .stdout(predicate::str::contains("Fix bug").not())
+        .stdout(predicate::str::contains("Old work").not());
+
+    run(&tmp_dir, &["list", "nope"])
+        .failure()
+        .stderr(predicate::str::contains("There's no view named nope"));
+    run(&tmp_dir, &["nope"])
+        .code(2)
+        .stderr(predicate::str::contains("unrecognized subcommand 'nope'"));
+    // Flags a view can't take are reported like any other bad argument
+    run(&tmp_dir, &["work", "--bogus"])
+        .code(2)
+        .stderr(predicate::str::contains("unexpected argument '--bogus'"));
+    run(&tmp_dir, &["work", "--help"])
+        .success()
+        .stdout(predicate::str::contains("Usage:"));
+    run(&tmp_dir, &["view", "rm", "work"]).success();
+    run(&tmp_dir, &["work"]).failure();
+}
+
+#[test]
+fn test_list_columns() {
+    let tmp_dir = TempDir::new("workingon_test").expect("cannot make temp directory for test");
+
+    let csv_path = tmp_dir.path().join("todos.csv");
+    std::fs::write(
+        &csv_path,
+        "title,notes\n\
+         Call Sam,\"Ask about the budget\nand the timeline\"\n\
+         A title long enough that it has to be cut short when the table isn't wide,\n",
+    )
+    .unwrap();
+    run(&tmp_dir, &["import", csv_path.to_str().unwrap()]).success();
+    run(&tmp_dir, &["add", "Ship it", "--tag", "work", "--complete"]).success();
+
+    run(&tmp_dir, &["list"])
+        .success()
+        .stdout(predicate::str::contains(
+            "A title long enough that it has to be cut short w…",
+        ))
+        .stdout(predicate::str::contains("notes").not())
+        .stdout(predicate::str::contains("completed").not());
+    run(&tmp_dir, &["list", "--wide"])
+        .success()
+        .stdout(predicate::str::contains("when the table isn't wide"))
+        .stdout(predicate::str::contains("Ask about the budget"))
+        .stdout(predicate::str::contains("and the timeline").not());
+    run(&tmp_dir, &["list", "--columns", "id,title,notes"])
+        .success()
+        .stdout(predicate::str::is_match(r"(?m)^ id +title +notes +$").unwrap())
+        .stdout(predicate::str::contains("Ask about the budget"));
+    run(&tmp_dir, &["list", "--columns", "id,owner"]).failure();
+    // Completed TODOs show when they were completed
+    run(&tmp_dir, &["list", "--completed"])
+        .success()
+        .stdout(predicate::str::is_match(r"(?m)^ id +created +due +completed ").unwrap());
+    run(
+        &tmp_dir,
+        &["list", "--completed", "--columns", "title,tags"],
+    )
+    .success()
+    .stdout(predicate::str::is_match(r"(?m)^ Ship it +work +$").unwrap());
+
+    // The config sets the default columns, views and flags take precedence
+    run(&tmp_dir, &["config", "columns"])
+        .success()
+        .stdout(predicate::str::contains("isn't set"));
+    run(&tmp_dir, &["config", "columns", "id,nope"])
+        .failure()
+        .stderr(predicate::str::contains("unknown column \"nope\""));
+    run(&tmp_dir, &["config", "columns", "id, title"])
+        .success()
+        .stdout(predicate::str::contains("columns set to id,title"));
+    run(&tmp_dir, &["list"])
+        .success()
+        .stdout(predicate::str::is_match(r"(?m)^ id +title +$").unwrap());
+    run(&tmp_dir, &["view", "save", "brief", "--columns", "title"]).success();
+    run(&tmp_dir, &["brief"])
+        .success()
+        .stdout(predicate::str::is_match(r"(?m)^ title +$").unwrap());
+    run(&tmp_dir, &["brief", "--columns", "id,due"])
+        .success()
+        .stdout(predicate::str::is_match(r"(?m)^ id +due +$").unwrap());
+    run(&tmp_dir, &["config", "columns", "--unset"]).success();
+    run(&tmp_dir, &["list"])
+        .success()
+        .stdout(predicate::str::contains("priority"));
+}
+
+#[test]
+fn test_error_exit_codes() {
+    let tmp_dir = TempDir::new("workingon_test").expect("cannot make temp directory for test");
+
+    run(&tmp_dir, &["show", "zzzzz"])
+        .code(3)
+        .stderr(predicate::str::contains("There's no TODO with id zzzzz"))
+        .stderr(predicate::str::contains("panicked").not());
+    run(&tmp_dir, &["done", "!!"])
+        .code(4)
+        .stderr(predicate::str::contains("!! isn't a valid id"));
+    run(&tmp_dir, &["add", "Bad due", "--due", "someday soonish"])
+        .code(5)
+        .stderr(predicate::str::contains("\"someday soonish\" isn't a time"));
+    // Nothing is added when the due time doesn't work
+    run(&tmp_dir, &["list"])
+        .success()
+        .stdout(predicate::str::contains("Bad due").not());
+    run(&tmp_dir, &["add", "Blocked"]).success();
+    let stdout = run(&tmp_dir, &["list"]).get_output().stdout.clone();
+    let show_id = String::from_utf8(stdout)
+        .unwrap()
+        .lines()
+        .find(|line| line.contains("Blocked"))
+        .and_then(|line| line.split_whitespace().next())
+        .unwrap()
+        .to_string();
+    run(&tmp_dir, &["block", &show_id, "--on", &show_id])
+        .code(6)
+        .stderr(predicate::str::contains("that would create a cycle"));
+    run(&tmp_dir, &["project", "rename", "nope", "other"])
+        .code(3)
+        .stderr(predicate::str::contains("There's no project named nope"));
+}
+
+#[test]
+fn test_batch_ids() {
+    let tmp_dir = TempDir::new("workingon_test").expect("cannot make temp directory for test");
+
+    for todo_title in ["First", "Second", "Third"] {
+        run(&tmp_dir, &["add", todo_title]).success();
+    }
+    let stdout = run(&tmp_dir, &["list"]).get_output().stdout.clone();
+    let ids: Vec<String> = String::from_utf8(stdout)
+        .unwrap()
+        .lines()
+        .skip(1)
+        .filter_map(|line| line.split_whitespace().next())
+        .map(|show_id| show_id.to_string())
+        .collect();
+    assert_eq!(ids.len(), 3);
+
+    // A bad id is reported, the others are still completed
+    run(&tmp_dir, &["done", &ids[0], "zzzzz", &ids[1]])
+        .code(3)
+        .stdout(predicate::str::contains(format!("{} completed", ids[0])))
+        .stdout(predicate::str::contains(format!("{} completed", ids[1])))
+        .stdout(predicate::str::contains("2 of 3 TODOs completed"))
+        .stderr(predicate::str::contains("There's no TODO with id zzzzz"));
+    assert!(store(&tmp_dir)
+        .get_todo(&ids[0])
+        .unwrap()
+        .completed
+        .is_some());
+    assert!(store(&tmp_dir)
+        .get_todo(&ids[1])
+        .unwrap()
+        .completed
+        .is_some());
+
+    // With --atomic nothing is changed
+    run(&tmp_dir, &["reopen", "--atomic", &ids[0], "zzzzz"])
+        .code(3)
+        .stderr(predicate::str::contains("Nothing was changed"));
+    assert!(store(&tmp_dir)
+        .get_todo(&ids[0])
+        .unwrap()
+        .completed
+        .is_some());
+
+    // Ids can come from stdin, the first word of each line
+    workingon(&tmp_dir)
+        .args(["reopen", "-"])
+        .write_stdin(format!("{} First\n\n{}\n", ids[0], ids[1]))
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("2 of 2 TODOs reopened"));
+    assert!(store(&tmp_dir)
+        .get_todo(&ids[1])
+        .unwrap()
+        .completed
+        .is_none());
+
+    // The last argument of due is the time
+    run(&tmp_dir, &["due", &ids[0], &ids[1], "tomorrow"])
+        .success()
+        .stdout(predicate::str::contains("2 of 2 TODOs changed"));
+    assert!(store(&tmp_dir).get_todo(&ids[0]).unwrap().due.is_some());
+    assert!(store(&tmp_dir).get_todo(&ids[1]).unwrap().due.is_some());
+    run(&tmp_dir, &["due", "--clear", &ids[0], &ids[1]]).success();
+    assert!(store(&tmp_dir).get_todo(&ids[0]).unwrap().due.is_none());
+    assert!(store(&tmp_dir).get_todo(&ids[1]).unwrap().due.is_none());
+    // --at takes a time that could be mistaken for an id, like "friday"
+    run(&tmp_dir, &["due", &ids[0], &ids[1], "--at", "friday"])
+        .success()
+        .stdout(predicate::str::contains("2 of 2 TODOs changed"));
+    assert!(store(&tmp_dir).get_todo(&ids[1]).unwrap().due.is_some());
+    // Ids alone remove the due time
+    run(&tmp_dir, &["due", &ids[0], &ids[1]]).success();
+    assert!(store(&tmp_dir).get_todo(&ids[0]).unwrap().due.is_none());
+    assert!(store(&tmp_dir).get_todo(&ids[1]).unwrap().due.is_none());
+
+    let stdout = run(&tmp_dir, &["delete", &ids[1], &ids[2], "--format", "json"])
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let deleted: serde_json::Value = serde_json::from_slice(&stdout).unwrap();
+    assert_eq!(deleted["todos"].as_array().unwrap().len(), 2);
+    assert_eq!(deleted["todos"][0]["id"], ids[1].as_str());
+    assert!(store(&tmp_dir).get_todo(&ids[2]).is_err());
+}
+
+#[test]
+fn test_undo() {
+    let tmp_dir = TempDir::new("workingon_test").expect("cannot make temp directory for test");
+
+    run(&tmp_dir, &["undo"])
+        .success()
+        .stdout(predicate::str::contains("Nothing to undo"));
+    run(
+        &tmp_dir,
+        &["add", "Keep me", "--tag", "work", "--priority", "high"],
+    )
+    .success();
+    let stdout = run(&tmp_dir, &["list"]).get_output().stdout.clone();
+    let show_id = String::from_utf8(stdout)
+        .unwrap()
+        .lines()
+        .nth(1)
+        .and_then(|line| line.split_whitespace().next())
+        .unwrap()
+        .to_string();
+
+    run(&tmp_dir, &["delete", &show_id])
+        .success()
+        .stdout(predicate::str::contains("workingon undo"));
+    run(&tmp_dir, &["undo"])
+        .success()
+        .stdout(predicate::str::contains(format!(
+            "Undid: delete {}",
+            show_id
+        )));
+    let restored = store(&tmp_dir).get_todo(&show_id).unwrap();
+    assert_eq!(restored.title, "Keep me");
+    assert_eq!(restored.priority, workingon::models::Priority::High.level());
+    assert_eq!(
+        store(&tmp_dir).get_todo_tags(&show_id).unwrap(),
+        vec!["work"]
+    );
+
+    run(&tmp_dir, &["redo"])
+        .success()
+        .stdout(predicate::str::contains(format!(
+            "Redid: delete {}",
+            show_id
+        )));
+    assert!(store(&tmp_dir).get_todo(&show_id).is_err());
+
+    // Undoing the add removes the TODO along with everything it was created with
+    let stdout = run(&tmp_dir, &["undo", "-n", "2", "--format", "json"])
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let undone: serde_json::Value = serde_json::from_slice(&stdout).unwrap();
+    assert_eq!(undone["operations"].as_array().unwrap().len(), 2);
+    assert_eq!(undone["operations"][1]["description"], "add \"Keep me\"");
+    assert!(store(&tmp_dir).get_todo(&show_id).is_err());
+    run(&tmp_dir, &["redo"]).success();
+    assert_eq!(
+        store(&tmp_dir).get_todo_tags(&show_id).unwrap(),
+        vec!["work"]
+    );
+}
@@ -38,7 +38,7 @@ fn test_encode_id() {
 fn test_decode_id() {
     let original_id = 42;
     let encoded = encode_id(original_id);
-    let decoded = decode_id(&encoded);
+    let decoded = decode_id(&encoded).unwrap();
     assert_eq!(decoded, original_id as i32);
 }
 
@@ -48,7 +48,7 @@ fn test_encode_decode_roundtrip() {
     let test_cases = vec![1, 100, 1000, 9999];
     for id in test_cases {
         let encoded = encode_id(id);
-        let decoded = decode_id(&encoded);
+        let decoded = decode_id(&encoded).unwrap();
         assert_eq!(decoded, id as i32);
     }
 }
@@ -60,7 +60,7 @@ fn test_get_project_data_folder_with_env_var() {
     let env_var_name = format!("{}_data_dir", workingon::constants::BIN).to_uppercase();
     env::set_var(&env_var_name, _tmp_dir.path());
 
-    let result = get_project_data_folder();
+    let result = get_project_data_folder().unwrap();
     assert_eq!(result, _tmp_dir.path());
 
     env::remove_var(&env_var_name);
@@ -73,7 +73,7 @@ fn test_get_project_data_folder_without_env_var() {
     let env_var_name = format!("{}_data_dir", workingon::constants::BIN).to_uppercase();
     env::remove_var(&env_var_name);
 
-    let result = get_project_data_folder();
+    let result = get_project_data_folder().unwrap();
     assert!(result.exists());
     assert!(result.is_dir());
     assert!(result.to_string_lossy().contains(workingon::constants::BIN));
@@ -83,7 +83,7 @@ fn test_get_project_data_folder_without_env_var() {
 #[serial]
 fn test_get_db_file() {
     let _tmp_dir = setup_test_env();
-    let db_file = get_db_file();
+    let db_file = get_db_file().unwrap();
 
     assert!(db_file.to_string_lossy().ends_with("todos.sqlite3"));
     assert_eq!(db_file.parent().unwrap(), _tmp_dir.path());
@@ -95,7 +95,7 @@ fn test_get_db_file() {
 #[serial]
 fn test_get_todoeditmsg_file() {
     let _tmp_dir = setup_test_env();
-    let todo_file = get_todoeditmsg_file();
+    let todo_file = get_todoeditmsg_file().unwrap();
 
     assert!(todo_file.to_string_lossy().ends_with("TODO_EDITMSG"));
     assert_eq!(todo_file.parent().unwrap(), _tmp_dir.path());
@@ -125,9 +125,51 @@ fn test_get_editor_without_env_var() {
 fn test_establish_connection() {
     let _tmp_dir = setup_test_env();
 
-    let _connection = establish_connection();
-    // If we get here without panicking, the connection was established successfully
-    assert!(true);
+    assert!(establish_connection().is_ok());
+
+    cleanup_test_env();
+}
+
+#[test]
+#[serial]
+fn test_todo_store_transaction() {
+    let tmp_dir = setup_test_env();
+
+    // A store doesn't need the data folder, it can be opened anywhere
+    let mut store = TodoStore::open_at(&tmp_dir.path().join("other.sqlite3")).unwrap();
+    let kept = store
+        .add_todo(&NewTodo {
+            title: "Kept",
+            notes: "",
+            created: Utc::now(),
+        })
+        .unwrap();
+    let result: Result<(), Error> = store.transaction(|store| {
+        store.add_todo(&NewTodo {
+            title: "Rolled back",
+            notes: "",
+            created: Utc::now(),
+        })?;
+        store.set_priority(
+            &encode_id(kept.id.try_into().unwrap()),
+            models::Priority::High,
+        )?;
+        store.set_due("zzzzz", None)
+    });
+    assert!(matches!(result, Err(Error::NotFound { .. })));
+    let todos = store.get_todos().unwrap();
+    assert_eq!(todos.len(), 1);
+    assert_eq!(todos[0].title, "Kept");
+    assert_eq!(todos[0].priority, models::Priority::Unset.level());
+
+    store
+        .transaction(|store| {
+            store.set_todo_title(&encode_id(kept.id.try_into().unwrap()), "Renamed")
+        })
+        .unwrap();
+    assert_eq!(store.get_todos().unwrap()[0].title, "Renamed");
+    // The free functions use the store in the data folder
+    assert!(get_todos().unwrap().is_empty());
 
     cleanup_test_env();
 }
@@ -142,7 +184,8 @@ fn test_create_temp_todo_file_with_editor_dash() {
         &test_file,
         "Test Title".to_string(),
         "Test Notes".to_string(),
-    );
+    )
+    .unwrap();
 
     assert_eq!(title, "Test Title");
     assert_eq!(notes, "Test Notes");
@@ -164,7 +207,8 @@ fn test_create_temp_todo_file_with_real_editor() {
         &test_file,
         "Test Title".to_string(),
         "Test Notes".to_string(),
-    );
+    )
+    .unwrap();
 
     // With echo as editor, the file content should be processed
     assert_eq!(title, "Test Title");
@@ -184,10 +228,11 @@ fn test_add_todo_with_title() {
         title: "Test TODO",
         notes: "",
         created: Utc::now(),
-    });
+    })
+    .unwrap();
 
     // Verify the TODO was added by checking the database
-    let connection = &mut establish_connection();
+    let connection = &mut establish_connection().unwrap();
     use workingon::schema::todos::dsl::*;
     let results = todos
         .select(workingon::models::Todos::as_select())
@@ -211,10 +256,11 @@ fn test_add_todo_without_title() {
         title: "<title>",
         notes: "",
         created: Utc::now(),
-    });
+    })
+    .unwrap();
 
     // Verify the TODO was added with default title
-    let connection = &mut establish_connection();
+    let connection = &mut establish_connection().unwrap();
     use workingon::schema::todos::dsl::*;
     let results = todos
         .select(workingon::models::Todos::as_select())
@@ -239,10 +285,11 @@ fn test_show_todo() {
         title: "Show Test TODO",
         notes: "",
         created: Utc::now(),
-    });
+    })
+    .unwrap();
 
     // Get the TODO ID
-    let connection = &mut establish_connection();
+    let connection = &mut establish_connection().unwrap();
     use workingon::schema::todos::dsl::*;
     let results = todos
         .select(workingon::models::Todos::as_select())
@@ -253,7 +300,7 @@ fn test_show_todo() {
     let todo_id = encode_id(results[0].id.try_into().unwrap());
 
     // Show the TODO
-    get_todo(&todo_id);
+    get_todo(&todo_id).unwrap();
 
     cleanup_test_env();
 }
@@ -269,8 +316,9 @@ fn test_list_todos_flags() {
         title: "Open TODO",
         notes: "",
         created: Utc::now(),
-    });
-    let connection = &mut establish_connection();
+    })
+    .unwrap();
+    let connection = &mut establish_connection().unwrap();
     use workingon::schema::todos::dsl::*;
     let open_results = todos
         .select(workingon::models::Todos::as_select())
@@ -284,17 +332,18 @@ fn test_list_todos_flags() {
         title: "Completed TODO",
         notes: "",
         created: Utc::now(),
-    });
+    })
+    .unwrap();
     let completed_results = todos
         .select(workingon::models::Todos::as_select())
         .filter(title.eq("Completed TODO"))
         .load(connection)
         .expect("Error loading todos");
     let completed_todo_id = completed_results[0].id;
-    complete_todo(&encode_id(completed_todo_id.try_into().unwrap()), None);
+    complete_todo(&encode_id(completed_todo_id.try_into().unwrap()), None).unwrap();
 
     // Test 1: None (default) should show only open TODOs
-    let connection = &mut establish_connection();
+    let connection = &mut establish_connection().unwrap();
     let results = todos
         .select(workingon::models::Todos::as_select())
         .filter(completed.is_null())
@@ -305,7 +354,7 @@ fn test_list_todos_flags() {
     assert_eq!(results[0].title, "Open TODO");
 
     // Test 2: Some(true) should show only completed TODOs
-    let connection = &mut establish_connection();
+    let connection = &mut establish_connection().unwrap();
     let results = todos
         .select(workingon::models::Todos::as_select())
         .filter(completed.is_not_null())
@@ -316,7 +365,7 @@ fn test_list_todos_flags() {
     assert_eq!(results[0].title, "Completed TODO");
 
     // Test 3: Some(false) should show all TODOs
-    let connection = &mut establish_connection();
+    let connection = &mut establish_connection().unwrap();
     let results = todos
         .select(workingon::models::Todos::as_select())
         .filter(id.eq(open_todo_id).or(id.eq(completed_todo_id)))
@@ -328,230 +377,1801 @@ fn test_list_todos_flags() {
 }
 
 #[test]
-#[serial]
 fn test_delete_todo() {
-    let _tmp_dir = setup_test_env();
+    let storage = &mut storage::MemoryStorage::new();
+
+    // Add a TODO first
+    let created_todo = storage
+        .add_todo(&NewTodo {
+            title: "Delete Test TODO",
+            notes: "",
+            created: Utc::now(),
+        })
+        .unwrap();
+    let todo_id = encode_id(created_todo.id.try_into().unwrap());
+
+    // Delete the TODO
+    storage.delete_todo(&todo_id).unwrap();
+
+    // Verify it was deleted
+    assert!(storage.get_todos().unwrap().is_empty());
+    assert!(matches!(
+        storage.delete_todo(&todo_id),
+        Err(Error::NotFound { .. })
+    ));
+}
+
+#[test]
+fn test_complete_todo() {
+    let storage = &mut storage::MemoryStorage::new();
+
+    // Add a TODO first
+    let created_todo = storage
+        .add_todo(&NewTodo {
+            title: "Complete Test TODO",
+            notes: "",
+            created: Utc::now(),
+        })
+        .unwrap();
+    let todo_id = encode_id(created_todo.id.try_into().unwrap());
+
+    // Verify it's not completed initially
+    assert!(created_todo.completed.is_none());
+
+    // Complete the TODO
+    storage.complete_todo(&todo_id, None).unwrap();
+
+    // Verify it's now completed
+    let completed_todo = storage.get_todo(&todo_id).unwrap();
+    assert!(completed_todo.completed.is_some());
+
+    // Completing it again keeps the first completion time
+    storage
+        .complete_todo(&todo_id, Some(Utc::now() + TimeDelta::days(1)))
+        .unwrap();
+    assert_eq!(
+        storage.get_todo(&todo_id).unwrap().completed,
+        completed_todo.completed
+    );
+}
+
+#[test]
+fn test_reopen_todo() {
+    let storage = &mut storage::MemoryStorage::new();
 
     // Add a TODO first
+    let created_todo = storage
+        .add_todo(&NewTodo {
+            title: "Reopen Test TODO",
+            notes: "",
+            created: Utc::now(),
+        })
+        .unwrap();
+    let todo_id = encode_id(created_todo.id.try_into().unwrap());
+
+    // Complete the TODO first
+    storage.complete_todo(&todo_id, None).unwrap();
+
+    // Verify it's completed
+    assert!(storage.get_todo(&todo_id).unwrap().completed.is_some());
+
+    // Reopen the TODO
+    storage.reopen_todo(&todo_id).unwrap();
+
+    // Verify it's now reopened (completed is None)
+    assert!(storage.get_todo(&todo_id).unwrap().completed.is_none());
+}
+
+#[test]
+fn test_reopen_todo_already_uncompleted() {
+    let storage = &mut storage::MemoryStorage::new();
+
+    // Add a TODO first
+    let created_todo = storage
+        .add_todo(&NewTodo {
+            title: "Reopen Uncompleted Test TODO",
+            notes: "",
+            created: Utc::now(),
+        })
+        .unwrap();
+    let todo_id = encode_id(created_todo.id.try_into().unwrap());
+
+    // Verify it's not completed initially
+    assert!(created_todo.completed.is_none());
+
+    // Reopen the TODO (even though it's not completed)
+    storage.reopen_todo(&todo_id).unwrap();
+
+    // Verify it's still uncompleted (completed is None)
+    assert!(storage.get_todo(&todo_id).unwrap().completed.is_none());
+}
+
+#[test]
+fn test_decode_id_invalid_input() {
+    assert!(matches!(
+        decode_id("invalid_id"),
+        Err(Error::InvalidId(id)) if id == "invalid_id"
+    ));
+}
+
+#[test]
+fn test_constants() {
+    assert_eq!(
+        workingon::COMMENT_DISCLAIMER,
+        "# This is a comment, lines starting with a # will be ignored"
+    );
+    assert!(!workingon::constants::BIN.is_empty());
+    assert!(!workingon::constants::DEFAULT_EDITOR.is_empty());
+}
+
+#[test]
+fn test_add_and_complete() {
+    let storage = &mut storage::MemoryStorage::new();
+
+    let created_todo = storage
+        .add_todo(&NewTodo {
+            title: "Closed TODO",
+            notes: "",
+            created: Utc::now(),
+        })
+        .unwrap();
+    let id_string = encode_id(created_todo.id.try_into().unwrap());
+    storage
+        .complete_todo(&id_string, Some(created_todo.created))
+        .unwrap();
+    let updated_todo = storage.get_todo(&id_string).unwrap();
+    assert!(updated_todo.created == updated_todo.completed.unwrap())
+}
+
+#[test]
+fn test_set_due() {
+    let storage = &mut storage::MemoryStorage::new();
+
+    let created_todo = storage
+        .add_todo(&NewTodo {
+            title: "test_set_due",
+            notes: "",
+            created: Utc::now(),
+        })
+        .unwrap();
+    let id_string = encode_id(created_todo.id.try_into().unwrap());
+    let a_week_later = created_todo.created + TimeDelta::seconds(60 * 60 * 24 * 7);
+    storage.set_due(&id_string, Some(a_week_later)).unwrap();
+    let updated_todo = storage.get_todo(&id_string).unwrap();
+    assert!(updated_todo.due.unwrap() == a_week_later);
+    storage.set_due(&id_string, None).unwrap();
+    let updated_todo = storage.get_todo(&id_string).unwrap();
+    assert!(updated_todo.due.is_none());
+}
+
+#[test]
+fn test_add_and_remove_tags() {
+    let storage = &mut storage::MemoryStorage::new();
+
+    let created_todo = storage
+        .add_todo(&NewTodo {
+            title: "test_add_and_remove_tags",
+            notes: "",
+            created: Utc::now(),
+        })
+        .unwrap();
+    let id_string = encode_id(created_todo.id.try_into().unwrap());
+    storage
+        .add_tags(
+            &id_string,
+            &[
+                "oncall".to_string(),
+                "infra".to_string(),
+                "infra".to_string(),
+            ],
+        )
+        .unwrap();
+    assert_eq!(
+        storage.get_todo_tags(&id_string).unwrap(),
+        vec!["infra", "oncall"]
+    );
+    storage
+        .remove_tags(&id_string, &["oncall".to_string()])
+        .unwrap();
+    assert_eq!(storage.get_todo_tags(&id_string).unwrap(), vec!["infra"]);
+    assert!(matches!(
+        storage.remove_tags("zzzzz", &["infra".to_string()]),
+        Err(Error::NotFound { .. })
+    ));
+}
+
+#[test]
+#[serial]
+fn test_get_todos_with_tags() {
+    let _tmp_dir = setup_test_env();
+
+    let both = add_todo(&NewTodo {
+        title: "both tags",
+        notes: "",
+        created: Utc::now(),
+    })
+    .unwrap();
+    let one = add_todo(&NewTodo {
+        title: "one tag",
+        notes: "",
+        created: Utc::now(),
+    })
+    .unwrap();
     add_todo(&NewTodo {
-        title: "Delete Test TODO",
+        title: "no tags",
         notes: "",
         created: Utc::now(),
-    });
+    })
+    .unwrap();
+    let infra = "infra".to_string();
+    let oncall = "oncall".to_string();
+    add_tags(
+        &encode_id(both.id.try_into().unwrap()),
+        &[infra.clone(), oncall.clone()],
+    )
+    .unwrap();
+    add_tags(
+        &encode_id(one.id.try_into().unwrap()),
+        &["infra".to_string()],
+    )
+    .unwrap();
+
+    let results = get_todos_with_tags(&["infra".to_string()]).unwrap();
+    assert_eq!(results.len(), 2);
+    let results = get_todos_with_tags(&[infra, oncall]).unwrap();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].title, "both tags");
 
-    // Get the TODO ID
-    let connection = &mut establish_connection();
-    use workingon::schema::todos::dsl::*;
-    let results = todos
-        .select(workingon::models::Todos::as_select())
-        .filter(title.eq("Delete Test TODO"))
-        .load(connection)
-        .expect("Error loading todos");
+    // Deleting a TODO also removes its tags
+    delete_todo(&encode_id(both.id.try_into().unwrap())).unwrap();
+    assert!(get_todos_with_tags(&["oncall".to_string()])
+        .unwrap()
+        .is_empty());
 
-    let todo_id = encode_id(results[0].id.try_into().unwrap());
+    cleanup_test_env();
+}
 
-    // Delete the TODO
-    delete_todo(&todo_id);
+#[test]
+#[serial]
+fn test_projects() {
+    let _tmp_dir = setup_test_env();
 
-    // Verify it was deleted
-    let remaining = todos
-        .select(workingon::models::Todos::as_select())
-        .filter(title.eq("Delete Test TODO"))
-        .load(connection)
-        .expect("Error loading todos");
-    assert_eq!(remaining.len(), 0);
+    add_project("infra").unwrap();
+    add_project("home").unwrap();
+    let created_todo = add_todo(&NewTodo {
+        title: "test_projects",
+        notes: "",
+        created: Utc::now(),
+    })
+    .unwrap();
+    let id_string = encode_id(created_todo.id.try_into().unwrap());
+    set_todo_project(&id_string, Some("infra")).unwrap();
+    let infra = get_project("infra").unwrap();
+    assert_eq!(get_todo(&id_string).unwrap().project_id, Some(infra.id));
+    assert_eq!(
+        count_open_todos_by_project().unwrap().get(&Some(infra.id)),
+        Some(&1)
+    );
+
+    rename_project("infra", "platform").unwrap();
+    assert_eq!(get_project("platform").unwrap().id, infra.id);
+    archive_project("home").unwrap();
+    assert!(get_project("home").unwrap().archived.is_some());
+    assert_eq!(
+        get_projects()
+            .unwrap()
+            .into_iter()
+            .map(|project| project.name)
+            .collect::<Vec<String>>(),
+        vec!["home", "platform"]
+    );
+
+    set_todo_project(&id_string, None).unwrap();
+    assert!(get_todo(&id_string).unwrap().project_id.is_none());
 
     cleanup_test_env();
 }
 
 #[test]
 #[serial]
-fn test_complete_todo() {
+fn test_set_todo_project_archived() {
     let _tmp_dir = setup_test_env();
-    env::set_var("EDITOR", "-"); // Ensure editor is set to dash
 
-    // Add a TODO first
-    add_todo(&NewTodo {
-        title: "Complete Test TODO",
+    add_project("home").unwrap();
+    archive_project("home").unwrap();
+    let created_todo = add_todo(&NewTodo {
+        title: "test_set_todo_project_archived",
         notes: "",
         created: Utc::now(),
-    });
+    })
+    .unwrap();
+    let result = set_todo_project(
+        &encode_id(created_todo.id.try_into().unwrap()),
+        Some("home"),
+    );
+    assert!(matches!(result, Err(Error::Conflict(_))));
+    assert!(result
+        .unwrap_err()
+        .to_string()
+        .contains("Project home is archived"));
 
-    // Get the TODO ID
-    let connection = &mut establish_connection();
-    use workingon::schema::todos::dsl::*;
-    let results = todos
-        .select(workingon::models::Todos::as_select())
-        .filter(title.eq("Complete Test TODO"))
-        .load(connection)
-        .expect("Error loading todos");
+    cleanup_test_env();
+}
 
-    let todo_id = encode_id(results[0].id.try_into().unwrap());
+#[test]
+fn test_priority_from_str() {
+    use workingon::models::Priority;
+    assert_eq!("high".parse::<Priority>(), Ok(Priority::High));
+    assert_eq!("M".parse::<Priority>(), Ok(Priority::Medium));
+    assert_eq!("1".parse::<Priority>(), Ok(Priority::Low));
+    assert_eq!("none".parse::<Priority>(), Ok(Priority::Unset));
+    assert!("urgent".parse::<Priority>().is_err());
+    assert_eq!(Priority::from_level(Priority::High.level()), Priority::High);
+}
 
-    // Verify it's not completed initially
-    assert!(results[0].completed.is_none());
+#[test]
+fn test_set_priority() {
+    use workingon::models::Priority;
+    let storage = &mut storage::MemoryStorage::new();
+
+    let created_todo = storage
+        .add_todo(&NewTodo {
+            title: "test_set_priority",
+            notes: "",
+            created: Utc::now(),
+        })
+        .unwrap();
+    assert_eq!(created_todo.priority, Priority::Unset.level());
+    let id_string = encode_id(created_todo.id.try_into().unwrap());
+    storage.set_priority(&id_string, Priority::High).unwrap();
+    assert_eq!(
+        storage.get_todo(&id_string).unwrap().priority,
+        Priority::High.level()
+    );
+}
 
-    // Complete the TODO
-    complete_todo(&todo_id, None);
+#[test]
+#[serial]
+fn test_urgency() {
+    use workingon::models::Priority;
+    use workingon::urgency::{due_bucket, sort_by_urgency, urgency, DueBucket};
+    let _tmp_dir = setup_test_env();
 
-    // Verify it's now completed
-    let completed_results = todos
-        .select(workingon::models::Todos::as_select())
-        .filter(id.eq(results[0].id))
-        .load(connection)
-        .expect("Error loading todos");
+    let now = Utc::now();
+    assert_eq!(due_bucket(None, now), DueBucket::NoDueDate);
+    assert_eq!(
+        due_bucket(Some(now - TimeDelta::hours(1)), now),
+        DueBucket::Overdue
+    );
+    assert_eq!(
+        due_bucket(Some(now + TimeDelta::days(2)), now),
+        DueBucket::WithinThreeDays
+    );
 
-    assert_eq!(completed_results.len(), 1);
-    assert!(completed_results[0].completed.is_some());
+    let plain = add_todo(&NewTodo {
+        title: "plain",
+        notes: "",
+        created: now,
+    })
+    .unwrap();
+    assert_eq!(urgency(&plain, 0, now).total(), 0.0);
+    assert!(urgency(&plain, 0, now).factors.is_empty());
+
+    let old_important = add_todo(&NewTodo {
+        title: "old and important",
+        notes: "",
+        created: now - TimeDelta::days(365),
+    })
+    .unwrap();
+    let old_important_id = encode_id(old_important.id.try_into().unwrap());
+    set_priority(&old_important_id, Priority::High).unwrap();
+    let overdue = add_todo(&NewTodo {
+        title: "overdue",
+        notes: "",
+        created: now,
+    })
+    .unwrap();
+    set_due(
+        &encode_id(overdue.id.try_into().unwrap()),
+        Some(now - TimeDelta::hours(1)),
+    )
+    .unwrap();
+
+    let old_important = get_todo(&old_important_id).unwrap();
+    let old_important_urgency = urgency(&old_important, 3, now);
+    let reasons: Vec<&str> = old_important_urgency
+        .factors
+        .iter()
+        .map(|factor| factor.reason.as_str())
+        .collect();
+    assert_eq!(reasons, vec!["high priority", "365 days old", "3 tags"]);
+    assert_eq!(old_important_urgency.total(), 9.0);
+
+    let ranked: Vec<String> = sort_by_urgency(get_todos().unwrap(), now)
+        .unwrap()
+        .into_iter()
+        .map(|(todo, _)| todo.title)
+        .collect();
+    assert_eq!(ranked, vec!["overdue", "old and important", "plain"]);
 
     cleanup_test_env();
 }
 
+#[test]
+fn test_recurrence_from_str() {
+    use workingon::recurrence::{Frequency, Recurrence};
+    let weekly = Recurrence::every(1, Frequency::Weekly);
+    assert_eq!("week".parse::<Recurrence>(), Ok(weekly));
+    assert_eq!("weekly".parse::<Recurrence>(), Ok(weekly));
+    assert_eq!("FREQ=WEEKLY".parse::<Recurrence>(), Ok(weekly));
+    assert_eq!(
+        "every 3 days".parse::<Recurrence>(),
+        Ok(Recurrence::every(3, Frequency::Daily))
+    );
+    assert_eq!(
+        "RRULE:FREQ=MONTHLY;INTERVAL=2".parse::<Recurrence>(),
+        Ok(Recurrence::every(2, Frequency::Monthly))
+    );
+    assert!("FREQ=WEEKLY;BYDAY=MO".parse::<Recurrence>().is_err());
+    assert!("every 0 days".parse::<Recurrence>().is_err());
+    assert!("fortnightly".parse::<Recurrence>().is_err());
+    // Intervals whose next occurrence can't be computed
+    assert!("every 400000000 years".parse::<Recurrence>().is_err());
+    assert!("FREQ=DAILY;INTERVAL=4000000000"
+        .parse::<Recurrence>()
+        .is_err());
+    // Stored as an RRULE and read back the same way
+    let rule = Recurrence::every(2, Frequency::Yearly);
+    assert_eq!(rule.to_string(), "FREQ=YEARLY;INTERVAL=2");
+    assert_eq!(rule.to_string().parse::<Recurrence>(), Ok(rule));
+    assert_eq!(rule.describe(), "every 2 years");
+}
+
+#[test]
+fn test_recurrence_next_after() {
+    use chrono::{Local, TimeZone};
+    use workingon::recurrence::{Frequency, Recurrence};
+    let ts = Local
+        .with_ymd_and_hms(2026, 1, 31, 9, 0, 0)
+        .unwrap()
+        .to_utc();
+    let local_next = |rule: Recurrence| {
+        rule.next_after(ts)
+            .unwrap()
+            .with_timezone(&Local)
+            .naive_local()
+    };
+    assert_eq!(
+        local_next(Recurrence::every(3, Frequency::Daily)).to_string(),
+        "2026-02-03 09:00:00"
+    );
+    assert_eq!(
+        local_next(Recurrence::every(1, Frequency::Weekly)).to_string(),
+        "2026-02-07 09:00:00"
+    );
+    // Months are clamped to their last day
+    assert_eq!(
+        local_next(Recurrence::every(1, Frequency::Monthly)).to_string(),
+        "2026-02-28 09:00:00"
+    );
+}
+
+#[test]
+fn test_complete_recurring_todo() {
+    use workingon::recurrence::{Frequency, Recurrence};
+    let storage = &mut storage::MemoryStorage::new();
+
+    let created_todo = storage
+        .add_todo(&NewTodo {
+            title: "weekly report",
+            notes: "",
+            created: Utc::now(),
+        })
+        .unwrap();
+    let id_string = encode_id(created_todo.id.try_into().unwrap());
+    let due_ts = created_todo.created + TimeDelta::days(1);
+    storage.set_due(&id_string, Some(due_ts)).unwrap();
+    storage
+        .set_recurrence(&id_string, Some(&Recurrence::every(1, Frequency::Weekly)))
+        .unwrap();
+    storage.add_tags(&id_string, &["work".to_string()]).unwrap();
+
+    let next_todo = storage
+        .complete_todo(&id_string, None)
+        .unwrap()
+        .expect("No next occurrence was spawned");
+    assert_eq!(next_todo.title, "weekly report");
+    assert_eq!(next_todo.template_id, Some(created_todo.id));
+    assert_eq!(
+        next_todo.recurrence,
+        Some("FREQ=WEEKLY;INTERVAL=1".to_string())
+    );
+    assert_eq!(
+        next_todo.due,
+        Some(
+            Recurrence::every(1, Frequency::Weekly)
+                .next_after(due_ts)
+                .unwrap()
+        )
+    );
+    assert!(next_todo.completed.is_none());
+    let next_id = encode_id(next_todo.id.try_into().unwrap());
+    assert_eq!(storage.get_todo_tags(&next_id).unwrap(), vec!["work"]);
+
+    // Completing an already completed TODO doesn't spawn another one
+    assert!(storage.complete_todo(&id_string, None).unwrap().is_none());
+    // Later occurrences keep linking to the first one
+    let third_todo = storage
+        .complete_todo(&next_id, None)
+        .unwrap()
+        .expect("No next occurrence was spawned");
+    assert_eq!(third_todo.template_id, Some(created_todo.id));
+
+    let third_id = encode_id(third_todo.id.try_into().unwrap());
+    storage.set_recurrence(&third_id, None).unwrap();
+    assert!(storage.complete_todo(&third_id, None).unwrap().is_none());
+
+    // An interval too large to compute the next occurrence of is refused, not a panic
+    storage
+        .set_recurrence(
+            &next_id,
+            Some(&Recurrence::every(400_000_000, Frequency::Yearly)),
+        )
+        .unwrap();
+    storage.reopen_todo(&next_id).unwrap();
+    assert!(matches!(
+        storage.complete_todo(&next_id, None),
+        Err(Error::Conflict(_))
+    ));
+}
+
 #[test]
 #[serial]
-fn test_reopen_todo() {
+fn test_subtasks() {
     let _tmp_dir = setup_test_env();
-    env::set_var("EDITOR", "-"); // Ensure editor is set to dash
 
-    // Add a TODO first
-    add_todo(&NewTodo {
-        title: "Reopen Test TODO",
+    let parent = add_todo(&NewTodo {
+        title: "parent",
         notes: "",
         created: Utc::now(),
-    });
+    })
+    .unwrap();
+    let parent_id = encode_id(parent.id.try_into().unwrap());
+    let mut subtask_ids = Vec::new();
+    for subtask_title in ["first", "second"] {
+        let subtask = add_todo(&NewTodo {
+            title: subtask_title,
+            notes: "",
+            created: Utc::now(),
+        })
+        .unwrap();
+        let subtask_id = encode_id(subtask.id.try_into().unwrap());
+        set_parent(&subtask_id, Some(&parent_id)).unwrap();
+        subtask_ids.push(subtask_id);
+    }
+    complete_todo(&subtask_ids[0], None).unwrap();
 
-    // Get the TODO ID
-    let connection = &mut establish_connection();
-    use workingon::schema::todos::dsl::*;
-    let results = todos
-        .select(workingon::models::Todos::as_select())
-        .filter(title.eq("Reopen Test TODO"))
-        .load(connection)
-        .expect("Error loading todos");
+    assert_eq!(get_subtasks(&parent_id).unwrap().len(), 2);
+    assert_eq!(
+        count_subtasks_by_parent().unwrap().get(&parent.id),
+        Some(&(1, 2))
+    );
 
-    let todo_id = encode_id(results[0].id.try_into().unwrap());
+    // Deleting the parent turns subtasks into top level TODOs
+    delete_todo(&parent_id).unwrap();
+    assert!(get_todo(&subtask_ids[1]).unwrap().parent_id.is_none());
+    assert!(count_subtasks_by_parent().unwrap().is_empty());
 
-    // Complete the TODO first
-    complete_todo(&todo_id, None);
+    cleanup_test_env();
+}
 
-    // Verify it's completed
-    let completed_results = todos
-        .select(workingon::models::Todos::as_select())
-        .filter(id.eq(results[0].id))
-        .load(connection)
-        .expect("Error loading todos");
+#[test]
+#[serial]
+fn test_set_parent_cycle() {
+    let _tmp_dir = setup_test_env();
 
-    assert_eq!(completed_results.len(), 1);
-    assert!(completed_results[0].completed.is_some());
+    let parent = add_todo(&NewTodo {
+        title: "parent",
+        notes: "",
+        created: Utc::now(),
+    })
+    .unwrap();
+    let child = add_todo(&NewTodo {
+        title: "child",
+        notes: "",
+        created: Utc::now(),
+    })
+    .unwrap();
+    let parent_id = encode_id(parent.id.try_into().unwrap());
+    let child_id = encode_id(child.id.try_into().unwrap());
+    set_parent(&child_id, Some(&parent_id)).unwrap();
+    let result = set_parent(&parent_id, Some(&child_id));
+    assert!(matches!(
+        result,
+        Err(Error::Conflict(reason)) if reason.contains("that would create a cycle")
+    ));
 
-    // Reopen the TODO
-    reopen_todo(&todo_id);
+    cleanup_test_env();
+}
 
-    // Verify it's now reopened (completed is None)
-    let reopened_results = todos
-        .select(workingon::models::Todos::as_select())
-        .filter(id.eq(results[0].id))
-        .load(connection)
-        .expect("Error loading todos");
+#[test]
+#[serial]
+fn test_dependencies() {
+    let _tmp_dir = setup_test_env();
 
-    assert_eq!(reopened_results.len(), 1);
-    assert!(reopened_results[0].completed.is_none());
+    let mut ids = Vec::new();
+    for dependency_title in ["write", "review", "ship"] {
+        let created_todo = add_todo(&NewTodo {
+            title: dependency_title,
+            notes: "",
+            created: Utc::now(),
+        })
+        .unwrap();
+        ids.push(encode_id(created_todo.id.try_into().unwrap()));
+    }
+    // ship is blocked by review, which is blocked by write
+    add_dependency(&ids[2], &ids[1]).unwrap();
+    add_dependency(&ids[1], &ids[0]).unwrap();
+    assert_eq!(get_blockers(&ids[2]).unwrap()[0].title, "review");
+    assert_eq!(get_dependents(&ids[0]).unwrap()[0].title, "review");
+    assert_eq!(get_blocked_todo_ids().unwrap().len(), 2);
+
+    complete_todo(&ids[0], None).unwrap();
+    let blocked_ids = get_blocked_todo_ids().unwrap();
+    assert_eq!(blocked_ids.len(), 1);
+    assert!(blocked_ids.contains(&decode_id(&ids[2]).unwrap()));
+
+    remove_dependency(&ids[2], &ids[1]).unwrap();
+    assert!(get_blocked_todo_ids().unwrap().is_empty());
+    // Nothing to remove anymore
+    assert!(matches!(
+        remove_dependency(&ids[2], &ids[1]),
+        Err(Error::NotFound {
+            kind: "dependency",
+            ..
+        })
+    ));
+    assert!(matches!(
+        remove_dependency(&ids[2], "zzzzz"),
+        Err(Error::NotFound { kind: "TODO", .. })
+    ));
+
+    add_dependency(&ids[2], &ids[1]).unwrap();
+    delete_todo(&ids[1]).unwrap();
+    assert!(get_blockers(&ids[2]).unwrap().is_empty());
 
     cleanup_test_env();
 }
 
 #[test]
 #[serial]
-fn test_reopen_todo_already_uncompleted() {
+fn test_add_dependency_cycle() {
     let _tmp_dir = setup_test_env();
-    env::set_var("EDITOR", "-"); // Ensure editor is set to dash
 
-    // Add a TODO first
-    add_todo(&NewTodo {
-        title: "Reopen Uncompleted Test TODO",
+    let mut ids = Vec::new();
+    for dependency_title in ["first", "second", "third"] {
+        let created_todo = add_todo(&NewTodo {
+            title: dependency_title,
+            notes: "",
+            created: Utc::now(),
+        })
+        .unwrap();
+        ids.push(encode_id(created_todo.id.try_into().unwrap()));
+    }
+    add_dependency(&ids[1], &ids[0]).unwrap();
+    add_dependency(&ids[2], &ids[1]).unwrap();
+    let result = add_dependency(&ids[0], &ids[2]);
+    assert!(matches!(
+        result,
+        Err(Error::Conflict(reason)) if reason.contains("that would create a cycle")
+    ));
+
+    cleanup_test_env();
+}
+
+#[test]
+#[serial]
+fn test_sessions() {
+    let _tmp_dir = setup_test_env();
+
+    let first = add_todo(&NewTodo {
+        title: "first",
+        notes: "",
+        created: Utc::now(),
+    })
+    .unwrap();
+    let second = add_todo(&NewTodo {
+        title: "second",
         notes: "",
         created: Utc::now(),
+    })
+    .unwrap();
+    let first_id = encode_id(first.id.try_into().unwrap());
+    let second_id = encode_id(second.id.try_into().unwrap());
+    assert!(get_active_session().unwrap().is_none());
+    assert!(stop_session().unwrap().is_none());
+
+    let (session, stopped_session) = start_session(&first_id).unwrap();
+    assert_eq!(session.todo_id, first.id);
+    assert!(stopped_session.is_none());
+    // Starting the same TODO again keeps the session going
+    let (same_session, stopped_session) = start_session(&first_id).unwrap();
+    assert_eq!(same_session.id, session.id);
+    assert!(stopped_session.is_none());
+
+    // Starting another TODO stops the active session
+    let (session, stopped_session) = start_session(&second_id).unwrap();
+    assert_eq!(session.todo_id, second.id);
+    let stopped_session = stopped_session.expect("Previous session wasn't stopped");
+    assert_eq!(stopped_session.todo_id, first.id);
+    assert!(stopped_session.stopped.is_some());
+    assert_eq!(
+        get_active_session().unwrap().map(|s| s.id),
+        Some(session.id)
+    );
+
+    // Completing the TODO being worked on stops its session
+    complete_todo(&second_id, None).unwrap();
+    assert!(get_active_session().unwrap().is_none());
+    assert_eq!(get_sessions(&first_id).unwrap().len(), 1);
+    let tracked = tracked_time_by_todo(Utc::now()).unwrap();
+    assert_eq!(tracked.len(), 2);
+    assert_eq!(
+        tracked.get(&first.id),
+        Some(&stopped_session.duration(Utc::now()))
+    );
+
+    delete_todo(&first_id).unwrap();
+    assert!(get_sessions(&first_id).unwrap().is_empty());
+
+    cleanup_test_env();
+}
+
+#[test]
+#[serial]
+fn test_report_parse_day() {
+    // A Sunday
+    let today = chrono::NaiveDate::from_ymd_opt(2026, 10, 18).unwrap();
+    let parse = |s: &str| report::parse_day(s, today).unwrap().to_string();
+    assert_eq!(parse("today"), "2026-10-18");
+    assert_eq!(parse("yesterday"), "2026-10-17");
+    // Bare weekdays look back instead of ahead
+    assert_eq!(parse("monday"), "2026-10-12");
+    assert_eq!(parse("sunday"), "2026-10-18");
+    assert_eq!(
+        report::week_start(today),
+        chrono::NaiveDate::from_ymd_opt(2026, 10, 12).unwrap()
+    );
+}
+
+#[test]
+#[serial]
+fn test_report_time_entries() {
+    let monday = chrono::NaiveDate::from_ymd_opt(2026, 10, 12).unwrap();
+    let tuesday = monday.succ_opt().unwrap();
+    let since = report::day_start(monday);
+    let until = report::day_start(tuesday.succ_opt().unwrap());
+    let session = |todo_id: i32, started, stopped| models::Session {
+        id: 0,
+        todo_id,
+        started,
+        stopped,
+    };
+    let sessions = vec![
+        // Started before the report and runs over midnight
+        session(
+            1,
+            since - TimeDelta::hours(1),
+            Some(report::day_start(tuesday) + TimeDelta::hours(2)),
+        ),
+        // Still active, counts until now
+        session(2, until - TimeDelta::hours(1), None),
+    ];
+    let entries = report::time_entries(&sessions, since, until, until + TimeDelta::hours(5));
+    let by_day = report::group_entries(&entries, |entry| vec![entry.day]);
+    assert_eq!(
+        by_day,
+        vec![
+            (monday, report::day_start(tuesday) - since),
+            (tuesday, TimeDelta::hours(3)),
+        ]
+    );
+    // Entries with multiple keys count towards each of them, but not twice in the total
+    let by_tag = report::group_entries(&entries, |entry| match entry.todo_id {
+        1 => vec!["a", "b"],
+        _ => vec!["b"],
     });
+    assert_eq!(
+        by_tag[0].1,
+        report::day_start(tuesday) - since + TimeDelta::hours(2)
+    );
+    assert_eq!(by_tag[1].1, report::total(&entries));
+    assert_eq!(
+        report::total(&entries),
+        report::day_start(tuesday) - since + TimeDelta::hours(3)
+    );
+}
 
-    // Get the TODO ID
-    let connection = &mut establish_connection();
-    use workingon::schema::todos::dsl::*;
-    let results = todos
-        .select(workingon::models::Todos::as_select())
-        .filter(title.eq("Reopen Uncompleted Test TODO"))
-        .load(connection)
-        .expect("Error loading todos");
+#[test]
+#[serial]
+fn test_get_sessions_between() {
+    let _tmp_dir = setup_test_env();
 
-    let todo_id = encode_id(results[0].id.try_into().unwrap());
+    let todo = add_todo(&NewTodo {
+        title: "tracked",
+        notes: "",
+        created: Utc::now(),
+    })
+    .unwrap();
+    let todo_id = encode_id(todo.id.try_into().unwrap());
+    let before = Utc::now();
+    start_session(&todo_id).unwrap();
+    stop_session().unwrap();
+    start_session(&todo_id).unwrap();
+    let after = Utc::now();
+
+    assert_eq!(get_sessions_between(before, after).unwrap().len(), 2);
+    // The active session overlaps with anything after it started
+    assert_eq!(
+        get_sessions_between(after, after + TimeDelta::days(1))
+            .unwrap()
+            .len(),
+        1
+    );
+    assert!(get_sessions_between(before - TimeDelta::days(1), before)
+        .unwrap()
+        .is_empty());
 
-    // Verify it's not completed initially
-    assert!(results[0].completed.is_none());
+    add_tags(&todo_id, &["b".to_string(), "a".to_string()]).unwrap();
+    assert_eq!(
+        get_tags_by_todo().unwrap().get(&todo.id),
+        Some(&vec!["a".to_string(), "b".to_string()])
+    );
 
-    // Reopen the TODO (even though it's not completed)
-    reopen_todo(&todo_id);
+    cleanup_test_env();
+}
 
-    // Verify it's still uncompleted (completed is None)
-    let reopened_results = todos
-        .select(workingon::models::Todos::as_select())
-        .filter(id.eq(results[0].id))
-        .load(connection)
-        .expect("Error loading todos");
+#[test]
+#[serial]
+fn test_report_parse_duration() {
+    assert_eq!(report::parse_duration("90m"), Ok(TimeDelta::minutes(90)));
+    assert_eq!(report::parse_duration("2h"), Ok(TimeDelta::hours(2)));
+    assert_eq!(report::parse_duration("1h30m"), Ok(TimeDelta::minutes(90)));
+    assert_eq!(
+        report::parse_duration("1.5 hours"),
+        Ok(TimeDelta::minutes(90))
+    );
+    assert_eq!(report::parse_duration("45"), Ok(TimeDelta::minutes(45)));
+    assert!(report::parse_duration("").is_err());
+    assert!(report::parse_duration("0m").is_err());
+    assert!(report::parse_duration("2 days").is_err());
+    assert!(report::parse_duration("soon").is_err());
+}
+
+#[test]
+#[serial]
+fn test_report_estimate_accuracy() {
+    let accuracies = vec![
+        report::EstimateAccuracy {
+            todo_id: 1,
+            estimate: TimeDelta::hours(2),
+            actual: TimeDelta::hours(3),
+        },
+        report::EstimateAccuracy {
+            todo_id: 2,
+            estimate: TimeDelta::hours(1),
+            actual: TimeDelta::minutes(30),
+        },
+    ];
+    assert_eq!(accuracies[0].error(), 0.5);
+    assert_eq!(accuracies[1].error(), -0.5);
+    assert_eq!(report::mean_absolute_error(&accuracies), Some(0.5));
+    assert_eq!(report::mean_absolute_error(&[]), None);
+}
+
+#[test]
+#[serial]
+fn test_set_estimate() {
+    let _tmp_dir = setup_test_env();
+
+    let todo = add_todo(&NewTodo {
+        title: "estimated",
+        notes: "",
+        created: Utc::now(),
+    })
+    .unwrap();
+    let todo_id = encode_id(todo.id.try_into().unwrap());
+    assert_eq!(get_todo(&todo_id).unwrap().estimate_duration(), None);
+
+    set_estimate(&todo_id, Some(TimeDelta::minutes(90))).unwrap();
+    assert_eq!(get_todo(&todo_id).unwrap().estimate, Some(90));
+    assert_eq!(
+        get_todo(&todo_id).unwrap().estimate_duration(),
+        Some(TimeDelta::minutes(90))
+    );
+
+    // Next occurrences are expected to take as long
+    set_recurrence(
+        &todo_id,
+        Some(&"daily".parse::<recurrence::Recurrence>().unwrap()),
+    )
+    .unwrap();
+    let next_todo = complete_todo(&todo_id, None)
+        .unwrap()
+        .expect("No next occurrence");
+    assert_eq!(next_todo.estimate, Some(90));
 
-    assert_eq!(reopened_results.len(), 1);
-    assert!(reopened_results[0].completed.is_none());
+    set_estimate(&todo_id, None).unwrap();
+    assert_eq!(get_todo(&todo_id).unwrap().estimate, None);
 
     cleanup_test_env();
 }
 
 #[test]
-#[should_panic(expected = "Couldn't decode id")]
-fn test_decode_id_invalid_input() {
-    decode_id("invalid_id");
+#[serial]
+fn test_csv_export_import_roundtrip() {
+    let _tmp_dir = setup_test_env();
+
+    let parent = add_todo(&NewTodo {
+        title: "Parent, with a comma",
+        notes: "multi\nline",
+        created: Utc::now(),
+    })
+    .unwrap();
+    let parent_id = encode_id(parent.id.try_into().unwrap());
+    add_tags(&parent_id, &["a".to_string(), "b".to_string()]).unwrap();
+    set_priority(&parent_id, models::Priority::High).unwrap();
+    set_estimate(&parent_id, Some(TimeDelta::minutes(45))).unwrap();
+    let child = add_todo(&NewTodo {
+        title: "Child",
+        notes: "",
+        created: Utc::now(),
+    })
+    .unwrap();
+    let child_id = encode_id(child.id.try_into().unwrap());
+    set_parent(&child_id, Some(&parent_id)).unwrap();
+    complete_todo(&child_id, None).unwrap();
+
+    let context = output::TodoJsonContext::load().unwrap();
+    let todos: Vec<output::TodoJson> = get_todos()
+        .unwrap()
+        .iter()
+        .map(|todo| context.todo_json(todo))
+        .collect();
+    let mut written = Vec::new();
+    formats::csv::write_todos(&mut written, &todos).unwrap();
+
+    let rows = formats::csv::read_todos(written.as_slice()).unwrap();
+    assert_eq!(rows.len(), 2);
+    let imported: Vec<formats::ImportedTodo> =
+        rows.into_iter().map(|row| row.todo.unwrap()).collect();
+    assert_eq!(imported[0].title, "Parent, with a comma");
+    assert_eq!(imported[0].notes, "multi\nline");
+    assert_eq!(imported[0].tags, vec!["a", "b"]);
+    assert_eq!(imported[0].priority, Some(models::Priority::High));
+    assert_eq!(imported[0].estimate, Some(TimeDelta::minutes(45)));
+    assert_eq!(imported[1].parent, Some(parent_id.clone()));
+    assert!(imported[1].completed.is_some());
+
+    let added = formats::add_imported(&mut TodoStore::open().unwrap(), &imported).unwrap();
+    assert_eq!(added.len(), 2);
+    assert_eq!(added[1].parent_id, Some(added[0].id));
+    assert_eq!(added[1].completed, imported[1].completed);
+    assert_eq!(
+        get_todo_tags(&encode_id(added[0].id.try_into().unwrap())).unwrap(),
+        vec!["a", "b"]
+    );
+
+    cleanup_test_env();
 }
 
 #[test]
-fn test_constants() {
+#[serial]
+fn test_csv_import_bad_rows() {
+    let _tmp_dir = setup_test_env();
+
+    let csv = "title,due,priority,parent\n\
+               Fine,2026-10-20,low,\n\
+               ,,,\n\
+               Bad due,next tuesday,,\n\
+               Bad priority,,urgent,\n\
+               Orphan,,,missing\n";
+    let mut rows = formats::csv::read_todos(csv.as_bytes()).unwrap();
+    formats::check_references(&mut TodoStore::open().unwrap(), &mut rows).unwrap();
+    let failed: Vec<(usize, String)> = rows
+        .iter()
+        .filter_map(|row| row.todo.as_ref().err().map(|e| (row.line, e.clone())))
+        .collect();
+    assert_eq!(failed.len(), 4);
+    assert_eq!(failed[0], (3, "title is empty".to_string()));
+    assert!(failed[1].1.contains("unknown timestamp"));
+    assert!(failed[2].1.contains("unknown priority"));
+    assert!(failed[3].1.contains("parent missing isn't in the file"));
+    assert!(rows[0].todo.is_ok());
+
+    assert!(formats::csv::read_todos("name,due\nx,\n".as_bytes()).is_err());
+    assert!(formats::parse_timestamp("2026-10-18T09:00:00Z").is_ok());
+    assert!(formats::parse_timestamp("2026-10-18 09:00").is_ok());
+
+    cleanup_test_env();
+}
+
+#[test]
+fn test_todotxt_parse() {
+    use formats::todotxt::Task;
+    let task =
+        Task::parse("(A) 2026-10-01 Call mom +family +phone @home due:2026-10-25 rec:+2w id:abc");
+    assert_eq!(task.priority, Some('A'));
+    assert_eq!(task.created, chrono::NaiveDate::from_ymd_opt(2026, 10, 1));
+    assert_eq!(task.title, "Call mom");
+    assert_eq!(task.projects, vec!["family", "phone"]);
+    assert_eq!(task.contexts, vec!["home"]);
+    assert_eq!(task.due, chrono::NaiveDate::from_ymd_opt(2026, 10, 25));
+    assert_eq!(task.rec.as_deref(), Some("+2w"));
+    assert_eq!(task.id.as_deref(), Some("abc"));
+
+    let imported = task.to_imported().unwrap();
+    assert_eq!(imported.project.as_deref(), Some("family"));
+    assert_eq!(imported.tags, vec!["home", "phone"]);
+    assert_eq!(imported.priority, Some(models::Priority::High));
     assert_eq!(
-        workingon::COMMENT_DISCLAIMER,
-        "# This is a comment, lines starting with a # will be ignored"
+        imported.recurrence,
+        Some(recurrence::Recurrence::every(
+            2,
+            recurrence::Frequency::Weekly
+        ))
     );
-    assert!(!workingon::constants::BIN.is_empty());
-    assert!(!workingon::constants::DEFAULT_EDITOR.is_empty());
+
+    let done = Task::parse("x 2026-10-02 2026-10-01 Old thing pri:B");
+    assert_eq!(done.completed, chrono::NaiveDate::from_ymd_opt(2026, 10, 2));
+    assert_eq!(done.created, chrono::NaiveDate::from_ymd_opt(2026, 10, 1));
+    assert_eq!(done.priority, Some('B'));
+    assert_eq!(done.to_string(), "x 2026-10-02 2026-10-01 Old thing pri:B");
+
+    assert!(Task::parse("Bad rec rec:2x").to_imported().is_err());
+    assert!(Task::parse("(B) +project").to_imported().is_err());
 }
 
 #[test]
 #[serial]
-fn test_add_and_complete() {
+fn test_sync_todotxt() {
+    let tmp_dir = setup_test_env();
+    let path = tmp_dir.path().join("todo.txt");
+
+    let db_todo = add_todo(&NewTodo {
+        title: "From workingon",
+        notes: "",
+        created: Utc::now(),
+    })
+    .unwrap();
+    let db_id = encode_id(db_todo.id.try_into().unwrap());
+    std::fs::write(&path, "(B) From the file +home\n").unwrap();
+
+    let changes = sync::sync_todotxt(&path).unwrap();
+    let kinds: Vec<sync::SyncChangeKind> = changes.iter().map(|change| change.kind).collect();
+    assert_eq!(
+        kinds,
+        vec![
+            sync::SyncChangeKind::AddedToWorkingon,
+            sync::SyncChangeKind::AddedToFile
+        ]
+    );
+    let file_id = changes[0].id.clone().unwrap();
+    let content = std::fs::read_to_string(&path).unwrap();
+    assert!(content.contains(&format!("From the file +home id:{}", file_id)));
+    assert!(content.contains(&format!("From workingon id:{}", db_id)));
+    assert!(sync::sync_todotxt(&path).unwrap().is_empty());
+
+    // Changed on one side each
+    std::fs::write(
+        &path,
+        content.replace("From the file", "Edited in the file"),
+    )
+    .unwrap();
+    set_todo_title(&db_id, "Edited in workingon").unwrap();
+    let kinds: Vec<sync::SyncChangeKind> = sync::sync_todotxt(&path)
+        .unwrap()
+        .iter()
+        .map(|change| change.kind)
+        .collect();
+    assert_eq!(
+        kinds,
+        vec![
+            sync::SyncChangeKind::UpdatedWorkingon,
+            sync::SyncChangeKind::UpdatedFile
+        ]
+    );
+    assert_eq!(get_todo(&file_id).unwrap().title, "Edited in the file");
+    let content = std::fs::read_to_string(&path).unwrap();
+    assert!(content.contains("Edited in workingon"));
+
+    // Changed on both sides, workingon wins
+    std::fs::write(&path, content.replace("Edited in workingon", "File side")).unwrap();
+    set_todo_title(&db_id, "Workingon side").unwrap();
+    let changes = sync::sync_todotxt(&path).unwrap();
+    assert_eq!(changes[0].kind, sync::SyncChangeKind::Conflict);
+    let content = std::fs::read_to_string(&path).unwrap();
+    assert!(content.contains("Workingon side"));
+    assert!(!content.contains("File side"));
+
+    // Removed from the file, and deleted in workingon
+    let content: String = content
+        .lines()
+        .filter(|line| !line.contains("Workingon side"))
+        .map(|line| format!("{}\n", line))
+        .collect();
+    std::fs::write(&path, content).unwrap();
+    delete_todo(&file_id).unwrap();
+    let kinds: Vec<sync::SyncChangeKind> = sync::sync_todotxt(&path)
+        .unwrap()
+        .iter()
+        .map(|change| change.kind)
+        .collect();
+    assert_eq!(
+        kinds,
+        vec![
+            sync::SyncChangeKind::DeletedFromFile,
+            sync::SyncChangeKind::DeletedFromWorkingon
+        ]
+    );
+    assert!(get_todos().unwrap().is_empty());
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "");
+
+    cleanup_test_env();
+}
+
+#[test]
+#[serial]
+fn test_ics_export_import_roundtrip() {
     let _tmp_dir = setup_test_env();
-    env::set_var("EDITOR", "-"); // Ensure editor is set to dash
 
-    let created_todo = add_todo(&NewTodo {
-        title: "Closed TODO",
+    let parent = add_todo(&NewTodo {
+        title: "Parent; with, escapes and a title long enough to be folded over more than one line",
+        notes: "first\nsecond",
+        created: Utc::now(),
+    })
+    .unwrap();
+    let parent_id = encode_id(parent.id.try_into().unwrap());
+    add_tags(&parent_id, &["a,b".to_string(), "c".to_string()]).unwrap();
+    set_priority(&parent_id, models::Priority::Low).unwrap();
+    set_due(&parent_id, Some(Utc::now() + TimeDelta::days(2))).unwrap();
+    set_estimate(&parent_id, Some(TimeDelta::minutes(90))).unwrap();
+    set_recurrence(
+        &parent_id,
+        Some(&recurrence::Recurrence::every(
+            2,
+            recurrence::Frequency::Weekly,
+        )),
+    )
+    .unwrap();
+    let child = add_todo(&NewTodo {
+        title: "Child",
         notes: "",
         created: Utc::now(),
-    });
-    let id_string = encode_id(created_todo.id.try_into().unwrap());
-    complete_todo(&id_string, Some(created_todo.created));
-    let updated_todo = get_todo(&id_string);
-    assert!(updated_todo.created == updated_todo.completed.unwrap())
+    })
+    .unwrap();
+    let child_id = encode_id(child.id.try_into().unwrap());
+    set_parent(&child_id, Some(&parent_id)).unwrap();
+    complete_todo(&child_id, None).unwrap();
+
+    let context = output::TodoJsonContext::load().unwrap();
+    let todos: Vec<output::TodoJson> = get_todos()
+        .unwrap()
+        .iter()
+        .map(|todo| context.todo_json(todo))
+        .collect();
+    let written = formats::ics::write_todos(&todos);
+    assert!(written.starts_with("BEGIN:VCALENDAR\r\n"));
+    assert!(written.lines().all(|line| line.len() <= 76));
+    assert!(written.contains(&format!("UID:{}@workingon", parent_id)));
+
+    let rows = formats::ics::read_todos(&written);
+    assert_eq!(rows.len(), 2);
+    let imported: Vec<formats::ImportedTodo> =
+        rows.into_iter().map(|row| row.todo.unwrap()).collect();
+    let second = |ts: Option<chrono::DateTime<Utc>>| ts.map(|ts| ts.timestamp());
+    assert_eq!(imported[0].title, todos[0].title);
+    assert_eq!(imported[0].notes, "first\nsecond");
+    assert_eq!(imported[0].tags, vec!["a,b", "c"]);
+    assert_eq!(imported[0].priority, Some(models::Priority::Low));
+    assert_eq!(second(imported[0].due), second(todos[0].due));
+    assert_eq!(imported[0].estimate, Some(TimeDelta::minutes(90)));
+    assert_eq!(
+        imported[0].recurrence,
+        Some(recurrence::Recurrence::every(
+            2,
+            recurrence::Frequency::Weekly
+        ))
+    );
+    assert_eq!(imported[1].parent, Some(parent_id.clone()));
+    assert_eq!(second(imported[1].completed), second(todos[1].completed));
+
+    let added = formats::add_imported(&mut TodoStore::open().unwrap(), &imported).unwrap();
+    assert_eq!(added[1].parent_id, Some(added[0].id));
+    assert!(added[1].completed.is_some());
+    assert!(added[0].completed.is_none());
+
+    cleanup_test_env();
+}
+
+#[test]
+fn test_ics_import_from_other_apps() {
+    let ics = "BEGIN:VCALENDAR\r\n\
+               BEGIN:VEVENT\r\n\
+               SUMMARY:Not a TODO\r\n\
+               END:VEVENT\r\n\
+               BEGIN:VTODO\r\n\
+               UID:123-abc\r\n\
+               SUMMARY:Due on a day\r\n\
+               DUE;VALUE=DATE:20261020\r\n\
+               PRIORITY:3\r\n\
+               STATUS:COMPLETED\r\n\
+               END:VTODO\r\n\
+               BEGIN:VTODO\r\n\
+               SUMMARY:Bad due\r\n\
+               DUE:tomorrow\r\n\
+               END:VTODO\r\n\
+               BEGIN:VTODO\r\n\
+               DESCRIPTION:No summary\r\n\
+               END:VTODO\r\n\
+               END:VCALENDAR\r\n";
+    let rows = formats::ics::read_todos(ics);
+    assert_eq!(rows.len(), 3);
+    assert_eq!(rows[0].line, 5);
+    let todo = rows[0].todo.as_ref().unwrap();
+    assert_eq!(todo.source_id.as_deref(), Some("123-abc"));
+    assert_eq!(todo.priority, Some(models::Priority::High));
+    assert!(todo.completed.is_some());
+    let due = todo.due.unwrap().with_timezone(&chrono::Local);
+    assert_eq!(
+        due.date_naive(),
+        chrono::NaiveDate::from_ymd_opt(2026, 10, 20).unwrap()
+    );
+    assert_eq!(
+        due.time(),
+        chrono::NaiveTime::from_hms_opt(23, 59, 0).unwrap()
+    );
+    assert!(rows[1].todo.as_ref().unwrap_err().contains("tomorrow"));
+    assert_eq!(rows[2].todo.as_ref().unwrap_err(), "SUMMARY is empty");
+}
+
+#[test]
+fn test_taskwarrior_import() {
+    let export = r#"[
+{"id":1,"description":"Pay rent","entry":"20261001T080000Z","modified":"20261001T080000Z","due":"20261030T230000Z","priority":"H","project":"Home.Bills","status":"pending","tags":["money"],"uuid":"a1","urgency":9.1},
+{"id":0,"description":"Old","end":"20261005T120000Z","entry":"20261002T080000Z","status":"completed","uuid":"b2","annotations":[{"entry":"20261003T100000Z","description":"called them"},{"entry":"20261004T100000Z","description":"done"}]},
+{"id":0,"description":"Gone","entry":"20261002T080000Z","status":"deleted","uuid":"c3"},
+{"id":0,"description":"Water plants","entry":"20261002T080000Z","recur":"weekly","status":"recurring","uuid":"d4"},
+{"id":2,"description":"Bad","entry":"yesterday","status":"pending","uuid":"e5"}
+]
+"#;
+    let import = formats::taskwarrior::read_todos(export).unwrap();
+    assert_eq!(import.skipped, 2);
+    assert_eq!(import.rows.len(), 3);
+    assert_eq!(
+        import
+            .rows
+            .iter()
+            .map(|row| row.line)
+            .collect::<Vec<usize>>(),
+        vec![2, 3, 6]
+    );
+
+    let pay = import.rows[0].todo.as_ref().unwrap();
+    assert_eq!(pay.title, "Pay rent");
+    assert_eq!(pay.source_id.as_deref(), Some("a1"));
+    assert_eq!(pay.priority, Some(models::Priority::High));
+    assert_eq!(pay.project.as_deref(), Some("Home.Bills"));
+    assert_eq!(pay.tags, vec!["money"]);
+    assert_eq!(
+        pay.due.unwrap().to_rfc3339(),
+        "2026-10-30T23:00:00+00:00".to_string()
+    );
+    assert!(pay.completed.is_none());
+
+    let old = import.rows[1].todo.as_ref().unwrap();
+    assert_eq!(
+        old.completed.unwrap().to_rfc3339(),
+        "2026-10-05T12:00:00+00:00".to_string()
+    );
+    assert_eq!(old.notes.lines().count(), 2);
+    assert!(old.notes.ends_with("called them\n2026-10-04 done"));
+
+    assert!(import.rows[2]
+        .todo
+        .as_ref()
+        .unwrap_err()
+        .contains("yesterday"));
+
+    // Reformatted by jq, rows get the position of their task
+    let pretty = "[\n  {\n    \"description\": \"Pretty\",\n    \"status\": \"pending\"\n  }\n]";
+    let import = formats::taskwarrior::read_todos(pretty).unwrap();
+    assert_eq!(import.rows[0].line, 1);
+    assert_eq!(import.rows[0].todo.as_ref().unwrap().title, "Pretty");
+
+    assert!(formats::taskwarrior::read_todos("not json").is_err());
 }
 
 #[test]
 #[serial]
-fn test_set_due() {
+fn test_markdown_and_org_export() {
     let _tmp_dir = setup_test_env();
 
-    let created_todo = add_todo(&NewTodo {
-        title: "test_set_due",
+    let done = add_todo(&NewTodo {
+        title: "Ship it",
+        notes: "",
+        created: Utc::now(),
+    })
+    .unwrap();
+    let done_id = encode_id(done.id.try_into().unwrap());
+    complete_todo(&done_id, None).unwrap();
+    let open = add_todo(&NewTodo {
+        title: "Write update",
+        notes: "* first point\n\nsecond point",
+        created: Utc::now(),
+    })
+    .unwrap();
+    let open_id = encode_id(open.id.try_into().unwrap());
+    set_due(&open_id, Some(Utc::now() + TimeDelta::days(1))).unwrap();
+    set_priority(&open_id, models::Priority::Medium).unwrap();
+    add_tags(&open_id, &["weekly sync".to_string()]).unwrap();
+
+    let context = output::TodoJsonContext::load().unwrap();
+    let todos: Vec<output::TodoJson> = get_todos()
+        .unwrap()
+        .iter()
+        .map(|todo| context.todo_json(todo))
+        .collect();
+    let due = get_todo(&open_id)
+        .unwrap()
+        .due
+        .unwrap()
+        .with_timezone(&chrono::Local);
+
+    let markdown = formats::markdown::write_todos(&todos);
+    assert_eq!(
+        markdown,
+        format!(
+            "- [ ] Write update (due {})\n  * first point\n\n  second point\n- [x] Ship it\n",
+            due.format("%Y-%m-%d %H:%M")
+        )
+    );
+
+    let org = formats::org::write_todos(&todos);
+    let closed = get_todo(&done_id)
+        .unwrap()
+        .completed
+        .unwrap()
+        .with_timezone(&chrono::Local);
+    assert_eq!(
+        org,
+        format!(
+            "* Open\n\
+             ** TODO [#B] Write update :weekly_sync:\n   DEADLINE: <{}>\n   * first point\n\n   second point\n\
+             * Done\n\
+             ** DONE Ship it\n   CLOSED: [{}]\n",
+            due.format("%Y-%m-%d %a %H:%M"),
+            closed.format("%Y-%m-%d %a %H:%M")
+        )
+    );
+
+    cleanup_test_env();
+}
+
+#[test]
+fn test_search_match_query() {
+    assert_eq!(
+        search::match_query("deploy*  \"release notes\" don't"),
+        Some("\"deploy\"* \"release notes\" \"don't\"".to_string())
+    );
+    assert_eq!(
+        search::match_query("\"half open"),
+        Some("\"half open\"".to_string())
+    );
+    assert_eq!(
+        search::match_query("\"release notes\"*"),
+        Some("\"release notes\"*".to_string())
+    );
+    assert_eq!(search::match_query("  -- * \"\" "), None);
+}
+
+#[test]
+#[serial]
+fn test_search_todos() {
+    let _tmp_dir = setup_test_env();
+
+    let deploy = add_todo(&NewTodo {
+        title: "Deploy the release",
+        notes: "Check the dashboards first,\nthen ping the team about the rollback plan",
+        created: Utc::now(),
+    })
+    .unwrap();
+    let deploy_id = encode_id(deploy.id.try_into().unwrap());
+    let notes = add_todo(&NewTodo {
+        title: "Write release notes",
+        notes: "",
+        created: Utc::now(),
+    })
+    .unwrap();
+    let notes_id = encode_id(notes.id.try_into().unwrap());
+
+    let titles = |query: &str| -> Vec<String> {
+        search_todos(query)
+            .unwrap()
+            .into_iter()
+            .map(|found| found.todo.title)
+            .collect()
+    };
+    assert_eq!(titles("release").len(), 2);
+    assert_eq!(titles("\"release notes\""), vec!["Write release notes"]);
+    assert_eq!(titles("dashboard"), Vec::<String>::new());
+    assert_eq!(titles("dash*"), vec!["Deploy the release"]);
+
+    let found = search_todos("rollback").unwrap();
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].title, "Deploy the release");
+    assert!(found[0].snippet.contains(&format!(
+        "{}rollback{}",
+        search::HIGHLIGHT_START,
+        search::HIGHLIGHT_END
+    )));
+    let found = search_todos("deploy").unwrap();
+    assert_eq!(
+        found[0].title,
+        format!(
+            "{}Deploy{} the release",
+            search::HIGHLIGHT_START,
+            search::HIGHLIGHT_END
+        )
+    );
+    assert_eq!(found[0].snippet, "");
+
+    // The index follows edits and deletions
+    set_todo_title(&deploy_id, "Ship the build").unwrap();
+    assert_eq!(titles("deploy"), Vec::<String>::new());
+    assert_eq!(titles("ship"), vec!["Ship the build"]);
+    delete_todo(&notes_id).unwrap();
+    assert_eq!(titles("release"), Vec::<String>::new());
+
+    cleanup_test_env();
+}
+
+#[test]
+fn test_parse_filter() {
+    use filter::{Comparison, Condition, Filter};
+    let tag = |name: &str| Filter::Condition(Condition::Tag(name.to_string()));
+
+    assert_eq!(
+        filter::parse_filter("tag:a or tag:b tag:c").unwrap(),
+        Filter::Or(Box::new(tag("a")), Box::new(tag("b").and(tag("c"))))
+    );
+    assert_eq!(
+        filter::parse_filter("NOT (tag:a OR tag:b) and title~\"weekly \\\"sync\\\"\"").unwrap(),
+        Filter::Not(Box::new(Filter::Or(Box::new(tag("a")), Box::new(tag("b"))))).and(
+            Filter::Condition(Condition::TitleContains("weekly \"sync\"".to_string()))
+        )
+    );
+    assert_eq!(
+        filter::parse_filter("priority>=medium project:none estimate!=none").unwrap(),
+        Filter::Condition(Condition::Priority(
+            Comparison::Ge,
+            models::Priority::Medium
+        ))
+        .and(Filter::Condition(Condition::Project(None)))
+        .and(Filter::Condition(Condition::Estimate(Comparison::Ne, None)))
+    );
+    let Filter::Condition(Condition::Time(filter::TimeField::Due, Comparison::Lt, Some(moment))) =
+        filter::parse_filter("due<2026-10-23").unwrap()
+    else {
+        panic!("due<2026-10-23 isn't a due condition");
+    };
+    assert_eq!(
+        moment.start,
+        report::day_start(chrono::NaiveDate::from_ymd_opt(2026, 10, 23).unwrap())
+    );
+    assert_eq!(moment.end - moment.start, TimeDelta::days(1));
+
+    for bad in [
+        "",
+        "tag:",
+        "tag",
+        "tag:a or",
+        "(tag:a",
+        "tag:a)",
+        "color:red",
+        "due>none",
+        "due<someday",
+        "tag<a",
+        "title:a",
+        "priority>urgent",
+        "notes~\"open",
+    ] {
+        assert!(filter::parse_filter(bad).is_err(), "{} should fail", bad);
+    }
+}
+
+#[test]
+#[serial]
+fn test_get_todos_where() {
+    let _tmp_dir = setup_test_env();
+
+    let mut ids = Vec::new();
+    for title in ["Weekly meeting", "Fix 100% of bugs", "Groceries", "Done"] {
+        let todo = add_todo(&NewTodo {
+            title,
+            notes: "",
+            created: Utc::now(),
+        })
+        .unwrap();
+        ids.push(encode_id(todo.id.try_into().unwrap()));
+    }
+    add_tags(&ids[0], &["work".to_string()]).unwrap();
+    add_tags(&ids[1], &["work".to_string()]).unwrap();
+    set_due(&ids[0], Some(Utc::now() + TimeDelta::days(10))).unwrap();
+    set_due(&ids[1], Some(Utc::now() + TimeDelta::hours(1))).unwrap();
+    set_priority(&ids[1], models::Priority::High).unwrap();
+    set_estimate(&ids[2], Some(TimeDelta::minutes(30))).unwrap();
+    complete_todo(&ids[3], None).unwrap();
+
+    let titles = |filter: &str| -> Vec<String> {
+        get_todos_where(Some(&filter::parse_filter(filter).unwrap()))
+            .unwrap()
+            .into_iter()
+            .map(|todo| todo.title)
+            .collect()
+    };
+    assert_eq!(get_todos_where(None).unwrap().len(), 4);
+    assert_eq!(
+        titles("due<\"5 days\" and tag:work and not title~meeting"),
+        vec!["Fix 100% of bugs"]
+    );
+    assert_eq!(titles("due=none status:open"), vec!["Groceries"]);
+    assert_eq!(
+        titles("not due<\"5 days\""),
+        vec!["Weekly meeting", "Groceries", "Done"]
+    );
+    assert_eq!(titles("title~\"100%\""), vec!["Fix 100% of bugs"]);
+    assert_eq!(titles("title~\"%\" or title~_"), vec!["Fix 100% of bugs"]);
+    assert_eq!(
+        titles("priority=high or estimate<=30m"),
+        vec!["Fix 100% of bugs", "Groceries"]
+    );
+    assert_eq!(titles("estimate>30m"), Vec::<String>::new());
+    assert_eq!(titles("completed>=today"), vec!["Done"]);
+
+    cleanup_test_env();
+}
+
+#[test]
+#[serial]
+fn test_save_view() {
+    let _tmp_dir = setup_test_env();
+
+    assert!(get_view("today").unwrap().is_none());
+    let view = save_view(&models::NewView {
+        name: "today",
+        status: "open",
+        tags: "[\"work\",\"on call\"]".to_string(),
+        filter: Some("due<tomorrow"),
+        sort: Some("urgency"),
+        show_time: true,
+        list_columns: Some("id,due,title"),
+        wide: true,
+    })
+    .unwrap();
+    assert_eq!(view.tag_names().unwrap(), vec!["work", "on call"]);
+    assert_eq!(view.list_columns.as_deref(), Some("id,due,title"));
+
+    // Saving under the same name replaces the view
+    save_view(&models::NewView {
+        name: "today",
+        status: "all",
+        tags: "[]".to_string(),
+        filter: None,
+        sort: None,
+        show_time: false,
+        list_columns: None,
+        wide: false,
+    })
+    .unwrap();
+    let views = get_views().unwrap();
+    assert_eq!(views.len(), 1);
+    assert_eq!(views[0].id, view.id);
+    assert_eq!(views[0].status, "all");
+    assert_eq!(views[0].filter, None);
+    assert_eq!(views[0].sort, None);
+    assert_eq!(views[0].list_columns, None);
+    assert!(!views[0].wide);
+
+    assert!(delete_view("today").unwrap());
+    assert!(!delete_view("today").unwrap());
+    assert!(get_views().unwrap().is_empty());
+
+    cleanup_test_env();
+}
+
+// Runs the same operations against a storage backend, they all have to behave alike
+fn check_storage(storage: &mut impl Storage) {
+    use workingon::recurrence::{Frequency, Recurrence};
+
+    let first = storage
+        .add_todo(&NewTodo {
+            title: "First",
+            notes: "",
+            created: Utc::now(),
+        })
+        .unwrap();
+    let first_id = encode_id(first.id.try_into().unwrap());
+    let second = storage
+        .add_todo(&NewTodo {
+            title: "Second",
+            notes: "some notes",
+            created: Utc::now(),
+        })
+        .unwrap();
+    let second_id = encode_id(second.id.try_into().unwrap());
+    assert_ne!(first.id, second.id);
+
+    storage.set_todo_title(&first_id, "Renamed").unwrap();
+    storage
+        .set_priority(&first_id, models::Priority::High)
+        .unwrap();
+    storage
+        .set_estimate(&first_id, Some(TimeDelta::minutes(90)))
+        .unwrap();
+    storage
+        .add_tags(&first_id, &["work".to_string(), "home".to_string()])
+        .unwrap();
+    storage
+        .remove_tags(&first_id, &["home".to_string()])
+        .unwrap();
+    assert!(matches!(
+        storage.remove_tags("zzzzz", &["home".to_string()]),
+        Err(Error::NotFound { .. })
+    ));
+    let found = storage.get_todo(&first_id).unwrap();
+    assert_eq!(found.title, "Renamed");
+    assert_eq!(found.priority, models::Priority::High.level());
+    assert_eq!(found.estimate, Some(90));
+    assert_eq!(storage.get_todo_tags(&first_id).unwrap(), vec!["work"]);
+
+    // Completing a recurring TODO spawns its next occurrence, with the same tags
+    let due = Utc::now();
+    storage.set_due(&first_id, Some(due)).unwrap();
+    storage
+        .set_recurrence(&first_id, Some(&Recurrence::every(1, Frequency::Daily)))
+        .unwrap();
+    let next = storage.complete_todo(&first_id, None).unwrap().unwrap();
+    assert_eq!(next.template_id, Some(first.id));
+    assert_eq!(next.due, Some(due + TimeDelta::days(1)));
+    let next_id = encode_id(next.id.try_into().unwrap());
+    assert_eq!(storage.get_todo_tags(&next_id).unwrap(), vec!["work"]);
+    // Completing it again doesn't, and keeps the first completion time
+    let completed = storage.get_todo(&first_id).unwrap().completed;
+    assert!(storage
+        .complete_todo(&first_id, Some(Utc::now() + TimeDelta::days(1)))
+        .unwrap()
+        .is_none());
+    assert_eq!(storage.get_todo(&first_id).unwrap().completed, completed);
+    storage.reopen_todo(&first_id).unwrap();
+    assert!(storage.get_todo(&first_id).unwrap().completed.is_none());
+
+    storage.delete_todo(&first_id).unwrap();
+    assert!(storage.get_todo(&next_id).unwrap().template_id.is_none());
+    assert!(matches!(
+        storage.get_todo(&first_id),
+        Err(Error::NotFound { .. })
+    ));
+    assert!(matches!(
+        storage.set_due(&first_id, None),
+        Err(Error::NotFound { .. })
+    ));
+    assert!(matches!(storage.get_todo("!!"), Err(Error::InvalidId(_))));
+    let titles: Vec<String> = storage
+        .get_todos()
+        .unwrap()
+        .into_iter()
+        .map(|todo| todo.title)
+        .collect();
+    assert_eq!(titles, vec!["Second", "Renamed"]);
+    assert_eq!(storage.get_todo(&second_id).unwrap().notes, "some notes");
+}
+
+// These don't touch the data folder, so they don't need to run serially
+#[test]
+fn test_memory_storage() {
+    check_storage(&mut storage::MemoryStorage::new());
+}
+
+#[test]
+fn test_json_storage() {
+    let tmp_dir = TempDir::new("workingon_test").expect("cannot make temp directory for test");
+    let path = tmp_dir.path().join("todos.json");
+    check_storage(&mut storage::JsonStorage::open(&path).unwrap());
+
+    // Everything was written to the file
+    let mut reopened = storage::JsonStorage::open(&path).unwrap();
+    assert_eq!(reopened.get_todos().unwrap().len(), 2);
+
+    // A change that can't be written isn't kept
+    let mut unwritable =
+        storage::JsonStorage::open(&tmp_dir.path().join("missing/todos.json")).unwrap();
+    let result = unwritable.add_todo(&NewTodo {
+        title: "Lost",
         notes: "",
         created: Utc::now(),
     });
-    let id_string = encode_id(created_todo.id.try_into().unwrap());
-    let a_week_later = created_todo.created + TimeDelta::seconds(60 * 60 * 24 * 7);
-    set_due(&id_string, Some(a_week_later));
-    let updated_todo = get_todo(&id_string);
-    assert!(updated_todo.due.unwrap() == a_week_later);
-    set_due(&id_string, None);
-    let updated_todo = get_todo(&id_string);
-    assert!(updated_todo.due.is_none());
+    assert!(matches!(result, Err(Error::Io { .. })));
+    assert!(unwritable.get_todos().unwrap().is_empty());
+}
+
+#[test]
+fn test_sqlite_storage() {
+    let tmp_dir = TempDir::new("workingon_test").expect("cannot make temp directory for test");
+    check_storage(&mut TodoStore::open_at(&tmp_dir.path().join("todos.sqlite3")).unwrap());
+}
+
+#[test]
+#[serial]
+fn test_undo_redo() {
+    let tmp_dir = setup_test_env();
+    let mut store = TodoStore::open_at(&tmp_dir.path().join("undo.sqlite3")).unwrap();
+    let add = |store: &mut TodoStore, name: &str| {
+        let added = store
+            .add_todo(&NewTodo {
+                title: name,
+                notes: "",
+                created: Utc::now(),
+            })
+            .unwrap();
+        encode_id(added.id.try_into().unwrap())
+    };
+    let parent = add(&mut store, "Parent");
+    let blocker = add(&mut store, "Blocker");
+    let subtask = add(&mut store, "Subtask");
+    store.add_tags(&parent, &["work".to_string()]).unwrap();
+    store.add_dependency(&parent, &blocker).unwrap();
+    store.set_parent(&subtask, Some(&parent)).unwrap();
+    store.delete_todo(&parent).unwrap();
+    assert!(store.get_todo(&parent).is_err());
+
+    let undone = store.undo(1).unwrap();
+    assert_eq!(undone.len(), 1);
+    assert_eq!(undone[0].description, format!("delete {}", parent));
+    // The TODO comes back with its id and everything that pointed to it
+    assert_eq!(store.get_todo(&parent).unwrap().title, "Parent");
+    assert_eq!(store.get_todo_tags(&parent).unwrap(), vec!["work"]);
+    assert_eq!(store.get_blockers(&parent).unwrap()[0].title, "Blocker");
+    assert_eq!(store.get_subtasks(&parent).unwrap()[0].title, "Subtask");
+
+    let redone = store.redo(1).unwrap();
+    assert_eq!(redone.len(), 1);
+    assert!(store.get_todo(&parent).is_err());
+    assert!(store.redo(1).unwrap().is_empty());
+
+    // Several changes can be undone at once, newest first
+    let undone = store.undo(2).unwrap();
+    assert_eq!(undone[1].description, format!("set parent of {}", subtask));
+    assert!(store.get_subtasks(&parent).unwrap().is_empty());
+    // A new change can't be followed by redoing older ones
+    store.set_todo_title(&blocker, "Renamed").unwrap();
+    assert!(store.redo(1).unwrap().is_empty());
+    assert_eq!(
+        store.undo(1).unwrap()[0].description,
+        format!("set title of {}", blocker)
+    );
+    assert_eq!(store.get_todo(&blocker).unwrap().title, "Blocker");
+
+    // An operation is undone as a whole
+    store
+        .operation("add two", |store| {
+            add(store, "First");
+            add(store, "Second");
+            Ok::<_, Error>(())
+        })
+        .unwrap();
+    assert_eq!(store.get_todos().unwrap().len(), 5);
+    store.undo(1).unwrap();
+    assert_eq!(store.get_todos().unwrap().len(), 3);
+    cleanup_test_env();
 }